@@ -0,0 +1,411 @@
+//! Benchmarks for finding navigable distances.
+//!
+//! "cargo bench --bench apsp" runs them.  Each "_hashmap" benchmark runs
+//! the same searches with the hash map adjacency graph that came before the
+//! compressed sparse row Graph, for comparison.
+//!
+//! The imperium benchmarks need the
+//! sector data for sector_lists/Im.txt to already be in the data directory
+//! ($TRADERUST_DATA_DIR, or the default cache directory), for example from
+//! an earlier "traderust -f sector_lists/Im.txt" run.  If
+//! $TRADERUST_DATA_DIR is set and the data isn't there, that's an error;
+//! otherwise they are skipped with a warning, and only the synthetic
+//! benchmarks run.
+
+use anyhow::Result;
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion};
+use rand::prelude::*;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+
+use traderust::apsp::{Algorithm, Graph, ShortestPaths};
+use traderust::{default_data_dir, parse_file_of_sectors, Galaxy, TradeModel};
+
+const ALGORITHMS: [Algorithm; 3] = [Algorithm::Dial, Algorithm::Dijkstra, Algorithm::Radix];
+
+/// The algorithms the hash map graph had.
+const HASHMAP_ALGORITHMS: [Algorithm; 2] = [Algorithm::Dial, Algorithm::Dijkstra];
+
+/// Searches over the jump graph as it was before the compressed sparse row
+/// Graph: adjacency sets and edge weights in hash maps, and a hash map of
+/// settled nodes per search.
+mod hashmap {
+    use ::bucket_queue::*;
+    use std::cmp::Reverse;
+    use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+    use traderust::apsp::{Algorithm, Graph, INFINITY, NO_PRED_NODE};
+
+    pub type Settled = HashMap<u32, (u32, u32)>;
+
+    pub struct HashGraph {
+        neighbors_map: HashMap<u32, HashSet<u32>>,
+        weights: HashMap<(u32, u32), u32>,
+    }
+
+    impl HashGraph {
+        pub fn from_graph(graph: &Graph) -> HashGraph {
+            let mut neighbors_map: HashMap<u32, HashSet<u32>> = HashMap::new();
+            let mut weights = HashMap::new();
+            for u in 0..graph.size() as u32 {
+                for (v, weight) in graph.neighbors(u) {
+                    neighbors_map.entry(u).or_default().insert(v);
+                    weights.insert((u, v), weight);
+                }
+            }
+            HashGraph {
+                neighbors_map,
+                weights,
+            }
+        }
+    }
+
+    fn dijkstra_one_row(start: u32, graph: &HashGraph, targets: Option<&HashSet<u32>>) -> Settled {
+        let mut tentative: Settled = HashMap::new();
+        let mut settled: Settled = HashMap::new();
+        let mut remaining = targets.map(|targets| targets.len());
+        let mut heap = BinaryHeap::new();
+
+        tentative.insert(start, (0, NO_PRED_NODE));
+        heap.push(Reverse((0, start)));
+
+        while let Some(Reverse((priority, u))) = heap.pop() {
+            let (dist_u, pred_u) = tentative[&u];
+            if priority != dist_u || settled.contains_key(&u) {
+                continue;
+            }
+            settled.insert(u, (dist_u, pred_u));
+            if let (Some(targets), Some(count)) = (targets, remaining.as_mut()) {
+                if targets.contains(&u) {
+                    *count -= 1;
+                    if *count == 0 {
+                        break;
+                    }
+                }
+            }
+            if let Some(neighbors) = graph.neighbors_map.get(&u) {
+                for v in neighbors {
+                    let weight = graph.weights.get(&(u, *v)).unwrap();
+                    let alt = dist_u + weight;
+                    let dist_v = tentative.get(v).map_or(INFINITY, |(dist, _)| *dist);
+                    if alt < dist_v {
+                        tentative.insert(*v, (alt, u));
+                        heap.push(Reverse((alt, *v)));
+                    }
+                }
+            }
+        }
+
+        settled
+    }
+
+    fn dial_one_row(start: u32, graph: &HashGraph, targets: Option<&HashSet<u32>>) -> Settled {
+        let mut tentative: Settled = HashMap::new();
+        let mut settled: Settled = HashMap::new();
+        let mut remaining = targets.map(|targets| targets.len());
+        let mut queue = BucketQueue::<VecDeque<u32>>::new();
+
+        tentative.insert(start, (0, NO_PRED_NODE));
+        queue.enqueue(start, 0);
+
+        while let Some(priority) = queue.min_priority() {
+            let u = queue.dequeue_min().unwrap();
+            let (dist_u, pred_u) = tentative[&u];
+            if priority != dist_u as usize || settled.contains_key(&u) {
+                continue;
+            }
+            settled.insert(u, (dist_u, pred_u));
+            if let (Some(targets), Some(count)) = (targets, remaining.as_mut()) {
+                if targets.contains(&u) {
+                    *count -= 1;
+                    if *count == 0 {
+                        break;
+                    }
+                }
+            }
+            if let Some(neighbors) = graph.neighbors_map.get(&u) {
+                for v in neighbors {
+                    let weight = graph.weights.get(&(u, *v)).unwrap();
+                    let alt = dist_u + weight;
+                    let dist_v = tentative.get(v).map_or(INFINITY, |(dist, _)| *dist);
+                    if alt < dist_v {
+                        tentative.insert(*v, (alt, u));
+                        queue.enqueue(*v, alt as usize);
+                    }
+                }
+            }
+        }
+
+        settled
+    }
+
+    pub fn one_row(
+        start: u32,
+        graph: &HashGraph,
+        targets: Option<&HashSet<u32>>,
+        alg: Algorithm,
+    ) -> Settled {
+        match alg {
+            Algorithm::Dijkstra => dijkstra_one_row(start, graph, targets),
+            Algorithm::Dial => dial_one_row(start, graph, targets),
+            _ => panic!("invalid Algorithm"),
+        }
+    }
+
+    /// Keep only the paths back to the source from each target, as
+    /// ShortestPaths::prepare did.
+    pub fn keep_paths(settled: &Settled, targets: &HashSet<u32>) -> Settled {
+        let mut kept = HashMap::new();
+        for target in targets {
+            let mut node = *target;
+            while let Some(entry) = settled.get(&node) {
+                if kept.insert(node, *entry).is_some() {
+                    break;
+                }
+                node = entry.1;
+            }
+            kept.entry(*target).or_insert((INFINITY, NO_PRED_NODE));
+        }
+        kept
+    }
+}
+
+use hashmap::HashGraph;
+
+/// Benchmark preparing source_to_targets, and up to 100 full searches to
+/// far, with each algorithm over graph, and the same with the hash map
+/// graph.
+fn bench_searches(
+    group: &mut BenchmarkGroup<WallTime>,
+    graph: &Graph,
+    source_to_targets: &HashMap<u32, HashSet<u32>>,
+    far: u32,
+) {
+    for alg in ALGORITHMS {
+        group.bench_with_input(
+            BenchmarkId::new("prepare", format!("{:?}", alg)),
+            &alg,
+            |b, alg| {
+                b.iter(|| {
+                    let paths = ShortestPaths::new(graph.clone(), *alg);
+                    paths.prepare(source_to_targets);
+                    paths
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("full_search_x100", format!("{:?}", alg)),
+            &alg,
+            |b, alg| {
+                b.iter(|| {
+                    let paths = ShortestPaths::new(graph.clone(), *alg);
+                    for source in 0..far.min(100) {
+                        paths.distance(source, far);
+                    }
+                    paths
+                })
+            },
+        );
+    }
+
+    let hash_graph = HashGraph::from_graph(graph);
+    for alg in HASHMAP_ALGORITHMS {
+        group.bench_with_input(
+            BenchmarkId::new("prepare_hashmap", format!("{:?}", alg)),
+            &alg,
+            |b, alg| {
+                b.iter(|| {
+                    source_to_targets
+                        .par_iter()
+                        .map(|(source, targets)| {
+                            let settled =
+                                hashmap::one_row(*source, &hash_graph, Some(targets), *alg);
+                            (*source, hashmap::keep_paths(&settled, targets))
+                        })
+                        .collect::<HashMap<u32, hashmap::Settled>>()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("full_search_x100_hashmap", format!("{:?}", alg)),
+            &alg,
+            |b, alg| {
+                b.iter(|| {
+                    (0..far.min(100))
+                        .map(|source| {
+                            hashmap::one_row(source, &hash_graph, None, *alg)
+                                .get(&far)
+                                .copied()
+                        })
+                        .collect::<Vec<Option<(u32, u32)>>>()
+                })
+            },
+        );
+    }
+}
+
+/// Return the distance in hexes between two (column, row) hexes, with odd
+/// columns shifted down half a hex, as in Traveller sector maps.
+fn hex_distance((col1, row1): (i64, i64), (col2, row2): (i64, i64)) -> i64 {
+    let z1 = row1 - (col1 - (col1 & 1)) / 2;
+    let z2 = row2 - (col2 - (col2 & 1)) / 2;
+    let dx = col1 - col2;
+    let dz = z1 - z2;
+    (dx.abs() + dz.abs() + (dx + dz).abs()) / 2
+}
+
+/// A random map with worlds in about half the hexes of a width by height
+/// grid, and edges up to max_jump between them, like a dense part of charted
+/// space.
+fn hex_map(width: i64, height: i64, max_jump: i64) -> (Vec<(i64, i64)>, Graph) {
+    let mut rng = StdRng::seed_from_u64(1105);
+    let mut hexes = Vec::new();
+    for col in 0..width {
+        for row in 0..height {
+            if rng.gen_bool(0.5) {
+                hexes.push((col, row));
+            }
+        }
+    }
+    let hex_to_index: HashMap<(i64, i64), u32> = hexes
+        .iter()
+        .enumerate()
+        .map(|(ii, hex)| (*hex, ii as u32))
+        .collect();
+    let mut edges = Vec::new();
+    for (ii, (col, row)) in hexes.iter().enumerate() {
+        for col2 in col - max_jump..=col + max_jump {
+            for row2 in row - max_jump..=row + max_jump {
+                let distance = hex_distance((*col, *row), (col2, row2));
+                if distance == 0 || distance > max_jump {
+                    continue;
+                }
+                if let Some(jj) = hex_to_index.get(&(col2, row2)) {
+                    edges.push((ii as u32, *jj, distance as u32));
+                }
+            }
+        }
+    }
+    (hexes.clone(), Graph::from_edges(hexes.len(), &edges))
+}
+
+fn bench_hex_map(c: &mut Criterion) {
+    let (hexes, graph) = hex_map(128, 160, 2);
+    // Every world paired with every world up to 12 hexes away, which is
+    // about as far as trade routes reach.
+    let mut source_to_targets: HashMap<u32, HashSet<u32>> = HashMap::new();
+    for (ii, hex1) in hexes.iter().enumerate() {
+        for (jj, hex2) in hexes.iter().enumerate().skip(ii + 1) {
+            if hex2.0 - hex1.0 > 12 {
+                break;
+            }
+            if hex_distance(*hex1, *hex2) <= 12 {
+                source_to_targets
+                    .entry(ii as u32)
+                    .or_default()
+                    .insert(jj as u32);
+            }
+        }
+    }
+    let far = hexes.len() as u32 - 1;
+
+    let mut group = c.benchmark_group("hex_map");
+    group.sample_size(10);
+    bench_searches(&mut group, &graph, &source_to_targets, far);
+    group.finish();
+}
+
+/// All pairs in a small region with jump-6 edges, where a dense algorithm
+/// can keep up with repeated single-source searches.
+fn bench_dense_region(c: &mut Criterion) {
+    let (hexes, graph) = hex_map(32, 40, 6);
+    let last = hexes.len() as u32 - 1;
+
+    let mut group = c.benchmark_group("dense_region");
+    group.sample_size(10);
+    for alg in [Algorithm::Dial, Algorithm::BlockedFloyd] {
+        group.bench_with_input(
+            BenchmarkId::new("all_pairs", format!("{:?}", alg)),
+            &alg,
+            |b, alg| {
+                b.iter(|| {
+                    let paths = ShortestPaths::new(graph.clone(), *alg);
+                    for source in 0..=last {
+                        paths.distance(source, last);
+                    }
+                    paths
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Load the galaxy for sector_lists/Im.txt from data_dir, without
+/// downloading anything.
+fn load_imperium(data_dir: &Path) -> Result<Galaxy> {
+    let sector_names = parse_file_of_sectors(PathBuf::from("sector_lists/Im.txt"))?;
+    Galaxy::builder()
+        .data_directory(data_dir)
+        .offline(true)
+        .sectors(sector_names)
+        .build()
+}
+
+fn bench_imperium(c: &mut Criterion) {
+    let required = env::var_os("TRADERUST_DATA_DIR");
+    let data_dir = match &required {
+        Some(dir) => PathBuf::from(dir),
+        None => default_data_dir(),
+    };
+    let galaxy = match load_imperium(&data_dir) {
+        Ok(galaxy) => galaxy,
+        Err(err) if required.is_some() => {
+            panic!("Can't run imperium benchmarks: {:#}", err);
+        }
+        Err(err) => {
+            eprintln!(
+                "\nWARNING: imperium benchmarks NOT run: {:#}\n\
+                 Run \"traderust -d {} -f sector_lists/Im.txt\" to download \
+                 the sectors, or set $TRADERUST_DATA_DIR to where they are.\n",
+                err,
+                data_dir.display()
+            );
+            return;
+        }
+    };
+
+    // Time only the shortest path searches, over the jump graph that a
+    // trade run builds, with every world paired with every world up to 12
+    // parsecs away.
+    let trade_map = TradeModel::new()
+        .compute(galaxy)
+        .expect("Can't compute imperium trade routes");
+    let graph = trade_map.shortest_paths().graph().clone();
+    let hash_graph = HashGraph::from_graph(&graph);
+    let source_to_targets: HashMap<u32, HashSet<u32>> = (0..graph.size() as u32)
+        .into_par_iter()
+        .map(|source| {
+            let settled = hashmap::one_row(source, &hash_graph, None, Algorithm::Dial);
+            let targets = settled
+                .iter()
+                .filter(|(node, (dist, _))| **node > source && *dist <= 12)
+                .map(|(node, _)| *node)
+                .collect();
+            (source, targets)
+        })
+        .filter(|(_, targets): &(u32, HashSet<u32>)| !targets.is_empty())
+        .collect();
+    let far = graph.size() as u32 - 1;
+
+    let mut group = c.benchmark_group("imperium");
+    group.sample_size(10);
+    bench_searches(&mut group, &graph, &source_to_targets, far);
+    group.finish();
+}
+
+criterion_group!(benches, bench_hex_map, bench_dense_region, bench_imperium);
+criterion_main!(benches);
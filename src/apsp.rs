@@ -1,5 +1,7 @@
 use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::io::{self, Read, Write};
+use std::sync::RwLock;
 
 use rayon::prelude::*;
 
@@ -11,26 +13,31 @@ use clap::ArgEnum;
 extern crate ndarray;
 use ndarray::Array2;
 
-pub const INFINITY: u16 = u16::MAX;
-pub const NO_PRED_NODE: u16 = INFINITY - 1;
+pub const INFINITY: u32 = u32::MAX;
+pub const NO_PRED_NODE: u32 = INFINITY - 1;
+
+/// Start of a file written by ShortestPaths::write_rows.  Bump the number
+/// when the format or the meaning of the rows changes.
+const CACHE_MAGIC: &[u8; 8] = b"TRAPSP01";
 
 #[derive(ArgEnum, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Algorithm {
     Dijkstra,
     Dial,
     Floyd,
+    BlockedFloyd,
+    Radix,
 }
 
-/// Floyd-Warshall is a simple O(V^3) algorithm, where V is the number of
-/// vertexes.  We iterate over i, j, and k.  If dist[i, j] > dist[i, k] +
-/// dist[k, j] then we set dist[i, j] to that sum, and set pred[i, j] to
-/// pred[k, j] to show that the shortest path now runs through that node.  This
-/// implementation is currently single-threaded.  Even if it were
-/// multi-threaded, Floyd-Warshall is so much slower than Dijkstra for sparse
-/// matrixes (E << V^2) that it should not be used except for testing.
-fn floyd_warshall(dist: &mut Array2<u16>) -> Array2<u16> {
+/// Side length of the square tiles that blocked_floyd_warshall works on.
+/// 64 * 64 u32 distances plus predecessors fit comfortably in L2 cache.
+const FLOYD_BLOCK_SIZE: usize = 64;
+
+/// Make dist symmetric, with missing edges at INFINITY and each vertex at
+/// zero distance to itself, and return the initial predecessors for it.
+fn floyd_warshall_init(dist: &mut Array2<u32>) -> Array2<u32> {
     let size = dist.nrows();
-    let mut pred = Array2::<u16>::from_elem((size, size), NO_PRED_NODE);
+    let mut pred = Array2::<u32>::from_elem((size, size), NO_PRED_NODE);
 
     // Set all zero vertexes to infinity
     for i in 0..size {
@@ -59,11 +66,25 @@ fn floyd_warshall(dist: &mut Array2<u16>) -> Array2<u16> {
     for i in 0..size {
         for j in 0..size {
             if dist[[i, j]] > 0 && dist[[i, j]] < INFINITY {
-                pred[[i, j]] = i as u16;
+                pred[[i, j]] = i as u32;
             }
         }
     }
 
+    pred
+}
+
+/// Floyd-Warshall is a simple O(V^3) algorithm, where V is the number of
+/// vertexes.  We iterate over i, j, and k.  If dist[i, j] > dist[i, k] +
+/// dist[k, j] then we set dist[i, j] to that sum, and set pred[i, j] to
+/// pred[k, j] to show that the shortest path now runs through that node.  This
+/// implementation is single-threaded.  Floyd-Warshall is so much slower than
+/// Dijkstra for sparse matrixes (E << V^2) that it should not be used except
+/// for testing; see blocked_floyd_warshall for a faster version.
+fn floyd_warshall(dist: &mut Array2<u32>) -> Array2<u32> {
+    let size = dist.nrows();
+    let mut pred = floyd_warshall_init(dist);
+
     // Do the Floyd Warshall triple nested loop
     for k in 0..size {
         for i in 0..size {
@@ -81,174 +102,485 @@ fn floyd_warshall(dist: &mut Array2<u16>) -> Array2<u16> {
     pred
 }
 
-fn dijkstra_one_row(
-    start: u16,
-    size: usize,
-    neighbors_map: &HashMap<u16, HashSet<u16>>,
-    weights: &HashMap<(u16, u16), u16>,
-) -> (Vec<u16>, Vec<u16>) {
-    let mut dist_row = vec![INFINITY; size];
-    let mut pred_row = vec![NO_PRED_NODE; size];
-
-    // TODO Try a Fibonacci heap instead
-    let mut heap = BinaryHeap::new();
-
-    dist_row[start as usize] = 0;
-    heap.push(Reverse((0, start)));
+/// A square tile of the distance and predecessor matrixes, block_size on a
+/// side, stored row-major.  Predecessors are global node indexes.
+#[derive(Clone, Debug)]
+struct Tile {
+    dist: Vec<u32>,
+    pred: Vec<u32>,
+}
 
-    while !heap.is_empty() {
-        if let Some(Reverse((priority, u))) = heap.pop() {
-            if priority == dist_row[u as usize] as u16 {
-                if let Some(neighbors) = neighbors_map.get(&u) {
-                    for v in neighbors {
-                        let weight = weights.get(&(u, *v)).unwrap();
-                        let alt = dist_row[u as usize] as u16 + weight;
-                        if alt < (dist_row[*v as usize]) as u16 {
-                            dist_row[*v as usize] = alt as u16;
-                            pred_row[*v as usize] = u as u16;
-                            let tup = (alt, *v);
-                            heap.push(Reverse(tup));
-                        }
+impl Tile {
+    /// Run the Floyd-Warshall triple nested loop within this tile, which must
+    /// be on the diagonal, using only its own nodes as intermediates.
+    fn close(&mut self, block_size: usize) {
+        let b = block_size;
+        for k in 0..b {
+            for i in 0..b {
+                let dik = self.dist[i * b + k];
+                if dik == INFINITY {
+                    continue;
+                }
+                for j in 0..b {
+                    let dkj = self.dist[k * b + j];
+                    if dkj != INFINITY && self.dist[i * b + j] > dik + dkj {
+                        self.dist[i * b + j] = dik + dkj;
+                        self.pred[i * b + j] = self.pred[k * b + j];
                     }
                 }
             }
         }
     }
 
-    (dist_row, pred_row)
+    /// Shorten the paths in this tile from i to j by going from i to k in
+    /// left, then from k to j in right.
+    fn relax_through(&mut self, left: &Tile, right: &Tile, block_size: usize) {
+        let b = block_size;
+        for i in 0..b {
+            for k in 0..b {
+                let dik = left.dist[i * b + k];
+                if dik == INFINITY {
+                    continue;
+                }
+                for j in 0..b {
+                    let dkj = right.dist[k * b + j];
+                    if dkj != INFINITY && self.dist[i * b + j] > dik + dkj {
+                        self.dist[i * b + j] = dik + dkj;
+                        self.pred[i * b + j] = right.pred[k * b + j];
+                    }
+                }
+            }
+        }
+    }
 }
 
-fn dial_one_row(
-    start: u16,
-    size: usize,
-    neighbors_map: &HashMap<u16, HashSet<u16>>,
-    weights: &HashMap<(u16, u16), u16>,
-) -> (Vec<u16>, Vec<u16>) {
-    let mut dist_row = vec![INFINITY; size];
-    let mut pred_row = vec![NO_PRED_NODE; size];
+/// Blocked Floyd-Warshall splits the matrixes into square tiles and, for each
+/// diagonal tile kb in turn, does the work of the k loop for all of kb's
+/// nodes at once, in three phases.  First the diagonal tile on its own.  Then
+/// the rest of row kb and column kb, which only need themselves and the
+/// diagonal tile, in parallel.  Then all the other tiles, which only need
+/// their tiles in row kb and column kb, in parallel.  It's still O(V^3), but
+/// each tile fits in cache and the later phases use every core, so it's a
+/// reasonable cross-check, and competitive when the graph is dense (high
+/// max jump in a crowded region).
+fn blocked_floyd_warshall(dist: &mut Array2<u32>, block_size: usize) -> Array2<u32> {
+    let size = dist.nrows();
+    let mut pred = floyd_warshall_init(dist);
+    let b = block_size;
+    let blocks = size.div_ceil(b);
 
-    let mut queue = BucketQueue::<VecDeque<u16>>::new();
+    // The last row and column of tiles are padded with unreachable nodes.
+    let mut tiles: Vec<Tile> = (0..blocks * blocks)
+        .map(|t| {
+            let (bi, bj) = (t / blocks, t % blocks);
+            let mut tile = Tile {
+                dist: vec![INFINITY; b * b],
+                pred: vec![NO_PRED_NODE; b * b],
+            };
+            for i in 0..b.min(size - bi * b) {
+                for j in 0..b.min(size - bj * b) {
+                    tile.dist[i * b + j] = dist[[bi * b + i, bj * b + j]];
+                    tile.pred[i * b + j] = pred[[bi * b + i, bj * b + j]];
+                }
+            }
+            tile
+        })
+        .collect();
 
-    dist_row[start as usize] = 0;
-    queue.enqueue(start, 0);
+    for kb in 0..blocks {
+        tiles[kb * blocks + kb].close(b);
 
-    while !queue.is_empty() {
-        if let Some(priority) = queue.min_priority() {
-            if let Some(u) = queue.dequeue_min() {
-                if priority == dist_row[u as usize] as usize {
-                    if let Some(neighbors) = neighbors_map.get(&u) {
-                        for v in neighbors {
-                            let weight = weights.get(&(u, *v)).unwrap();
-                            let alt = dist_row[u as usize] as u16 + weight;
-                            if alt < (dist_row[*v as usize]) as u16 {
-                                dist_row[*v as usize] = alt as u16;
-                                pred_row[*v as usize] = u as u16;
-                                queue.enqueue(*v as u16, alt as usize);
-                            }
-                        }
-                    }
-                }
+        let diagonal = tiles[kb * blocks + kb].clone();
+        tiles.par_iter_mut().enumerate().for_each(|(t, tile)| {
+            let (bi, bj) = (t / blocks, t % blocks);
+            if bi == kb && bj != kb {
+                let old = tile.clone();
+                tile.relax_through(&diagonal, &old, b);
+            } else if bj == kb && bi != kb {
+                let old = tile.clone();
+                tile.relax_through(&old, &diagonal, b);
+            }
+        });
+
+        let row = tiles[kb * blocks..(kb + 1) * blocks].to_vec();
+        let column: Vec<Tile> = (0..blocks)
+            .map(|bi| tiles[bi * blocks + kb].clone())
+            .collect();
+        tiles.par_iter_mut().enumerate().for_each(|(t, tile)| {
+            let (bi, bj) = (t / blocks, t % blocks);
+            if bi != kb && bj != kb {
+                tile.relax_through(&column[bi], &row[bj], b);
+            }
+        });
+    }
+
+    for (t, tile) in tiles.iter().enumerate() {
+        let (bi, bj) = (t / blocks, t % blocks);
+        for i in 0..b.min(size - bi * b) {
+            for j in 0..b.min(size - bj * b) {
+                dist[[bi * b + i, bj * b + j]] = tile.dist[i * b + j];
+                pred[[bi * b + i, bj * b + j]] = tile.pred[i * b + j];
             }
         }
     }
+    pred
+}
 
-    (dist_row, pred_row)
+/// An undirected graph with small positive integer edge weights, in
+/// compressed sparse row form.  The neighbors of node u, and the weights of
+/// the edges to them, are targets[offsets[u]..offsets[u + 1]] and
+/// weights[offsets[u]..offsets[u + 1]].
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+    offsets: Vec<usize>,
+    targets: Vec<u32>,
+    weights: Vec<u32>,
 }
 
-fn dijkstra_dial_inner(dist: &mut Array2<u16>, alg: Algorithm) -> Array2<u16> {
-    let size = dist.nrows();
-    let mut pred = Array2::<u16>::from_elem((size, size), NO_PRED_NODE);
+impl Graph {
+    /// Build a graph with size nodes from a list of (i, j, weight) edges.
+    /// Each edge goes in both directions.  If there are several edges
+    /// between the same two nodes, keep the lowest weight.
+    pub fn from_edges(size: usize, edges: &[(u32, u32, u32)]) -> Graph {
+        let mut directed: Vec<(u32, u32, u32)> = Vec::with_capacity(2 * edges.len());
+        for (i, j, weight) in edges {
+            if i != j {
+                directed.push((*i, *j, *weight));
+                directed.push((*j, *i, *weight));
+            }
+        }
+        // Sorting puts the lowest weight first among duplicates.
+        directed.sort_unstable();
+        directed.dedup_by_key(|(i, j, _)| (*i, *j));
 
-    // Set all zero vertexes to infinity
-    for i in 0..size {
-        for j in 0..size {
-            if dist[[i, j]] == 0 {
-                dist[[i, j]] = INFINITY;
+        let mut offsets = vec![0; size + 1];
+        for (i, _, _) in directed.iter() {
+            offsets[*i as usize + 1] += 1;
+        }
+        for u in 0..size {
+            offsets[u + 1] += offsets[u];
+        }
+        Graph {
+            offsets,
+            targets: directed.iter().map(|(_, j, _)| *j).collect(),
+            weights: directed.iter().map(|(_, _, weight)| *weight).collect(),
+        }
+    }
+
+    /// Build a graph from a dense matrix, where 0 and INFINITY mean there
+    /// is no edge.  Edges are assumed to be bidirectional.
+    pub fn from_matrix(dist: &Array2<u32>) -> Graph {
+        let size = dist.nrows();
+        let mut edges = Vec::new();
+        for i in 0..size {
+            for j in 0..size {
+                let weight = dist[[i, j]];
+                if weight > 0 && weight < INFINITY {
+                    edges.push((i as u32, j as u32, weight));
+                }
             }
         }
+        Graph::from_edges(size, &edges)
     }
 
-    // Set each vertex at zero distance to itself
-    for i in 0..size {
-        dist[[i, i]] = 0;
+    pub fn size(&self) -> usize {
+        self.offsets.len() - 1
     }
 
-    // Assume bidirectional movement
-    for i in 0..size {
-        for j in 0..size {
-            if dist[[i, j]] > dist[[j, i]] {
-                dist[[i, j]] = dist[[j, i]];
+    /// Return the number of edges, counting each direction separately.
+    pub fn num_edges(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Return (neighbor, weight) for each edge from u.
+    pub fn neighbors(&self, u: u32) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let range = self.offsets[u as usize]..self.offsets[u as usize + 1];
+        self.targets[range.clone()]
+            .iter()
+            .cloned()
+            .zip(self.weights[range].iter().cloned())
+    }
+
+    /// Return the weight of the edge from u to v, if there is one.
+    pub fn weight(&self, u: u32, v: u32) -> Option<u32> {
+        self.neighbors(u)
+            .find(|(neighbor, _)| *neighbor == v)
+            .map(|(_, weight)| weight)
+    }
+
+    fn to_matrix(&self) -> Array2<u32> {
+        let size = self.size();
+        let mut dist = Array2::<u32>::from_elem((size, size), INFINITY);
+        for u in 0..size {
+            for (v, weight) in self.neighbors(u as u32) {
+                dist[[u, v as usize]] = weight;
             }
         }
+        dist
     }
+}
 
-    // Populate neighbors_map
-    let mut neighbors_map: HashMap<u16, HashSet<u16>> = HashMap::new();
-    for i in 0..size {
-        let set = HashSet::new();
-        neighbors_map.insert(i as u16, set);
+/// Settled nodes from one single-source search, as a map of node to
+/// (distance from the source, predecessor on the way back to the source).
+type Settled = HashMap<u32, (u32, u32)>;
+
+/// Working space for single-source searches.  Each rayon thread keeps one
+/// and only resets the entries it touched, so a search that stops early
+/// costs time in proportion to how far it got, not to the graph size.
+struct Scratch {
+    dist: Vec<u32>,
+    pred: Vec<u32>,
+    touched: Vec<u32>,
+}
+
+impl Scratch {
+    fn new(size: usize) -> Scratch {
+        Scratch {
+            dist: vec![INFINITY; size],
+            pred: vec![NO_PRED_NODE; size],
+            touched: Vec::new(),
+        }
     }
-    for i in 0..size {
-        for j in 0..size {
-            if dist[[i, j]] > 0 && dist[[i, j]] < INFINITY {
-                let set = neighbors_map.get_mut(&(i as u16)).unwrap();
-                set.insert(j as u16);
+
+    /// Record a path to v through u, if it's shorter than what we have.
+    fn relax(&mut self, v: u32, alt: u32, u: u32) -> bool {
+        let v_index = v as usize;
+        if alt < self.dist[v_index] {
+            if self.dist[v_index] == INFINITY {
+                self.touched.push(v);
             }
+            self.dist[v_index] = alt;
+            self.pred[v_index] = u;
+            true
+        } else {
+            false
         }
     }
 
-    // Populate weights
-    let mut weights: HashMap<(u16, u16), u16> = HashMap::new();
-    for i in 0..size {
-        for j in 0..size {
-            if dist[[i, j]] > 0 && dist[[i, j]] != INFINITY {
-                weights.insert((i as u16, j as u16), dist[[i, j]] as u16);
+    fn reset(&mut self) {
+        for v in self.touched.drain(..) {
+            self.dist[v as usize] = INFINITY;
+            self.pred[v as usize] = NO_PRED_NODE;
+        }
+    }
+}
+
+/// Record u as settled.  Return true if that was the last target, so the
+/// search can stop.
+fn settle(
+    u: u32,
+    scratch: &Scratch,
+    settled: &mut Settled,
+    targets: Option<&HashSet<u32>>,
+    remaining: &mut usize,
+) -> bool {
+    settled.insert(u, (scratch.dist[u as usize], scratch.pred[u as usize]));
+    if let Some(targets) = targets {
+        if targets.contains(&u) {
+            *remaining -= 1;
+            return *remaining == 0;
+        }
+    }
+    false
+}
+
+/// A monotone priority queue of (key, value) pairs with u32 keys, which only
+/// works if no key pushed is less than the last key popped, as in Dijkstra.
+/// Bucket 0 holds keys equal to the last key popped, and bucket b holds keys
+/// whose highest bit that differs from it is bit b - 1.  Popping takes from
+/// bucket 0, and when that's empty, redistributes the lowest non-empty bucket
+/// around its smallest key.  Each pair can only move to lower buckets, so it
+/// moves at most 32 times.
+struct RadixHeap {
+    last: u32,
+    buckets: [Vec<(u32, u32)>; 33],
+}
+
+impl RadixHeap {
+    fn new() -> RadixHeap {
+        RadixHeap {
+            last: 0,
+            buckets: std::array::from_fn(|_| Vec::new()),
+        }
+    }
+
+    fn bucket(&self, key: u32) -> usize {
+        32 - (key ^ self.last).leading_zeros() as usize
+    }
+
+    fn push(&mut self, key: u32, value: u32) {
+        debug_assert!(key >= self.last);
+        let bucket = self.bucket(key);
+        self.buckets[bucket].push((key, value));
+    }
+
+    fn pop(&mut self) -> Option<(u32, u32)> {
+        if self.buckets[0].is_empty() {
+            let bucket = (1..self.buckets.len()).find(|b| !self.buckets[*b].is_empty())?;
+            let mut pairs = std::mem::take(&mut self.buckets[bucket]);
+            self.last = pairs.iter().map(|(key, _)| *key).min().unwrap();
+            for (key, value) in pairs.drain(..) {
+                let new_bucket = self.bucket(key);
+                self.buckets[new_bucket].push((key, value));
             }
+            // Keep the emptied bucket's allocation for reuse.
+            self.buckets[bucket] = pairs;
         }
+        self.buckets[0].pop()
     }
+}
 
-    // Initialize predecessors where we have paths
-    for i in 0..size {
-        for j in 0..size {
-            if dist[[i, j]] > 0 && dist[[i, j]] < INFINITY {
-                pred[[i, j]] = i as u16;
+/// Find the shortest paths from start to other nodes, stopping early once
+/// every node in targets (if given) has been reached.
+fn dijkstra_one_row(
+    start: u32,
+    graph: &Graph,
+    targets: Option<&HashSet<u32>>,
+    scratch: &mut Scratch,
+) -> Settled {
+    let mut settled: Settled = HashMap::new();
+    let mut remaining = targets.map_or(0, |targets| targets.len());
+
+    let mut heap = BinaryHeap::new();
+
+    scratch.relax(start, 0, NO_PRED_NODE);
+    heap.push(Reverse((0, start)));
+
+    while let Some(Reverse((priority, u))) = heap.pop() {
+        if priority != scratch.dist[u as usize] {
+            continue;
+        }
+        if settle(u, scratch, &mut settled, targets, &mut remaining) {
+            break;
+        }
+        for (v, weight) in graph.neighbors(u) {
+            let alt = priority + weight;
+            if scratch.relax(v, alt, u) {
+                heap.push(Reverse((alt, v)));
             }
         }
     }
 
-    let tuples: Vec<(Vec<u16>, Vec<u16>)>;
-    // Do the Dijkstra or Dial algorithm for each row, in parallel using Rayon
-    if alg == Algorithm::Dijkstra {
-        tuples = (0..size)
-            .into_par_iter()
-            .map(|i| dijkstra_one_row(i as u16, size, &neighbors_map, &weights))
-            .collect();
-    } else if alg == Algorithm::Dial {
-        tuples = (0..size)
-            .into_par_iter()
-            .map(|i| dial_one_row(i as u16, size, &neighbors_map, &weights))
-            .collect();
-    } else {
-        panic!("invalid Algorithm");
+    scratch.reset();
+    settled
+}
+
+/// Like dijkstra_one_row, but with a bucket queue.
+fn dial_one_row(
+    start: u32,
+    graph: &Graph,
+    targets: Option<&HashSet<u32>>,
+    scratch: &mut Scratch,
+) -> Settled {
+    let mut settled: Settled = HashMap::new();
+    let mut remaining = targets.map_or(0, |targets| targets.len());
+
+    let mut queue = BucketQueue::<VecDeque<u32>>::new();
+
+    scratch.relax(start, 0, NO_PRED_NODE);
+    queue.enqueue(start, 0);
+
+    while let Some(priority) = queue.min_priority() {
+        let u = queue.dequeue_min().unwrap();
+        if priority != scratch.dist[u as usize] as usize {
+            continue;
+        }
+        if settle(u, scratch, &mut settled, targets, &mut remaining) {
+            break;
+        }
+        for (v, weight) in graph.neighbors(u) {
+            let alt = priority as u32 + weight;
+            if scratch.relax(v, alt, u) {
+                queue.enqueue(v, alt as usize);
+            }
+        }
     }
-    for (i, (dist_row, pred_row)) in tuples.iter().enumerate() {
-        // TODO Find a way to copy the entire row
-        for (j, dist_el) in dist_row.iter().enumerate() {
-            dist[[i, j]] = *dist_el;
+
+    scratch.reset();
+    settled
+}
+
+/// Like dijkstra_one_row, but with a radix heap.
+fn radix_one_row(
+    start: u32,
+    graph: &Graph,
+    targets: Option<&HashSet<u32>>,
+    scratch: &mut Scratch,
+) -> Settled {
+    let mut settled: Settled = HashMap::new();
+    let mut remaining = targets.map_or(0, |targets| targets.len());
+
+    let mut heap = RadixHeap::new();
+
+    scratch.relax(start, 0, NO_PRED_NODE);
+    heap.push(0, start);
+
+    while let Some((priority, u)) = heap.pop() {
+        if priority != scratch.dist[u as usize] {
+            continue;
         }
-        for (j, pred_el) in pred_row.iter().enumerate() {
-            pred[[i, j]] = *pred_el;
+        if settle(u, scratch, &mut settled, targets, &mut remaining) {
+            break;
+        }
+        for (v, weight) in graph.neighbors(u) {
+            let alt = priority + weight;
+            if scratch.relax(v, alt, u) {
+                heap.push(alt, v);
+            }
+        }
+    }
+
+    scratch.reset();
+    settled
+}
+
+fn one_row(
+    start: u32,
+    graph: &Graph,
+    targets: Option<&HashSet<u32>>,
+    alg: Algorithm,
+    scratch: &mut Scratch,
+) -> Settled {
+    match alg {
+        Algorithm::Dijkstra => dijkstra_one_row(start, graph, targets, scratch),
+        Algorithm::Dial => dial_one_row(start, graph, targets, scratch),
+        Algorithm::Radix => radix_one_row(start, graph, targets, scratch),
+        _ => panic!("invalid Algorithm"),
+    }
+}
+
+fn dijkstra_dial_inner(dist: &mut Array2<u32>, alg: Algorithm) -> Array2<u32> {
+    let size = dist.nrows();
+    let graph = Graph::from_matrix(dist);
+
+    // Do the Dijkstra or Dial algorithm for each row, in parallel using Rayon
+    let rows: Vec<Settled> = (0..size)
+        .into_par_iter()
+        .map_init(
+            || Scratch::new(size),
+            |scratch, i| one_row(i as u32, &graph, None, alg, scratch),
+        )
+        .collect();
+
+    dist.fill(INFINITY);
+    let mut pred = Array2::<u32>::from_elem((size, size), NO_PRED_NODE);
+    for (i, row) in rows.iter().enumerate() {
+        for (j, (dist_el, pred_el)) in row.iter() {
+            dist[[i, *j as usize]] = *dist_el;
+            pred[[i, *j as usize]] = *pred_el;
         }
     }
 
     pred
 }
 
-pub fn shortest_path(dist: &mut Array2<u16>, alg: Algorithm) -> Array2<u16> {
+pub fn shortest_path(dist: &mut Array2<u32>, alg: Algorithm) -> Array2<u32> {
     match alg {
         Algorithm::Dial => dial(dist),
         Algorithm::Dijkstra => dijkstra(dist),
+        Algorithm::Radix => radix(dist),
         Algorithm::Floyd => floyd_warshall(dist),
+        Algorithm::BlockedFloyd => blocked_floyd_warshall(dist, FLOYD_BLOCK_SIZE),
     }
 }
 
@@ -265,7 +597,7 @@ pub fn shortest_path(dist: &mut Array2<u16>, alg: Algorithm) -> Array2<u16> {
 /// is a max-heap, so nodes are wrapped in std::cmp::Reverse to make it work
 /// as a min-heap.  APSP Dijkstra's runtime for V nodes and E edges, with a
 /// binary heap, is O(((E + V) log V)V).
-fn dijkstra(dist: &mut Array2<u16>) -> Array2<u16> {
+fn dijkstra(dist: &mut Array2<u32>) -> Array2<u32> {
     dijkstra_dial_inner(dist, Algorithm::Dijkstra)
 }
 
@@ -277,10 +609,387 @@ fn dijkstra(dist: &mut Array2<u16>) -> Array2<u16> {
 /// O(buckets) pop, compared to the binary heap's O(log n) push and O(log n)
 /// pop.  This makes APSP Dial's runtime O((E + VC)V) for E edges, V nodes, and
 /// C distinct edge weights.
-fn dial(dist: &mut Array2<u16>) -> Array2<u16> {
+fn dial(dist: &mut Array2<u32>) -> Array2<u32> {
     dijkstra_dial_inner(dist, Algorithm::Dial)
 }
 
+/// Dijkstra's algorithm with a radix heap (see RadixHeap) as the priority
+/// queue.  Unlike Dial's bucket queue, it doesn't need a bucket for every
+/// distance, so it copes with large edge weights (like long xboat routes or
+/// travel times), and unlike a binary heap, a pop only looks at the nodes
+/// near the minimum.  APSP runtime is O((E + V log C)V) for E edges, V nodes,
+/// and maximum edge weight C.
+fn radix(dist: &mut Array2<u32>) -> Array2<u32> {
+    dijkstra_dial_inner(dist, Algorithm::Radix)
+}
+
+/// What we know about shortest paths from one source node.  A complete row
+/// has every node reachable from the source; an incomplete one only has
+/// the nodes on the shortest paths to the targets it was computed for.
+#[derive(Clone, Debug)]
+struct Row {
+    settled: Settled,
+    complete: bool,
+}
+
+impl Row {
+    /// Add what other knows to this row.  Shortest path distances are
+    /// unique, so entries from either row chain back to the source along
+    /// shortest paths, and we keep the ones we already have.
+    fn merge(&mut self, other: Row) {
+        if self.complete {
+            return;
+        }
+        if other.complete {
+            *self = other;
+            return;
+        }
+        for (node, entry) in other.settled {
+            self.settled.entry(node).or_insert(entry);
+        }
+    }
+}
+
+/// Single-source shortest paths over a Graph, computed on demand.
+///
+/// Rather than storing a dense V*V matrix of distances and another of
+/// predecessors, we run Dijkstra or Dial from a source node only when
+/// something asks about it.  Callers that know which pairs they will ask
+/// about should prepare them first, in bulk and in parallel; then each
+/// search can stop as soon as it has reached its targets, and only the
+/// paths to those targets are kept.  Anything else falls back to a full
+/// search from the source, which is cached.
+///
+/// Floyd-Warshall has no single-source form, so with Algorithm::Floyd or
+/// Algorithm::BlockedFloyd all rows are computed up front, densely.  That
+/// needs V^2 memory, so it's only suitable for testing or small regions.
+#[derive(Debug)]
+pub struct ShortestPaths {
+    graph: Graph,
+    alg: Algorithm,
+    rows: RwLock<HashMap<u32, Row>>,
+}
+
+impl Clone for ShortestPaths {
+    fn clone(&self) -> ShortestPaths {
+        ShortestPaths {
+            graph: self.graph.clone(),
+            alg: self.alg,
+            rows: RwLock::new(self.rows.read().unwrap().clone()),
+        }
+    }
+}
+
+impl ShortestPaths {
+    pub fn new(graph: Graph, alg: Algorithm) -> ShortestPaths {
+        let mut rows = HashMap::new();
+        if matches!(alg, Algorithm::Floyd | Algorithm::BlockedFloyd) {
+            let mut dist = graph.to_matrix();
+            let pred = shortest_path(&mut dist, alg);
+            for i in 0..graph.size() {
+                let mut settled = HashMap::new();
+                for j in 0..graph.size() {
+                    if dist[[i, j]] != INFINITY {
+                        // floyd_warshall's predecessors point back toward i
+                        settled.insert(j as u32, (dist[[i, j]], pred[[i, j]]));
+                    }
+                }
+                let row = Row {
+                    settled,
+                    complete: true,
+                };
+                rows.insert(i as u32, row);
+            }
+        }
+        ShortestPaths {
+            graph,
+            alg,
+            rows: RwLock::new(rows),
+        }
+    }
+
+    pub fn graph(&self) -> &Graph {
+        &self.graph
+    }
+
+    /// Return the number of nodes we have stored shortest path data for,
+    /// summed over all sources.
+    pub fn num_entries(&self) -> usize {
+        let rows = self.rows.read().unwrap();
+        rows.values().map(|row| row.settled.len()).sum()
+    }
+
+    /// Compute, in parallel, the shortest paths from each source to its
+    /// targets, keeping only the nodes along those paths.  Only targets we
+    /// don't already know about are searched for, and what we find is
+    /// added to what we knew.
+    pub fn prepare(&self, source_to_targets: &HashMap<u32, HashSet<u32>>) {
+        let todo: Vec<(u32, HashSet<u32>)> = {
+            let rows = self.rows.read().unwrap();
+            source_to_targets
+                .iter()
+                .filter_map(|(source, targets)| {
+                    let missing: HashSet<u32> = match rows.get(source) {
+                        Some(row) if row.complete => return None,
+                        Some(row) => targets
+                            .iter()
+                            .filter(|target| !row.settled.contains_key(target))
+                            .cloned()
+                            .collect(),
+                        None => targets.clone(),
+                    };
+                    if missing.is_empty() {
+                        None
+                    } else {
+                        Some((*source, missing))
+                    }
+                })
+                .collect()
+        };
+        let new_rows: Vec<(u32, Row)> = todo
+            .into_par_iter()
+            .map_init(
+                || Scratch::new(self.graph.size()),
+                |scratch, (source, targets)| (source, self.search(source, Some(&targets), scratch)),
+            )
+            .collect();
+        let mut rows = self.rows.write().unwrap();
+        for (source, row) in new_rows {
+            match rows.get_mut(&source) {
+                Some(existing) => existing.merge(row),
+                None => {
+                    rows.insert(source, row);
+                }
+            }
+        }
+    }
+
+    fn search(&self, source: u32, targets: Option<&HashSet<u32>>, scratch: &mut Scratch) -> Row {
+        let settled = one_row(source, &self.graph, targets, self.alg, scratch);
+        match targets {
+            None => Row {
+                settled,
+                complete: true,
+            },
+            Some(targets) => {
+                // Keep only the paths back from each target.
+                let mut kept = HashMap::new();
+                for target in targets {
+                    let mut node = *target;
+                    while let Some(entry) = settled.get(&node) {
+                        if kept.insert(node, *entry).is_some() {
+                            break;
+                        }
+                        node = entry.1;
+                    }
+                    kept.entry(*target).or_insert((INFINITY, NO_PRED_NODE));
+                }
+                Row {
+                    settled: kept,
+                    complete: false,
+                }
+            }
+        }
+    }
+
+    /// Return a key that changes whenever the graph or the algorithm does,
+    /// so saved rows are only reused for the same inputs.
+    ///
+    /// This is the 64-bit FNV-1a hash of CACHE_MAGIC, then as little-endian
+    /// u32s the number of nodes, each node's edge count followed by its
+    /// (neighbor, weight) pairs, and the algorithm's number.  It must stay
+    /// the same across builds and platforms, since it names the cache file.
+    pub fn cache_key(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        let alg = match self.alg {
+            Algorithm::Dijkstra => 0,
+            Algorithm::Dial => 1,
+            Algorithm::Floyd => 2,
+            Algorithm::BlockedFloyd => 3,
+            Algorithm::Radix => 4,
+        };
+        hasher.write(CACHE_MAGIC);
+        hasher.write_u32(self.graph.size() as u32);
+        for u in 0..self.graph.size() as u32 {
+            hasher.write_u32(self.graph.neighbors(u).count() as u32);
+            for (v, weight) in self.graph.neighbors(u) {
+                hasher.write_u32(v);
+                hasher.write_u32(weight);
+            }
+        }
+        hasher.write_u32(alg);
+        hasher.finish()
+    }
+
+    /// Write all the rows we know to writer, as little-endian u32s: the
+    /// number of rows, then for each row its source, 1 if it's complete or
+    /// else 0, its number of entries, and (node, distance, predecessor) for
+    /// each entry.
+    pub fn write_rows<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let rows = self.rows.read().unwrap();
+        writer.write_all(CACHE_MAGIC)?;
+        write_u32(writer, self.cache_key() as u32)?;
+        write_u32(writer, rows.len() as u32)?;
+        let mut sources: Vec<&u32> = rows.keys().collect();
+        sources.sort();
+        for source in sources {
+            let row = &rows[source];
+            write_u32(writer, *source)?;
+            write_u32(writer, row.complete as u32)?;
+            write_u32(writer, row.settled.len() as u32)?;
+            for (node, (dist, pred)) in row.settled.iter() {
+                write_u32(writer, *node)?;
+                write_u32(writer, *dist)?;
+                write_u32(writer, *pred)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read rows written by write_rows for the same graph and algorithm.
+    /// Sources we already have rows for are left alone.
+    pub fn read_rows<R: Read>(&self, reader: &mut R) -> io::Result<()> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != CACHE_MAGIC || read_u32(reader)? != self.cache_key() as u32 {
+            return Err(invalid_data("not rows for this graph"));
+        }
+        let size = self.graph.size() as u32;
+        let num_rows = read_u32(reader)?;
+        let mut new_rows = HashMap::new();
+        for _ in 0..num_rows {
+            let source = read_u32(reader)?;
+            let complete = read_u32(reader)? != 0;
+            let num_entries = read_u32(reader)?;
+            if source >= size || num_entries > size {
+                return Err(invalid_data("row out of range"));
+            }
+            let mut settled = HashMap::new();
+            for _ in 0..num_entries {
+                let node = read_u32(reader)?;
+                let dist = read_u32(reader)?;
+                let pred = read_u32(reader)?;
+                if node >= size || (pred >= size && pred != NO_PRED_NODE) {
+                    return Err(invalid_data("entry out of range"));
+                }
+                settled.insert(node, (dist, pred));
+            }
+            new_rows.insert(source, Row { settled, complete });
+        }
+        let mut rows = self.rows.write().unwrap();
+        for (source, row) in new_rows {
+            rows.entry(source).or_insert(row);
+        }
+        Ok(())
+    }
+
+    /// Return the (distance, predecessor) of node in source's row, if we
+    /// know it.
+    fn lookup(&self, source: u32, node: u32) -> Option<(u32, u32)> {
+        let rows = self.rows.read().unwrap();
+        let row = rows.get(&source)?;
+        match row.settled.get(&node) {
+            Some(entry) => Some(*entry),
+            None if row.complete => Some((INFINITY, NO_PRED_NODE)),
+            None => None,
+        }
+    }
+
+    /// Return the (distance, predecessor) of node in source's row, doing a
+    /// full search from source if needed.
+    fn lookup_or_search(&self, source: u32, node: u32) -> (u32, u32) {
+        if let Some(entry) = self.lookup(source, node) {
+            return entry;
+        }
+        let mut scratch = Scratch::new(self.graph.size());
+        let row = self.search(source, None, &mut scratch);
+        let entry = row
+            .settled
+            .get(&node)
+            .cloned()
+            .unwrap_or((INFINITY, NO_PRED_NODE));
+        self.rows.write().unwrap().insert(source, row);
+        entry
+    }
+
+    /// Return the shortest distance between i and j, or INFINITY if there
+    /// is no path.
+    pub fn distance(&self, i: u32, j: u32) -> u32 {
+        if i == j {
+            return 0;
+        }
+        if let Some((distance, _)) = self.lookup(i, j) {
+            return distance;
+        }
+        // Edges are bidirectional, so j's row works too.
+        if let Some((distance, _)) = self.lookup(j, i) {
+            return distance;
+        }
+        self.lookup_or_search(i, j).0
+    }
+
+    /// Return the inclusive shortest path from start to end, or None if
+    /// there is no path.  This follows predecessors in end's row.
+    pub fn path(&self, start: u32, end: u32) -> Option<Vec<u32>> {
+        if start == end {
+            return Some(vec![start]);
+        }
+        let (distance, mut node) = self.lookup_or_search(end, start);
+        if distance == INFINITY {
+            return None;
+        }
+        let mut path = vec![start];
+        while node != end {
+            path.push(node);
+            node = self.lookup_or_search(end, node).1;
+        }
+        path.push(end);
+        Some(path)
+    }
+}
+
+/// The 64-bit FNV-1a hash, from
+/// http://www.isthe.com/chongo/tech/comp/fnv/ .  Unlike
+/// std::collections::hash_map::DefaultHasher, its output is fixed.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new() -> Fnv1a {
+        Fnv1a(Fnv1a::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(Fnv1a::PRIME);
+        }
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -290,9 +999,9 @@ mod tests {
     extern crate rand;
     use rand::prelude::*;
 
-    fn setup_scipy_test() -> Array2<u16> {
+    fn setup_scipy_test() -> Array2<u32> {
         // https://docs.scipy.org/doc/scipy/reference/generated/scipy.sparse.csgraph.shortest_path.html
-        let mut dist = Array2::<u16>::from_elem((4, 4), INFINITY);
+        let mut dist = Array2::<u32>::from_elem((4, 4), INFINITY);
         dist[[0, 1]] = 1;
         dist[[0, 2]] = 2;
         dist[[1, 3]] = 1;
@@ -303,7 +1012,7 @@ mod tests {
         dist
     }
 
-    fn compare_scipy_test(dist: Array2<u16>, pred: Array2<u16>) {
+    fn compare_scipy_test(dist: Array2<u32>, pred: Array2<u32>) {
         debug!("dist after {:?}\n", dist);
         debug!("pred after {:?}\n", pred);
 
@@ -355,6 +1064,25 @@ mod tests {
         compare_scipy_test(dist, pred);
     }
 
+    #[test]
+    fn test_blocked_floyd_warshall_scipy() {
+        let mut dist = setup_scipy_test();
+        let pred = shortest_path(&mut dist, Algorithm::BlockedFloyd);
+        compare_scipy_test(dist, pred);
+
+        // Two tiles on a side, the second padded
+        let mut dist = setup_scipy_test();
+        let pred = blocked_floyd_warshall(&mut dist, 3);
+        compare_scipy_test(dist, pred);
+    }
+
+    #[test]
+    fn test_radix_scipy() {
+        let mut dist = setup_scipy_test();
+        let pred = shortest_path(&mut dist, Algorithm::Radix);
+        compare_scipy_test(dist, pred);
+    }
+
     #[test]
     fn test_dijkstra_scipy() {
         let mut dist = setup_scipy_test();
@@ -369,15 +1097,22 @@ mod tests {
         compare_scipy_test(dist, pred);
     }
 
-    fn setup_random_matrix(vertexes: usize, edges: usize) -> Array2<u16> {
+    fn setup_random_matrix(vertexes: usize, edges: usize) -> Array2<u32> {
+        setup_random_matrix_with_max_cost(vertexes, edges, 4)
+    }
+
+    fn setup_random_matrix_with_max_cost(
+        vertexes: usize,
+        edges: usize,
+        max_cost: u32,
+    ) -> Array2<u32> {
         let mut rng = thread_rng();
-        let max_cost = 4;
-        let mut dist = Array2::<u16>::from_elem((vertexes, vertexes), INFINITY);
+        let mut dist = Array2::<u32>::from_elem((vertexes, vertexes), INFINITY);
         for _ in 0..edges {
             let i = rng.gen_range(0..vertexes);
             let j = rng.gen_range(0..vertexes);
             let cost = rng.gen_range(1..=max_cost);
-            dist[[i, j]] = cost as u16;
+            dist[[i, j]] = cost;
         }
         dist
     }
@@ -387,13 +1122,19 @@ mod tests {
         let mut dist1 = setup_random_matrix(100, 1000);
         let mut dist2 = dist1.clone();
         let mut dist3 = dist2.clone();
+        let mut dist4 = dist3.clone();
+        let mut dist5 = dist4.clone();
 
         floyd_warshall(&mut dist1);
         dijkstra(&mut dist2);
         dial(&mut dist3);
+        blocked_floyd_warshall(&mut dist4, 16);
+        radix(&mut dist5);
 
         assert_eq!(dist1, dist2);
         assert_eq!(dist1, dist3);
+        assert_eq!(dist1, dist4);
+        assert_eq!(dist1, dist5);
         // predecessors are not guaranteed to be identical
     }
 
@@ -409,6 +1150,312 @@ mod tests {
         // predecessors are not guaranteed to be identical
     }
 
+    #[test]
+    fn test_large_weights_random_matrix() {
+        // Too many distinct distances for Dial's bucket queue
+        let mut dist1 = setup_random_matrix_with_max_cost(300, 2000, 1_000_000);
+        let mut dist2 = dist1.clone();
+        let mut dist3 = dist2.clone();
+        let mut dist4 = dist3.clone();
+
+        floyd_warshall(&mut dist1);
+        dijkstra(&mut dist2);
+        blocked_floyd_warshall(&mut dist3, 64);
+        radix(&mut dist4);
+
+        assert_eq!(dist1, dist2);
+        assert_eq!(dist1, dist3);
+        assert_eq!(dist1, dist4);
+
+        // But Dial can still manage a few hundred
+        let mut dist5 = setup_random_matrix_with_max_cost(300, 2000, 300);
+        let mut dist6 = dist5.clone();
+
+        dial(&mut dist5);
+        radix(&mut dist6);
+
+        assert_eq!(dist5, dist6);
+    }
+
+    #[test]
+    fn test_radix_heap() {
+        let mut rng = thread_rng();
+        let mut heap = RadixHeap::new();
+        let mut popped = Vec::new();
+        heap.push(5, 0);
+        heap.push(5, 1);
+        heap.push(u32::MAX - 1, 2);
+        for value in 3..1000 {
+            // Never push below the last key popped.
+            let key = heap.last + rng.gen_range(0..1_000_000);
+            heap.push(key, value);
+            if rng.gen_bool(0.5) {
+                popped.push(heap.pop().unwrap());
+            }
+        }
+        while let Some(pair) = heap.pop() {
+            popped.push(pair);
+        }
+        assert_eq!(popped.len(), 1000);
+        assert!(popped.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert_eq!(popped.last(), Some(&(u32::MAX - 1, 2)));
+    }
+
+    #[test]
+    fn test_blocked_floyd_warshall_random_matrix() {
+        let original = setup_random_matrix(200, 1000);
+        let graph = Graph::from_matrix(&original);
+        let mut expected = original.clone();
+        dial(&mut expected);
+
+        // Tiles that divide the matrix evenly, that don't, and that are
+        // bigger than it
+        for block_size in [5, 7, 50, 64, 512] {
+            let mut dist = original.clone();
+            let pred = blocked_floyd_warshall(&mut dist, block_size);
+            assert_eq!(dist, expected);
+
+            // The predecessors may differ from Dial's, but must lead back
+            // to the start along a path of the shortest length.
+            for i in 0..200 {
+                for j in 0..200 {
+                    if dist[[i, j]] == INFINITY || i == j {
+                        assert_eq!(pred[[i, j]], NO_PRED_NODE);
+                        continue;
+                    }
+                    let mut length = 0;
+                    let mut node = j as u32;
+                    while node != i as u32 {
+                        let prev = pred[[i, node as usize]];
+                        length += graph.weight(prev, node).unwrap();
+                        node = prev;
+                    }
+                    assert_eq!(length, dist[[i, j]]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_shortest_paths_blocked_floyd_random_matrix() {
+        let dist = setup_random_matrix(200, 500);
+        let dial = ShortestPaths::new(Graph::from_matrix(&dist), Algorithm::Dial);
+        let floyd = ShortestPaths::new(Graph::from_matrix(&dist), Algorithm::BlockedFloyd);
+        let num_entries = floyd.num_entries();
+        for i in 0..200 {
+            for j in 0..200 {
+                assert_eq!(floyd.distance(i, j), dial.distance(i, j));
+                match floyd.path(i, j) {
+                    None => assert_eq!(dial.distance(i, j), INFINITY),
+                    Some(path) => {
+                        let mut length = 0;
+                        for pair in path.windows(2) {
+                            length += floyd.graph().weight(pair[0], pair[1]).unwrap();
+                        }
+                        assert_eq!(length, dial.distance(i, j));
+                    }
+                }
+            }
+        }
+        // Every row was computed up front.
+        assert_eq!(floyd.num_entries(), num_entries);
+    }
+
+    #[test]
+    fn test_shortest_paths_scipy() {
+        let dist = setup_scipy_test();
+        for alg in [
+            Algorithm::Dijkstra,
+            Algorithm::Dial,
+            Algorithm::Floyd,
+            Algorithm::BlockedFloyd,
+            Algorithm::Radix,
+        ] {
+            let paths = ShortestPaths::new(Graph::from_matrix(&dist), alg);
+            assert_eq!(paths.distance(0, 0), 0);
+            assert_eq!(paths.distance(0, 3), 2);
+            assert_eq!(paths.distance(3, 0), 2);
+            assert_eq!(paths.distance(1, 2), 3);
+            assert_eq!(paths.path(2, 2), Some(vec![2]));
+            assert_eq!(paths.path(0, 3), Some(vec![0, 1, 3]));
+            assert_eq!(paths.path(3, 0), Some(vec![3, 1, 0]));
+        }
+    }
+
+    #[test]
+    fn test_shortest_paths_no_path() {
+        let graph = Graph::from_edges(4, &[(0, 1, 2), (1, 0, 1), (2, 3, 4), (3, 3, 1)]);
+        assert_eq!(graph.num_edges(), 4);
+        assert_eq!(graph.weight(0, 1), Some(1));
+        assert_eq!(graph.weight(1, 2), None);
+        let paths = ShortestPaths::new(graph, Algorithm::Dial);
+        assert_eq!(paths.distance(0, 1), 1);
+        assert_eq!(paths.distance(1, 2), INFINITY);
+        assert_eq!(paths.path(1, 3), None);
+
+        let mut source_to_targets = HashMap::new();
+        source_to_targets.insert(3, HashSet::from([0, 2]));
+        paths.prepare(&source_to_targets);
+        assert_eq!(paths.distance(3, 0), INFINITY);
+        assert_eq!(paths.distance(3, 2), 4);
+    }
+
+    #[test]
+    fn test_shortest_paths_prepare_random_matrix() {
+        let mut dist = setup_random_matrix(300, 600);
+        let full = ShortestPaths::new(Graph::from_matrix(&dist), Algorithm::Dial);
+        let prepared = ShortestPaths::new(Graph::from_matrix(&dist), Algorithm::Dijkstra);
+        shortest_path(&mut dist, Algorithm::Dijkstra);
+
+        let mut rng = thread_rng();
+        let mut source_to_targets: HashMap<u32, HashSet<u32>> = HashMap::new();
+        for _ in 0..100 {
+            let i = rng.gen_range(0..300);
+            let j = rng.gen_range(0..300);
+            source_to_targets.entry(i).or_default().insert(j);
+        }
+        prepared.prepare(&source_to_targets);
+        // Only the paths to the targets are kept.
+        let num_entries = prepared.num_entries();
+        assert!(num_entries < 100 * 300);
+
+        for (i, targets) in source_to_targets.iter() {
+            for j in targets {
+                let distance = dist[[*i as usize, *j as usize]];
+                assert_eq!(prepared.distance(*i, *j), distance);
+                assert_eq!(full.distance(*i, *j), distance);
+                match prepared.path(*j, *i) {
+                    None => assert_eq!(distance, INFINITY),
+                    Some(path) => {
+                        assert_eq!(path.first(), Some(j));
+                        assert_eq!(path.last(), Some(i));
+                        let mut length = 0;
+                        for pair in path.windows(2) {
+                            length += prepared.graph().weight(pair[0], pair[1]).unwrap();
+                        }
+                        assert_eq!(length, distance);
+                    }
+                }
+            }
+        }
+        // Everything we asked about was prepared, so nothing new was searched.
+        assert_eq!(prepared.num_entries(), num_entries);
+    }
+
+    #[test]
+    fn test_shortest_paths_prepare_keeps_earlier_targets() {
+        let mut dist = setup_random_matrix(300, 600);
+        let paths = ShortestPaths::new(Graph::from_matrix(&dist), Algorithm::Dial);
+        shortest_path(&mut dist, Algorithm::Dijkstra);
+
+        // Find a near target, then a farther one, then the near one again.
+        let mut targets: Vec<u32> = (1..300)
+            .filter(|j| dist[[0, *j as usize]] < INFINITY)
+            .collect();
+        targets.sort_by_key(|j| dist[[0, *j as usize]]);
+        let far = *targets.last().unwrap();
+        let near = targets[targets.len() / 2];
+        assert!(dist[[0, near as usize]] < dist[[0, far as usize]]);
+        paths.prepare(&HashMap::from([(0, HashSet::from([far]))]));
+        paths.prepare(&HashMap::from([(0, HashSet::from([near]))]));
+        let num_entries = paths.num_entries();
+        paths.prepare(&HashMap::from([(0, HashSet::from([far, near]))]));
+        assert_eq!(paths.num_entries(), num_entries);
+
+        // Both are answered from the prepared row, without a full search.
+        assert_eq!(paths.distance(0, far), dist[[0, far as usize]]);
+        assert_eq!(paths.distance(0, near), dist[[0, near as usize]]);
+        assert_eq!(paths.num_entries(), num_entries);
+        for target in [far, near] {
+            let path = paths.path(target, 0).unwrap();
+            let mut length = 0;
+            for pair in path.windows(2) {
+                length += paths.graph().weight(pair[0], pair[1]).unwrap();
+            }
+            assert_eq!(length, dist[[0, target as usize]]);
+        }
+    }
+
+    #[test]
+    fn test_shortest_paths_more_than_u16() {
+        // A long chain, with more nodes than fit in a u16 and a total
+        // distance that doesn't either.
+        let size = 70000;
+        let edges: Vec<(u32, u32, u32)> = (0..size as u32 - 1).map(|i| (i, i + 1, 2)).collect();
+        let graph = Graph::from_edges(size, &edges);
+        let last = size as u32 - 1;
+        for alg in [Algorithm::Dijkstra, Algorithm::Dial, Algorithm::Radix] {
+            let paths = ShortestPaths::new(graph.clone(), alg);
+            assert_eq!(paths.distance(0, last), 2 * last);
+            let path = paths.path(last, 0).unwrap();
+            assert_eq!(path.len(), size);
+            assert_eq!(path[1], last - 1);
+        }
+    }
+
+    #[test]
+    fn test_shortest_paths_write_read_rows() -> io::Result<()> {
+        let dist = setup_random_matrix(100, 300);
+        let paths = ShortestPaths::new(Graph::from_matrix(&dist), Algorithm::Dial);
+        let mut source_to_targets = HashMap::new();
+        source_to_targets.insert(5, HashSet::from([10, 20]));
+        paths.prepare(&source_to_targets);
+        paths.distance(7, 8);
+        let mut body = Vec::new();
+        paths.write_rows(&mut body)?;
+
+        let paths2 = ShortestPaths::new(Graph::from_matrix(&dist), Algorithm::Dial);
+        assert_eq!(paths2.cache_key(), paths.cache_key());
+        paths2.read_rows(&mut body.as_slice())?;
+        assert_eq!(paths2.num_entries(), paths.num_entries());
+        assert_eq!(paths2.distance(5, 10), paths.distance(5, 10));
+        assert_eq!(paths2.path(20, 5), paths.path(20, 5));
+        assert_eq!(paths2.distance(7, 99), paths.distance(7, 99));
+        // Everything came from the saved rows.
+        assert_eq!(paths2.num_entries(), paths.num_entries());
+
+        // A different algorithm or graph can't use them.
+        let paths3 = ShortestPaths::new(Graph::from_matrix(&dist), Algorithm::Dijkstra);
+        assert_ne!(paths3.cache_key(), paths.cache_key());
+        assert!(paths3.read_rows(&mut body.as_slice()).is_err());
+        let dist4 = setup_random_matrix(100, 300);
+        let paths4 = ShortestPaths::new(Graph::from_matrix(&dist4), Algorithm::Dial);
+        assert!(paths4.read_rows(&mut body.as_slice()).is_err());
+        assert_eq!(paths4.num_entries(), 0);
+
+        // Truncated
+        let paths5 = ShortestPaths::new(Graph::from_matrix(&dist), Algorithm::Dial);
+        assert!(paths5.read_rows(&mut &body[..body.len() - 1]).is_err());
+        assert_eq!(paths5.num_entries(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        // Test vectors from the FNV reference code
+        for (bytes, expected) in [
+            (&b""[..], 0xcbf2_9ce4_8422_2325),
+            (b"a", 0xaf63_dc4c_8601_ec8c),
+            (b"foobar", 0x8594_4171_f739_67e8),
+        ] {
+            let mut hasher = Fnv1a::new();
+            hasher.write(bytes);
+            assert_eq!(hasher.finish(), expected);
+        }
+    }
+
+    #[test]
+    fn test_cache_key_is_stable() {
+        // Saved rows are found by this key, so it mustn't change between
+        // builds.
+        let graph = Graph::from_edges(3, &[(0, 1, 1), (1, 2, 2)]);
+        let paths = ShortestPaths::new(graph.clone(), Algorithm::Dial);
+        assert_eq!(paths.cache_key(), 0x2fee_05b5_26e0_180c);
+        let paths = ShortestPaths::new(graph, Algorithm::Dijkstra);
+        assert_eq!(paths.cache_key(), 0xcfe8_b1bd_7d15_d49d);
+    }
+
     #[test]
     #[should_panic(expected = "invalid Algorithm")]
     fn test_dijkstra_dial_inner_bad_algorithm() {
@@ -0,0 +1,134 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Metadata for a hand-authored sector that has no travellermap XML.
+///
+/// This is read from a sidecar file named "<sector>.meta" next to the
+/// sector's column data, or given on the command line.  Either way it is a
+/// list of "Key: Value" entries, one per line or separated by semicolons:
+///
+/// ```text
+/// Name: Homebrew
+/// Abbreviation: Home
+/// X: 5
+/// Y: -2
+/// A: First Subsector
+/// Allegiance HoFe: Homebrew Federation
+/// ```
+///
+/// X and Y (the sector location) are required.  Name may be repeated for
+/// alternate names.  Single letters A through P name subsectors.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CustomSector {
+    pub names: Vec<String>,
+    pub abbreviation: String,
+    pub location: (i64, i64),
+    pub subsector_letter_to_name: HashMap<char, String>,
+    pub allegiance_code_to_name: HashMap<String, String>,
+}
+
+impl CustomSector {
+    /// Return the primary name, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.names.first().map(|name| name.as_str())
+    }
+}
+
+impl FromStr for CustomSector {
+    type Err = anyhow::Error;
+
+    fn from_str(st: &str) -> Result<CustomSector> {
+        let mut custom = CustomSector::default();
+        let mut x_opt = None;
+        let mut y_opt = None;
+        for entry in st.split(['\n', ';']) {
+            let entry = entry.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            let (key, value) = entry
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Expected 'Key: Value' but got '{}'", entry))?;
+            let key = key.trim();
+            let value = value.trim().to_string();
+            match key {
+                "Name" => custom.names.push(value),
+                "Abbreviation" => custom.abbreviation = value,
+                "X" => x_opt = Some(value.parse()?),
+                "Y" => y_opt = Some(value.parse()?),
+                _ => {
+                    if let Some(code) = key.strip_prefix("Allegiance ") {
+                        custom
+                            .allegiance_code_to_name
+                            .insert(code.trim().to_string(), value);
+                    } else if key.len() == 1 && ('A'..='P').contains(&key.chars().next().unwrap()) {
+                        custom
+                            .subsector_letter_to_name
+                            .insert(key.chars().next().unwrap(), value);
+                    } else {
+                        bail!("Unknown custom sector key '{}'", key);
+                    }
+                }
+            }
+        }
+        match (x_opt, y_opt) {
+            (Some(x), Some(y)) => custom.location = (x, y),
+            _ => bail!("Custom sector needs both X and Y"),
+        }
+        Ok(custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_custom_sector() {
+        let custom: CustomSector = "# Our campaign\n\
+             Name: Homebrew\n\
+             Name: Casa\n\
+             Abbreviation: Home\n\
+             X: 5\n\
+             Y: -2\n\
+             \n\
+             A: First Light\n\
+             P: Far Corner\n\
+             Allegiance HoFe: Homebrew Federation, Core Worlds\n"
+            .parse()
+            .unwrap();
+        assert_eq!(custom.name(), Some("Homebrew"));
+        assert_eq!(custom.names, vec!["Homebrew", "Casa"]);
+        assert_eq!(custom.abbreviation, "Home");
+        assert_eq!(custom.location, (5, -2));
+        assert_eq!(custom.subsector_letter_to_name.len(), 2);
+        assert_eq!(custom.subsector_letter_to_name[&'A'], "First Light");
+        assert_eq!(custom.subsector_letter_to_name[&'P'], "Far Corner");
+        assert_eq!(
+            custom.allegiance_code_to_name["HoFe"],
+            "Homebrew Federation, Core Worlds"
+        );
+    }
+
+    #[test]
+    fn test_parse_custom_sector_semicolons() {
+        let custom: CustomSector = "Name: Homebrew; X: 0; Y: 1; B: Second".parse().unwrap();
+        assert_eq!(custom.name(), Some("Homebrew"));
+        assert_eq!(custom.abbreviation, "");
+        assert_eq!(custom.location, (0, 1));
+        assert_eq!(custom.subsector_letter_to_name[&'B'], "Second");
+    }
+
+    #[test]
+    fn test_parse_custom_sector_errors() {
+        assert!("Name: Homebrew; X: 5".parse::<CustomSector>().is_err());
+        assert!("Name: Homebrew; X: 5; Y: north"
+            .parse::<CustomSector>()
+            .is_err());
+        assert!("Name Homebrew; X: 5; Y: 1".parse::<CustomSector>().is_err());
+        assert!("Name: Homebrew; X: 5; Y: 1; Q: Nope"
+            .parse::<CustomSector>()
+            .is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Something wrong with one line of sector column data.
+///
+/// location is where the data came from, like the path of the .sec file.
+/// line is 1-based.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    /// A world line came before the header that describes its columns.
+    NoHeader { location: String, line: usize },
+    /// A world has a field we can't use, like a short UWP or a non-numeric
+    /// hex.
+    BadField {
+        location: String,
+        line: usize,
+        hex: String,
+        field: String,
+        value: String,
+    },
+    /// A world is in the same hex as an earlier one.  This isn't fatal: the
+    /// later world replaces the earlier one, and lint reports it.
+    DuplicateHex {
+        location: String,
+        line: usize,
+        hex: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::NoHeader { location, line } => {
+                write!(f, "{} line {}: world data before header", location, line)
+            }
+            ParseError::BadField {
+                location,
+                line,
+                hex,
+                field,
+                value,
+            } => write!(
+                f,
+                "{} line {} hex {}: bad {} '{}'",
+                location, line, hex, field, value
+            ),
+            ParseError::DuplicateHex {
+                location,
+                line,
+                hex,
+            } => write!(f, "{} line {} hex {}: duplicate hex", location, line, hex),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Why a Galaxy couldn't be built or traded over, for the cases callers may
+/// want to handle differently from other failures.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GalaxyError {
+    /// No sector names were given, and no regions matched any sectors.
+    NoSectors,
+    /// We're offline, and these sector data files are not in the data
+    /// directory.
+    MissingSectorData { paths: Vec<PathBuf> },
+    /// There are more worlds than navigable distances can number.
+    TooManyWorlds { num_worlds: usize },
+}
+
+impl fmt::Display for GalaxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GalaxyError::NoSectors => write!(f, "No sectors"),
+            GalaxyError::MissingSectorData { paths } => {
+                write!(
+                    f,
+                    "Offline mode, but {} sector data files are missing",
+                    paths.len()
+                )?;
+                for path in paths {
+                    write!(f, "\n    {}", path.display())?;
+                }
+                Ok(())
+            }
+            GalaxyError::TooManyWorlds { num_worlds } => {
+                write!(f, "Too many worlds ({}) to find routes between", num_worlds)
+            }
+        }
+    }
+}
+
+impl Error for GalaxyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_display() {
+        let err = ParseError::NoHeader {
+            location: "Homebrew.sec".to_string(),
+            line: 3,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Homebrew.sec line 3: world data before header"
+        );
+
+        let err = ParseError::BadField {
+            location: "Homebrew.sec".to_string(),
+            line: 7,
+            hex: "0101".to_string(),
+            field: "UWP".to_string(),
+            value: "A12".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Homebrew.sec line 7 hex 0101: bad UWP 'A12'"
+        );
+
+        let err = ParseError::DuplicateHex {
+            location: "Homebrew.sec".to_string(),
+            line: 9,
+            hex: "0101".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Homebrew.sec line 9 hex 0101: duplicate hex"
+        );
+    }
+
+    #[test]
+    fn test_galaxy_error_display() {
+        assert_eq!(GalaxyError::NoSectors.to_string(), "No sectors");
+        let err = GalaxyError::MissingSectorData {
+            paths: vec![PathBuf::from("Core.sec"), PathBuf::from("Core.xml")],
+        };
+        assert_eq!(
+            err.to_string(),
+            "Offline mode, but 2 sector data files are missing\n    Core.sec\n    Core.xml"
+        );
+        let err = GalaxyError::TooManyWorlds {
+            num_worlds: 1 << 32,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Too many worlds (4294967296) to find routes between"
+        );
+    }
+}
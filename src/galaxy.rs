@@ -0,0 +1,420 @@
+use anyhow::{anyhow, bail, Result};
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::custom::CustomSector;
+use crate::error::{GalaxyError, ParseError};
+use crate::lint::{lint_sectors, LintIssue};
+use crate::source::{FsSource, HttpSource, SectorSource, DEFAULT_BASE_URL};
+use crate::universe::{SectorRegion, SectorsAround, Universe};
+use crate::{
+    default_data_dir, download_sector_data, download_universe, find_custom_sectors,
+    find_missing_sector_data, resolve_sector_names, select_sectors, unknown_sector_names, Coords,
+    DownloadOptions, Sector, World,
+};
+
+/// Which sectors to load into a Galaxy, and where to get their data.
+///
+/// Data is cached in the data directory, and downloaded from base_url if it
+/// is missing there, unless offline is set.  Or it's read straight from a
+/// SectorSource given with source.
+#[derive(Clone, Debug)]
+pub struct GalaxyBuilder {
+    data_dir: PathBuf,
+    milieu: Option<String>,
+    sector_names: Vec<String>,
+    regions: Vec<SectorRegion>,
+    arounds: Vec<SectorsAround>,
+    custom_sectors: Vec<CustomSector>,
+    base_url: String,
+    offline: bool,
+    lenient: bool,
+    download_options: DownloadOptions,
+    source: Option<Arc<dyn SectorSource + Send + Sync>>,
+}
+
+impl Default for GalaxyBuilder {
+    fn default() -> GalaxyBuilder {
+        GalaxyBuilder {
+            data_dir: default_data_dir(),
+            milieu: None,
+            sector_names: Vec::new(),
+            regions: Vec::new(),
+            arounds: Vec::new(),
+            custom_sectors: Vec::new(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            offline: false,
+            lenient: false,
+            download_options: DownloadOptions::default(),
+            source: None,
+        }
+    }
+}
+
+impl GalaxyBuilder {
+    pub fn new() -> GalaxyBuilder {
+        GalaxyBuilder::default()
+    }
+
+    /// Directory where we read and write data files.  Defaults to
+    /// default_data_dir().
+    pub fn data_directory(mut self, data_dir: &Path) -> GalaxyBuilder {
+        self.data_dir = data_dir.to_path_buf();
+        self
+    }
+
+    /// Milieu (era) of the sector data, like M0 or M1105.  Defaults to
+    /// travellermap.com's current milieu.
+    pub fn milieu(mut self, milieu: &str) -> GalaxyBuilder {
+        self.milieu = Some(milieu.to_string());
+        self
+    }
+
+    /// Add a sector by name, abbreviation, or alternate name.
+    pub fn sector(mut self, sector_name: &str) -> GalaxyBuilder {
+        self.sector_names.push(sector_name.to_string());
+        self
+    }
+
+    /// Add several sectors, as with sector.
+    pub fn sectors<I, S>(mut self, sector_names: I) -> GalaxyBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for sector_name in sector_names {
+            self.sector_names.push(sector_name.as_ref().to_string());
+        }
+        self
+    }
+
+    /// Add every sector in a rectangle of sector coordinates.
+    pub fn region(mut self, region: SectorRegion) -> GalaxyBuilder {
+        self.regions.push(region);
+        self
+    }
+
+    /// Add every sector within a radius of a named sector.
+    pub fn around(mut self, around: SectorsAround) -> GalaxyBuilder {
+        self.arounds.push(around);
+        self
+    }
+
+    /// Add a hand-authored sector, whose column data must already be in the
+    /// data directory.
+    pub fn custom_sector(mut self, custom: CustomSector) -> GalaxyBuilder {
+        self.custom_sectors.push(custom);
+        self
+    }
+
+    /// Base URL to download sector data from, laid out like
+    /// travellermap.com.
+    pub fn base_url(mut self, base_url: &str) -> GalaxyBuilder {
+        self.base_url = base_url.to_string();
+        self
+    }
+
+    /// Never download anything.
+    pub fn offline(mut self, offline: bool) -> GalaxyBuilder {
+        self.offline = offline;
+        self
+    }
+
+    /// Skip worlds with malformed data, rather than failing on the first
+    /// one.  See Galaxy::skipped_worlds.
+    pub fn lenient(mut self, lenient: bool) -> GalaxyBuilder {
+        self.lenient = lenient;
+        self
+    }
+
+    pub fn download_options(mut self, download_options: DownloadOptions) -> GalaxyBuilder {
+        self.download_options = download_options;
+        self
+    }
+
+    /// Read all sector data straight from source, like a MemorySource,
+    /// rather than from the data directory.  Nothing is downloaded or
+    /// cached, so data_directory, base_url, offline, and download_options
+    /// don't apply, and custom sectors need to be given with custom_sector.
+    ///
+    /// If source has a universe index, sector names are resolved with it.
+    pub fn source<S: SectorSource + Send + Sync + 'static>(mut self, source: S) -> GalaxyBuilder {
+        self.source = Some(Arc::new(source));
+        self
+    }
+
+    /// Find all the sectors, download any missing data, and parse it.
+    ///
+    /// Fails with a GalaxyError if there are no sectors, or if we're offline
+    /// and data is missing.
+    pub fn build(&self) -> Result<Galaxy> {
+        match &self.source {
+            Some(source) => self.build_from_source(source.clone()),
+            None => self.build_from_data_directory(),
+        }
+    }
+
+    fn selecting(&self) -> bool {
+        !self.regions.is_empty() || !self.arounds.is_empty()
+    }
+
+    /// Replace unknown_names in sector_names_set with their canonical names
+    /// from universe, and add the sectors selected by region and radius.
+    ///
+    /// If the universe is unavailable, names are left as they are, and the
+    /// checks for missing data will report any we can't find, but selecting
+    /// sectors fails.
+    fn resolve_with_universe(
+        &self,
+        sector_names_set: &mut HashSet<String>,
+        unknown_names: &[String],
+        universe: Result<String>,
+        universe_location: &str,
+    ) -> Result<()> {
+        match universe {
+            Ok(universe_json) => {
+                let universe = Universe::parse(&universe_json)?;
+                for sector_name in unknown_names.iter() {
+                    sector_names_set.remove(sector_name);
+                }
+                for sector_name in resolve_sector_names(&universe, unknown_names)? {
+                    sector_names_set.insert(sector_name);
+                }
+                for sector_name in select_sectors(&universe, &self.regions, &self.arounds)? {
+                    sector_names_set.insert(sector_name);
+                }
+            }
+            Err(err) => {
+                if self.selecting() {
+                    bail!("{}: {}", universe_location, err);
+                }
+                debug!("Not resolving sector names: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    fn build_from_source(&self, source: Arc<dyn SectorSource + Send + Sync>) -> Result<Galaxy> {
+        let mut sector_names_set: HashSet<String> = self.sector_names.iter().cloned().collect();
+        let mut custom_sectors = HashMap::new();
+        for custom in &self.custom_sectors {
+            let sector_name = custom
+                .name()
+                .ok_or_else(|| anyhow!("Custom sector needs a Name"))?;
+            sector_names_set.insert(sector_name.to_string());
+            custom_sectors.insert(sector_name.to_string(), custom.clone());
+        }
+        let mut unknown_names: Vec<String> = sector_names_set
+            .iter()
+            .filter(|sector_name| !custom_sectors.contains_key(*sector_name))
+            .cloned()
+            .collect();
+        unknown_names.sort();
+        self.resolve_with_universe(
+            &mut sector_names_set,
+            &unknown_names,
+            source.universe(),
+            "universe",
+        )?;
+        let sector_names = sorted_sector_names(sector_names_set)?;
+        let custom_sector_names: Vec<String> = custom_sectors.keys().cloned().collect();
+        Galaxy::from_source(
+            source,
+            &sector_names,
+            &custom_sectors,
+            custom_sector_names,
+            self.lenient,
+        )
+    }
+
+    fn build_from_data_directory(&self) -> Result<Galaxy> {
+        let data_dir = &self.data_dir;
+        let milieu = self.milieu.as_deref();
+        let options = &self.download_options;
+        create_dir_all(data_dir)?;
+
+        let mut sector_names_set: HashSet<String> = self.sector_names.iter().cloned().collect();
+        for custom in &self.custom_sectors {
+            if let Some(sector_name) = custom.name() {
+                sector_names_set.insert(sector_name.to_string());
+            }
+        }
+        let mut sector_names: Vec<String> = sector_names_set.iter().cloned().collect();
+        sector_names.sort();
+        let unknown_names =
+            unknown_sector_names(data_dir, milieu, &sector_names, &self.custom_sectors);
+        let http_source = if self.offline {
+            None
+        } else {
+            Some(HttpSource::new(&self.base_url, milieu)?)
+        };
+        if self.selecting() || !unknown_names.is_empty() {
+            if let Some(http_source) = &http_source {
+                download_universe(http_source, data_dir, milieu, options)?;
+            }
+            let fs_source = FsSource::new(data_dir, milieu);
+            self.resolve_with_universe(
+                &mut sector_names_set,
+                &unknown_names,
+                fs_source.universe(),
+                &fs_source.universe_path().display().to_string(),
+            )?;
+        }
+        let sector_names = sorted_sector_names(sector_names_set)?;
+
+        let custom_sectors =
+            find_custom_sectors(data_dir, milieu, &sector_names, &self.custom_sectors)?;
+
+        if self.offline {
+            let paths = find_missing_sector_data(data_dir, milieu, &sector_names, &custom_sectors);
+            if !paths.is_empty() {
+                return Err(GalaxyError::MissingSectorData { paths }.into());
+            }
+        } else if let Some(http_source) = &http_source {
+            download_sector_data(
+                http_source,
+                data_dir,
+                milieu,
+                &sector_names,
+                &custom_sectors,
+                options,
+            )?;
+        }
+        let source = Arc::new(FsSource::new(data_dir, milieu));
+        let custom_sector_names: Vec<String> = custom_sectors.keys().cloned().collect();
+        Galaxy::from_source(
+            source,
+            &sector_names,
+            &custom_sectors,
+            custom_sector_names,
+            self.lenient,
+        )
+    }
+}
+
+/// Return the sector names, sorted, or fail with GalaxyError::NoSectors if
+/// there are none.
+fn sorted_sector_names(sector_names_set: HashSet<String>) -> Result<Vec<String>> {
+    let mut sector_names: Vec<String> = sector_names_set.into_iter().collect();
+    sector_names.sort();
+    if sector_names.is_empty() {
+        return Err(GalaxyError::NoSectors.into());
+    }
+    debug!("{} sectors: {:?}", sector_names.len(), sector_names);
+    Ok(sector_names)
+}
+
+/// A set of sectors and their worlds, with xboat routes, ready for a
+/// TradeModel.
+#[derive(Clone, Debug)]
+pub struct Galaxy {
+    pub(crate) location_to_sector: HashMap<(i64, i64), Sector>,
+    pub(crate) coords_to_world: HashMap<Coords, World>,
+    source: Arc<dyn SectorSource + Send + Sync>,
+    custom_sector_names: Vec<String>,
+}
+
+impl Galaxy {
+    pub fn builder() -> GalaxyBuilder {
+        GalaxyBuilder::new()
+    }
+
+    /// Parse sector_names from source, which must already have all their
+    /// data, and link up xboat routes.
+    fn from_source(
+        source: Arc<dyn SectorSource + Send + Sync>,
+        sector_names: &[String],
+        custom_sectors: &HashMap<String, CustomSector>,
+        custom_sector_names: Vec<String>,
+        lenient: bool,
+    ) -> Result<Galaxy> {
+        debug!("Building sectors");
+        let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        for sector_name in sector_names {
+            let custom_opt = custom_sectors.get(sector_name);
+            let sector = Sector::new(
+                &*source,
+                sector_name.to_string(),
+                custom_opt,
+                lenient,
+                &mut coords_to_world,
+            )?;
+            location_to_sector.insert(sector.location, sector);
+        }
+        debug!("Building xboat routes");
+        for sector in location_to_sector.values() {
+            // Custom sectors have no XML, so no xboat routes of their own.
+            if custom_sector_names.contains(&sector.name) {
+                continue;
+            }
+            sector.parse_xml_routes(&*source, &location_to_sector, &mut coords_to_world)?;
+        }
+        Ok(Galaxy {
+            location_to_sector,
+            coords_to_world,
+            source,
+            custom_sector_names,
+        })
+    }
+
+    /// Return all the sectors, sorted by name.
+    pub fn sectors(&self) -> Vec<&Sector> {
+        let mut sectors: Vec<&Sector> = self.location_to_sector.values().collect();
+        sectors.sort_by(|sector1, sector2| sector1.name.cmp(&sector2.name));
+        sectors
+    }
+
+    /// Return the sector with this canonical name.
+    pub fn sector(&self, sector_name: &str) -> Option<&Sector> {
+        self.location_to_sector
+            .values()
+            .find(|sector| sector.name == sector_name)
+    }
+
+    /// Return the world at hex in the named sector.
+    pub fn world(&self, sector_name: &str, hex: &str) -> Option<&World> {
+        self.sector(sector_name)?
+            .hex_to_world(hex.to_string(), &self.coords_to_world)
+    }
+
+    /// Return all the worlds, in no particular order.
+    pub fn worlds(&self) -> impl Iterator<Item = &World> {
+        self.coords_to_world.values()
+    }
+
+    pub fn location_to_sector(&self) -> &HashMap<(i64, i64), Sector> {
+        &self.location_to_sector
+    }
+
+    pub fn coords_to_world(&self) -> &HashMap<Coords, World> {
+        &self.coords_to_world
+    }
+
+    /// Return the worlds that lenient mode left out, by sector name.
+    pub fn skipped_worlds(&self) -> Vec<ParseError> {
+        let mut skipped_worlds = Vec::new();
+        for sector in self.sectors() {
+            skipped_worlds.extend(sector.skipped_worlds.iter().cloned());
+        }
+        skipped_worlds
+    }
+
+    /// Check the sector data for problems.  See lint::lint_sectors.
+    pub fn lint(&self) -> Result<Vec<LintIssue>> {
+        lint_sectors(
+            &*self.source,
+            &self.location_to_sector,
+            &self.coords_to_world,
+            &self.custom_sector_names,
+        )
+    }
+
+    /// Return where the sector data came from.
+    pub fn source(&self) -> &dyn SectorSource {
+        &*self.source
+    }
+}
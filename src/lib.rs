@@ -0,0 +1,2048 @@
+//! Trade route calculations for Traveller, following the GURPS Traveller:
+//! Far Trader rules, over sector data from travellermap.com.
+//!
+//! Load sectors into a [`Galaxy`] with [`GalaxyBuilder`], then compute trade
+//! routes over it with a [`TradeModel`]:
+//!
+//! ```no_run
+//! use traderust::{Galaxy, TradeModel};
+//!
+//! let galaxy = Galaxy::builder().sector("Spinward Marches").build()?;
+//! let trade_map = TradeModel::new().passenger(true).compute(galaxy)?;
+//! let regina = trade_map.galaxy().world("Spinward Marches", "1910").unwrap();
+//! for (route, world) in trade_map.routes(regina) {
+//!     println!("{:?} route to {}", route, world.name());
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{anyhow, bail, Result};
+use elementtree::Element;
+use log::{debug, error, warn};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs::{read_to_string, File};
+use std::hash::Hash;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::thread::sleep;
+use std::time::Duration;
+#[macro_use]
+extern crate lazy_static;
+use rayon::prelude::*;
+use tempfile::NamedTempFile;
+
+pub mod apsp;
+use apsp::{Algorithm, Graph, ShortestPaths, NO_PRED_NODE};
+
+pub mod custom;
+use custom::CustomSector;
+
+pub mod error;
+use error::{GalaxyError, ParseError};
+
+mod galaxy;
+pub use galaxy::{Galaxy, GalaxyBuilder};
+
+pub mod lint;
+
+mod pdf;
+use pdf::generate_pdfs;
+
+pub mod source;
+use source::{
+    check_sector_data, check_sector_metadata, check_universe, is_transient, FsSource, SectorSource,
+};
+
+pub mod rules;
+use rules::{AllegianceGroups, Ruleset, TradeRules, DBTN_TO_CREDITS, IMPERIUM};
+
+pub mod scenario;
+use scenario::Scenario;
+
+pub mod t5;
+
+pub mod sweep;
+
+#[cfg(test)]
+mod tests;
+
+mod trade;
+pub use trade::{TradeMap, TradeModel};
+
+pub mod universe;
+use universe::{SectorRegion, SectorsAround, Universe};
+
+pub const MAX_TECH_LEVEL: u32 = 23;
+pub const MAX_POPULATION: u32 = 15;
+
+const MAX_DISTANCE_PENALTY: f64 = 9999.0;
+
+pub const DEFAULT_MIN_BTN: &str = "6.5";
+pub const DEFAULT_MIN_ROUTE_BTN: &str = "8.0";
+
+const NON_IMPERIAL_PORT_SIZE_PENALTY: f64 = 0.5;
+const NEIGHBOR_1_PORT_SIZE_BONUS: f64 = 1.5;
+const NEIGHBOR_2_PORT_SIZE_BONUS: f64 = 1.0;
+const XBOAT_MAJOR_ROUTE_MIN_PORT_SIZE: f64 = 6.0;
+const FEEDER_ROUTE_MIN_PORT_SIZE: f64 = 5.0;
+const MINOR_ROUTE_MIN_PORT_SIZE: f64 = 4.0;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Route {
+    Minor,
+    Feeder,
+    Intermediate,
+    Main,
+    Major,
+}
+use Route::*;
+
+lazy_static! {
+    static ref STARPORT_TRAVELLER_TO_GURPS: HashMap<char, String> = {
+        let mut sttg: HashMap<char, String> = HashMap::new();
+        sttg.insert('A', "V".to_string());
+        sttg.insert('B', "IV".to_string());
+        sttg.insert('C', "III".to_string());
+        sttg.insert('D', "II".to_string());
+        sttg.insert('E', "I".to_string());
+        sttg.insert('X', "0".to_string());
+        sttg
+    };
+    static ref MIN_BTN: f64 = f64::from_str(DEFAULT_MIN_BTN).unwrap();
+    static ref MIN_ROUTE_BTN: f64 = f64::from_str(DEFAULT_MIN_ROUTE_BTN).unwrap();
+}
+
+/// Return the default data directory, which persists between runs.
+///
+/// This follows the XDG convention of $XDG_CACHE_HOME, falling back to
+/// $HOME/.cache, and finally to the system temporary directory.
+pub fn default_data_dir() -> PathBuf {
+    let mut data_dir = if let Some(cache_home) = env::var_os("XDG_CACHE_HOME") {
+        PathBuf::from(cache_home)
+    } else if let Some(home) = env::var_os("HOME") {
+        let mut cache_home = PathBuf::from(home);
+        cache_home.push(".cache");
+        cache_home
+    } else {
+        env::temp_dir()
+    };
+    data_dir.push("traderust");
+    data_dir
+}
+
+/// Gather metadata for hand-authored sectors, keyed by sector name.
+///
+/// These come from the command line, and from sidecar files in data_dir for
+/// any of sector_names.  The command line wins if both describe a sector.
+fn find_custom_sectors(
+    data_dir: &Path,
+    milieu: Option<&str>,
+    sector_names: &[String],
+    cli_custom_sectors: &[CustomSector],
+) -> Result<HashMap<String, CustomSector>> {
+    let fs_source = FsSource::new(data_dir, milieu);
+    let mut sector_name_to_custom = HashMap::new();
+    for sector_name in sector_names {
+        let custom_path = fs_source.custom_metadata_path(sector_name);
+        if custom_path.exists() {
+            let mut custom: CustomSector = read_to_string(&custom_path)?
+                .parse()
+                .map_err(|err| anyhow!("{}: {}", custom_path.display(), err))?;
+            if custom.names.is_empty() {
+                custom.names.push(sector_name.to_string());
+            }
+            sector_name_to_custom.insert(sector_name.to_string(), custom);
+        }
+    }
+    for custom in cli_custom_sectors {
+        let sector_name = custom
+            .name()
+            .ok_or_else(|| anyhow!("Custom sector needs a Name"))?;
+        sector_name_to_custom.insert(sector_name.to_string(), custom.clone());
+    }
+    Ok(sector_name_to_custom)
+}
+
+/// Return the paths of all data files for these sectors that are not in
+/// data_dir, in sector name order.
+///
+/// Custom sectors only need their column data.
+fn find_missing_sector_data(
+    data_dir: &Path,
+    milieu: Option<&str>,
+    sector_names: &[String],
+    custom_sectors: &HashMap<String, CustomSector>,
+) -> Vec<PathBuf> {
+    let fs_source = FsSource::new(data_dir, milieu);
+    let mut missing = Vec::new();
+    for sector_name in sector_names {
+        let (data_path, metadata_path) = fs_source.paths(sector_name);
+        let mut paths = vec![data_path];
+        if !custom_sectors.contains_key(sector_name) {
+            paths.push(metadata_path);
+        }
+        for path in paths {
+            if !path.exists() {
+                missing.push(path);
+            }
+        }
+    }
+    missing
+}
+
+/// Return whether the file at path should be (re)downloaded: because it is
+/// missing, because refresh is set, or because it fails check, the same
+/// check its download has to pass.  That catches error pages, empty files,
+/// and truncated files saved by older versions.
+fn needs_download(path: &Path, refresh: bool, check: impl Fn(&str) -> Result<()>) -> bool {
+    if refresh || !path.exists() {
+        return true;
+    }
+    match read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|body| check(&body))
+    {
+        Ok(()) => false,
+        Err(err) => {
+            warn!("Replacing corrupt {}: {}", path.display(), err);
+            true
+        }
+    }
+}
+
+/// Write body to path via a temporary file in the same directory, so that
+/// an interrupted run never leaves a partial file behind.
+fn write_atomically(path: &Path, body: impl AsRef<[u8]>) -> Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut temp_file = NamedTempFile::new_in(dir)?;
+    temp_file.write_all(body.as_ref())?;
+    temp_file.persist(path)?;
+    Ok(())
+}
+
+/// How to download sector data.
+#[derive(Clone, Debug)]
+pub struct DownloadOptions {
+    /// Download files even if they are already in the data directory
+    pub refresh: bool,
+    /// Maximum number of sectors to download at once
+    pub jobs: usize,
+    /// How many times to retry a download that fails transiently
+    pub retries: u32,
+    /// How long to wait before the first retry.  Doubles for each later one.
+    pub backoff: Duration,
+    /// Print a line to stderr as each sector finishes
+    pub progress: bool,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> DownloadOptions {
+        DownloadOptions {
+            refresh: false,
+            jobs: 8,
+            retries: 3,
+            backoff: Duration::from_secs(1),
+            progress: false,
+        }
+    }
+}
+
+/// Call fetch until it succeeds, fails with an error that won't go away,
+/// or runs out of retries, backing off exponentially between tries.
+fn with_retries<T>(
+    options: &DownloadOptions,
+    description: &str,
+    mut fetch: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut backoff = options.backoff;
+    let mut retry = 0;
+    loop {
+        match fetch() {
+            Ok(val) => return Ok(val),
+            Err(err) if retry < options.retries && is_transient(&err) => {
+                retry += 1;
+                warn!(
+                    "{}: {}; retry {} of {} in {:?}",
+                    description, err, retry, options.retries, backoff
+                );
+                sleep(backoff);
+                backoff *= 2;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Fetch any sector data files that are not already in data_dir from source,
+/// and save them there, named for milieu.  If options.refresh is set, fetch
+/// them all again.
+///
+/// Up to options.jobs sectors are fetched at once.  If any fail, the others
+/// are still saved, and the error lists every failure.
+///
+/// Custom sectors are never downloaded, so their column data must already
+/// be in data_dir.
+fn download_sector_data(
+    source: &(dyn SectorSource + Sync),
+    data_dir: &Path,
+    milieu: Option<&str>,
+    sector_names: &[String],
+    custom_sectors: &HashMap<String, CustomSector>,
+    options: &DownloadOptions,
+) -> Result<()> {
+    debug!("download_sector_data");
+    let fs_source = FsSource::new(data_dir, milieu);
+    // (sector name, whether to download data, whether to download metadata)
+    let mut downloads: Vec<(&String, bool, bool)> = Vec::new();
+    for sector_name in sector_names {
+        let (data_path, metadata_path) = fs_source.paths(sector_name);
+        if custom_sectors.contains_key(sector_name) {
+            if !data_path.exists() {
+                bail!(
+                    "Custom sector {} has no data file {}",
+                    sector_name,
+                    data_path.display()
+                );
+            }
+            continue;
+        }
+        let need_data = needs_download(&data_path, options.refresh, |body| {
+            check_sector_data(sector_name, body)
+        });
+        let need_metadata = needs_download(&metadata_path, options.refresh, |body| {
+            check_sector_metadata(sector_name, body)
+        });
+        if need_data || need_metadata {
+            downloads.push((sector_name, need_data, need_metadata));
+        }
+    }
+    if downloads.is_empty() {
+        return Ok(());
+    }
+
+    let download_sector = |sector_name: &str, need_data: bool, need_metadata: bool| {
+        let (data_path, metadata_path) = fs_source.paths(sector_name);
+        if need_data {
+            let body = with_retries(options, sector_name, || source.sector_data(sector_name))?;
+            write_atomically(&data_path, &body)?;
+        }
+        if need_metadata {
+            let body = with_retries(options, sector_name, || source.sector_metadata(sector_name))?;
+            write_atomically(&metadata_path, &body)?;
+        }
+        Ok(())
+    };
+    let total = downloads.len();
+    let finished = AtomicUsize::new(0);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.jobs.max(1))
+        .build()?;
+    let results: Vec<(&String, Result<()>)> = pool.install(|| {
+        downloads
+            .par_iter()
+            .map(|(sector_name, need_data, need_metadata)| {
+                let result = download_sector(sector_name, *need_data, *need_metadata);
+                let count = finished.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+                if options.progress {
+                    let status = if result.is_ok() {
+                        "Downloaded"
+                    } else {
+                        "Failed"
+                    };
+                    eprintln!("[{}/{}] {} {}", count, total, status, sector_name);
+                }
+                (*sector_name, result)
+            })
+            .collect()
+    });
+
+    let failures: Vec<(&String, anyhow::Error)> = results
+        .into_iter()
+        .filter_map(|(sector_name, result)| result.err().map(|err| (sector_name, err)))
+        .collect();
+    if failures.is_empty() {
+        debug!("Downloaded {} sectors", total);
+        return Ok(());
+    }
+    error!(
+        "Downloaded {} of {} sectors; {} failed:",
+        total - failures.len(),
+        total,
+        failures.len()
+    );
+    let mut messages = Vec::new();
+    for (sector_name, err) in failures {
+        error!("    {}: {}", sector_name, err);
+        messages.push(format!("{}: {}", sector_name, err));
+    }
+    bail!("Failed to download sectors:\n{}", messages.join("\n"));
+}
+
+/// Fetch the universe index from source and save it in data_dir, named for
+/// milieu, unless it is already there and options.refresh is not set.
+fn download_universe(
+    source: &dyn SectorSource,
+    data_dir: &Path,
+    milieu: Option<&str>,
+    options: &DownloadOptions,
+) -> Result<()> {
+    let universe_path = FsSource::new(data_dir, milieu).universe_path();
+    if needs_download(&universe_path, options.refresh, check_universe) {
+        let body = with_retries(options, "universe", || source.universe())?;
+        write_atomically(&universe_path, &body)?;
+    }
+    Ok(())
+}
+
+/// Return the names of all sectors in regions or around arounds.
+fn select_sectors(
+    universe: &Universe,
+    regions: &[SectorRegion],
+    arounds: &[SectorsAround],
+) -> Result<Vec<String>> {
+    let mut sector_names = Vec::new();
+    for region in regions {
+        sector_names.extend(universe.sectors_in_region(region));
+    }
+    for around in arounds {
+        sector_names.extend(universe.sectors_around(around)?);
+    }
+    Ok(sector_names)
+}
+
+/// Return the sector_names that have neither data in data_dir nor custom
+/// sector metadata, so may be abbreviations or alternate names.
+fn unknown_sector_names(
+    data_dir: &Path,
+    milieu: Option<&str>,
+    sector_names: &[String],
+    cli_custom_sectors: &[CustomSector],
+) -> Vec<String> {
+    let fs_source = FsSource::new(data_dir, milieu);
+    sector_names
+        .iter()
+        .filter(|sector_name| {
+            let (data_path, _) = fs_source.paths(sector_name);
+            !data_path.exists()
+                && !fs_source.custom_metadata_path(sector_name).exists()
+                && !cli_custom_sectors
+                    .iter()
+                    .any(|custom| custom.name() == Some(sector_name.as_str()))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Return the canonical name for each of sector_names, which may be
+/// abbreviations or alternate names, in the same order.
+///
+/// The error lists every name that could not be resolved.
+fn resolve_sector_names(universe: &Universe, sector_names: &[String]) -> Result<Vec<String>> {
+    let mut canonical_names = Vec::new();
+    let mut messages = Vec::new();
+    for sector_name in sector_names {
+        match universe.resolve(sector_name) {
+            Ok(sector) => canonical_names.push(sector.name().to_string()),
+            Err(err) => messages.push(err.to_string()),
+        }
+    }
+    if !messages.is_empty() {
+        bail!(messages.join("\n"));
+    }
+    Ok(canonical_names)
+}
+
+/// Parse header and separator and return [(start, end, field)]
+fn parse_header_and_separator(header: &str, separator: &str) -> Vec<(usize, usize, String)> {
+    let headers: Vec<&str> = header.split_whitespace().collect();
+    let separators = separator.split_whitespace();
+    let mut fields: Vec<(usize, usize, String)> = Vec::new();
+    let mut column = 0;
+    for (ii, hyphens) in separators.enumerate() {
+        let field = headers[ii];
+        let start = column;
+        let width = hyphens.len();
+        let end = column + width;
+        fields.push((start, end, field.to_string()));
+        column += width + 1;
+    }
+    fields
+}
+
+/// Map a column header from the tab-delimited format to the equivalent
+/// header from the fixed-width format.
+fn tab_field_name(header: &str) -> &str {
+    match header {
+        "Bases" => "B",
+        "Zone" => "Z",
+        "Allegiance" => "A",
+        "Stars" => "Stellar",
+        "Nobility" => "N",
+        _ => header,
+    }
+}
+
+/// Fail if there are too many worlds to number with u32s that stay clear
+/// of the INFINITY and NO_PRED_NODE sentinels.
+fn check_num_worlds(num_worlds: usize) -> Result<(), GalaxyError> {
+    if num_worlds >= NO_PRED_NODE as usize {
+        return Err(GalaxyError::TooManyWorlds { num_worlds });
+    }
+    Ok(())
+}
+
+/// Build the graph of jumps between worlds, for finding minimum distances
+/// and paths between them.  Only use jumps of up to max_jump hexes, except
+/// along xboat routes if ignore_xboat_routes is not set.
+/// Must be run after all neighbors are built.
+fn populate_navigable_distances(
+    sorted_coords: &Vec<Coords>,
+    coords_to_world: &HashMap<Coords, World>,
+    max_jump: u64,
+    ignore_xboat_routes: bool,
+    scenario: &Scenario,
+    alg: Algorithm,
+) -> Result<ShortestPaths> {
+    debug!("populate_navigable_distances max_jump={}", max_jump);
+    let num_worlds = sorted_coords.len();
+    check_num_worlds(num_worlds)?;
+    let mut edges: Vec<(u32, u32, u32)> = Vec::new();
+    for (ii, coords) in sorted_coords.iter().enumerate() {
+        let world = coords_to_world.get(coords).unwrap();
+        for jump in 1..=max_jump {
+            for coords in &world.neighbors[jump as usize] {
+                let neighbor = coords_to_world.get(coords).unwrap();
+                let jj = neighbor.index.unwrap();
+                edges.push((ii as u32, jj as u32, jump as u32));
+            }
+        }
+        if !ignore_xboat_routes {
+            for coords in &world.xboat_routes {
+                let neighbor = coords_to_world.get(coords).unwrap();
+                if scenario.closed(&world.allegiance, &neighbor.allegiance) {
+                    continue;
+                }
+                let jj = neighbor.index.unwrap();
+                edges.push((ii as u32, jj as u32, world.straight_line_distance(neighbor)));
+            }
+        }
+    }
+    let graph = Graph::from_edges(num_worlds, &edges);
+    debug!(
+        "shortest_paths alg={:?} worlds={} edges={}",
+        alg,
+        num_worlds,
+        graph.num_edges()
+    );
+    Ok(ShortestPaths::new(graph, alg))
+}
+
+/// Prepare dist to answer questions about each pair of coords, in both
+/// directions, without a full search from every world.
+fn prepare_navigable_distances(
+    coords_pairs: &[CoordsPair],
+    coords_to_world: &HashMap<Coords, World>,
+    dist: &ShortestPaths,
+) {
+    let mut source_to_targets: HashMap<u32, HashSet<u32>> = HashMap::new();
+    for (coords1, coords2) in coords_pairs {
+        let ii = coords_to_world.get(coords1).unwrap().index.unwrap() as u32;
+        let jj = coords_to_world.get(coords2).unwrap().index.unwrap() as u32;
+        source_to_targets.entry(ii).or_default().insert(jj);
+        source_to_targets.entry(jj).or_default().insert(ii);
+    }
+    dist.prepare(&source_to_targets);
+}
+
+fn same_allegiance(allegiance1: &str, allegiance2: &str, groups: &AllegianceGroups) -> bool {
+    if let Some(group) = groups.group(allegiance1) {
+        return groups.group(allegiance2) == Some(group);
+    }
+    if allegiance1 != allegiance2 {
+        return false;
+    }
+    if allegiance1.starts_with("Na") || allegiance1.starts_with("Cs") {
+        // Non-aligned worlds and client states with the same code are not
+        // necessarily the same allegiance
+        return false;
+    }
+    true
+}
+
+fn btn_to_route(btn: f64, min_route_btn: f64) -> Option<Route> {
+    let major_route_threshold: f64 = min_route_btn + Major as u64 as f64;
+    let main_route_threshold: f64 = min_route_btn + Main as u64 as f64;
+    let intermediate_route_threshold: f64 = min_route_btn + Intermediate as u64 as f64;
+    let feeder_route_threshold: f64 = min_route_btn + Feeder as u64 as f64;
+    let minor_route_threshold: f64 = min_route_btn;
+    if btn >= major_route_threshold {
+        Some(Major)
+    } else if btn >= main_route_threshold {
+        Some(Main)
+    } else if btn >= intermediate_route_threshold {
+        Some(Intermediate)
+    } else if btn >= feeder_route_threshold {
+        Some(Feeder)
+    } else if btn >= minor_route_threshold {
+        Some(Minor)
+    } else {
+        None
+    }
+}
+
+fn find_max_allowed_jump(btn: f64, max_jumps: &RouteCounter, min_route_btn: f64) -> u64 {
+    let route_opt = btn_to_route(btn, min_route_btn);
+    if let Some(route) = route_opt {
+        max_jumps[&route]
+    } else {
+        max_jumps[&Minor]
+    }
+}
+
+/// Return the highest BTN allowed between worlds with these WTNs: the rules'
+/// cap, but no more than the highest BTN that has a credit value.
+fn max_btn(wtn1: f64, wtn2: f64, rules: &dyn TradeRules) -> f64 {
+    let table_max_btn = (rules.dbtn_to_credits().len() - 1) as f64 / 2.0;
+    f64::min(rules.btn_cap(wtn1, wtn2), table_max_btn)
+}
+
+/// Fill in major_routes, main_routes, intermediate_routes, minor_routes,
+/// and feeder_routes for all Worlds.
+///
+/// This must be called after all Sectors and Worlds are mostly built.
+/// The rules say: main: 10+  feeder: 9-9.5  minor: 8-8.5
+/// The wiki says: blue major 12, cyan main 11, green intermediate 10,
+///                yellow feeder 9, red minor 8, no line 1-7
+/// The wiki version is more fun so we'll use that.
+fn populate_trade_routes(
+    coords_to_world: &mut HashMap<Coords, World>,
+    min_btn: f64,
+    min_route_btn: f64,
+    passenger: bool,
+    max_jumps: &RouteCounter,
+    dists: &HashMap<u64, ShortestPaths>,
+    rules: &dyn TradeRules,
+) {
+    debug!("populate_trade_routes");
+    let mut dwtn_coords: Vec<(u64, Coords)> = Vec::new();
+    for (coords, world) in coords_to_world.iter() {
+        // wtn can have 0.5 so double it to make a sortable integer
+        let dwtn = (world.wtn(rules) * 2.0) as u64;
+        dwtn_coords.push((dwtn, *coords));
+    }
+    dwtn_coords.sort();
+    dwtn_coords.reverse();
+
+    debug!("Building world trade pairs");
+    let mut coords_pairs: Vec<CoordsPair> = Vec::new();
+    for (ii, (dwtn1, coords1)) in dwtn_coords.iter().enumerate() {
+        let wtn1 = *dwtn1 as f64 / 2.0;
+        for (dwtn2, coords2) in dwtn_coords.iter().skip(ii + 1) {
+            let wtn2 = *dwtn2 as f64 / 2.0;
+            if max_btn(wtn1, wtn2, rules) < min_btn
+                || wtn1 + wtn2 < min_btn - rules.max_pair_bonus()
+            {
+                // If the lower WTN or the sum of the WTNs is small enough, we
+                // know that coords2 and later worlds won't come close to
+                // forming any trade routes with coords1.
+                break;
+            }
+            let sld = coords1.straight_line_distance(coords2) as u32;
+            let max_btn1 = wtn1 + wtn2 - rules.distance_modifier(sld);
+            if max_btn1 < min_btn - rules.max_pair_bonus() {
+                // BTN can't be more than the sum of the WTNs plus the bonus,
+                // so if even the straight line distance modifier puts us too
+                // low, we can't come close to forming any trade routes with
+                // world2.
+                continue;
+            }
+            let world1 = coords_to_world.get(coords1).unwrap();
+            let world2 = coords_to_world.get(coords2).unwrap();
+            if max_btn1 < min_btn + rules.max_pair_penalty() {
+                // Computing the pair modifier is cheaper than finding the
+                // full BTN
+                let max_btn2 = max_btn1 + rules.pair_modifier(world1, world2);
+                if max_btn2 < min_btn {
+                    continue;
+                }
+            }
+            // At this point we have exhausted ways to skip world2 without
+            // computing the BTN.
+            coords_pairs.push((*coords1, *coords2));
+        }
+    }
+
+    let max_max_jump: u64 = *max_jumps.values().max().unwrap();
+
+    debug!("(parallel) Finding navigable distances");
+    // Any of these pairs might become a route, and a route might use any
+    // jump up to its own max, so we need paths for all of them.
+    for dist in dists.values() {
+        prepare_navigable_distances(&coords_pairs, coords_to_world, dist);
+    }
+
+    debug!("(parallel) Finding BTNs");
+    // This will consider all jumps, even those only allowed for higher routes.
+    // So we need to filter some out later.
+    let dist = dists.get(&max_max_jump).unwrap();
+    let coords_pair_dbtn: Vec<(Coords, Coords, usize)> = coords_pairs
+        .into_par_iter()
+        .map(|(coords1, coords2)| {
+            let world1 = coords_to_world.get(&coords1).unwrap();
+            let world2 = coords_to_world.get(&coords2).unwrap();
+            let btn = world1.btn(world2, dist, passenger, rules);
+            let dbtn = (2.0 * btn) as usize;
+            (coords1, coords2, dbtn)
+        })
+        .collect();
+
+    debug!("Recording BTNs");
+    for (coords1, coords2, dbtn) in coords_pair_dbtn {
+        let credits = rules.dbtn_to_credits()[dbtn];
+        coords_to_world
+            .get_mut(&coords1)
+            .unwrap()
+            .endpoint_trade_credits += credits;
+        coords_to_world
+            .get_mut(&coords2)
+            .unwrap()
+            .endpoint_trade_credits += credits;
+
+        coords_to_world.get_mut(&coords1).unwrap().dbtn_to_coords[dbtn].insert(coords2);
+        coords_to_world.get_mut(&coords2).unwrap().dbtn_to_coords[dbtn].insert(coords1);
+    }
+
+    debug!("(parallel) Finding route paths");
+
+    let mut sorted_coords: Vec<Coords> = coords_to_world.keys().cloned().collect();
+    sorted_coords.sort();
+
+    let result_tuples: Vec<(HashMap<CoordsPair, RouteCounter>, HashMap<Coords, u64>)> = dwtn_coords
+        .into_par_iter()
+        .map(|(_, coords)| {
+            coords_to_world.get(&coords).unwrap().find_route_paths(
+                &sorted_coords,
+                coords_to_world,
+                max_jumps,
+                min_route_btn,
+                dists,
+                rules,
+            )
+        })
+        .collect();
+    let mut route_paths: HashMap<CoordsPair, RouteCounter> = HashMap::new();
+    let mut coords_to_transient_credits: HashMap<Coords, u64> = HashMap::new();
+
+    for (rp, cttc) in result_tuples {
+        for (coords_pair, new_route_to_count) in rp {
+            route_paths
+                .entry(coords_pair)
+                .and_modify(|route_to_count| {
+                    for (key, new_val) in new_route_to_count.iter() {
+                        route_to_count
+                            .entry(*key)
+                            .and_modify(|prev| *prev += new_val)
+                            .or_insert(*new_val);
+                    }
+                })
+                .or_insert(new_route_to_count);
+        }
+
+        for (coords, credits) in cttc {
+            coords_to_transient_credits
+                .entry(coords)
+                .and_modify(|count| *count += credits)
+                .or_insert(credits);
+        }
+    }
+
+    debug!("Inserting trade routes");
+    for ((coords1, coords2), route_to_count) in route_paths {
+        let mut num_major_routes = 0;
+        let mut num_main_routes = 0;
+        let mut num_intermediate_routes = 0;
+        let mut num_feeder_routes = 0;
+        let mut num_minor_routes = 0;
+        if let Some(major_routes) = route_to_count.get(&Major) {
+            num_major_routes += major_routes;
+        };
+        if let Some(main_routes) = route_to_count.get(&Main) {
+            num_main_routes += main_routes;
+        };
+        if let Some(intermediate_routes) = route_to_count.get(&Intermediate) {
+            num_intermediate_routes += intermediate_routes;
+        };
+        if let Some(feeder_routes) = route_to_count.get(&Feeder) {
+            num_feeder_routes += feeder_routes;
+        };
+        if let Some(minor_routes) = route_to_count.get(&Minor) {
+            num_minor_routes += minor_routes;
+        };
+        if num_minor_routes >= 3 {
+            num_feeder_routes += 1;
+            num_minor_routes = 0;
+        }
+        if num_feeder_routes >= 3 {
+            num_intermediate_routes += 1;
+            num_feeder_routes = 0;
+        }
+        if num_intermediate_routes >= 3 {
+            num_main_routes += 1;
+            num_intermediate_routes = 0;
+        }
+        if num_main_routes >= 3 {
+            num_major_routes += 1;
+            num_main_routes = 0;
+        }
+
+        if num_major_routes >= 1 {
+            coords_to_world
+                .get_mut(&coords1)
+                .unwrap()
+                .major_routes
+                .insert(coords2);
+            coords_to_world
+                .get_mut(&coords2)
+                .unwrap()
+                .major_routes
+                .insert(coords1);
+        } else if num_main_routes >= 1 {
+            coords_to_world
+                .get_mut(&coords1)
+                .unwrap()
+                .main_routes
+                .insert(coords2);
+            coords_to_world
+                .get_mut(&coords2)
+                .unwrap()
+                .main_routes
+                .insert(coords1);
+        } else if num_intermediate_routes >= 1 {
+            coords_to_world
+                .get_mut(&coords1)
+                .unwrap()
+                .intermediate_routes
+                .insert(coords2);
+            coords_to_world
+                .get_mut(&coords2)
+                .unwrap()
+                .intermediate_routes
+                .insert(coords1);
+        } else if num_feeder_routes >= 1 {
+            coords_to_world
+                .get_mut(&coords1)
+                .unwrap()
+                .feeder_routes
+                .insert(coords2);
+            coords_to_world
+                .get_mut(&coords2)
+                .unwrap()
+                .feeder_routes
+                .insert(coords1);
+        } else if num_minor_routes >= 1 {
+            coords_to_world
+                .get_mut(&coords1)
+                .unwrap()
+                .minor_routes
+                .insert(coords2);
+            coords_to_world
+                .get_mut(&coords2)
+                .unwrap()
+                .minor_routes
+                .insert(coords1);
+        }
+    }
+
+    debug!("Updating transient credits");
+    for (coords, credits) in coords_to_transient_credits {
+        coords_to_world
+            .get_mut(&coords)
+            .unwrap()
+            .transient_trade_credits += credits;
+    }
+}
+
+/// Absolute coordinates
+/// x is an integer
+/// y2 is an integer, equal to 2 * y
+/// This is needed because y is sometimes a float and floats can't be hash keys
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Coords {
+    x: i64,
+    y2: i64,
+}
+
+impl Coords {
+    fn new(xf: f64, yf: f64) -> Coords {
+        let x = xf as i64;
+        let y2 = (yf * 2.0) as i64;
+        Coords { x, y2 }
+    }
+
+    fn straight_line_distance(&self, other: &Coords) -> u32 {
+        let (x1, y1) = <(f64, f64)>::from(*self);
+        let (x2, y2) = <(f64, f64)>::from(*other);
+        let xdelta = f64::abs(x2 - x1);
+        let mut ydelta = f64::abs(y2 - y1) - xdelta / 2.0;
+        if ydelta < 0.0 {
+            ydelta = 0.0;
+        }
+        (f64::floor(xdelta + ydelta)) as u32
+    }
+}
+
+impl From<Coords> for (f64, f64) {
+    fn from(coords: Coords) -> (f64, f64) {
+        let fx = coords.x as f64;
+        let fy = coords.y2 as f64 / 2.0;
+        (fx, fy)
+    }
+}
+
+impl Ord for Coords {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.x.cmp(&other.x) {
+            Ordering::Less => Ordering::Less,
+            Ordering::Greater => Ordering::Greater,
+            Ordering::Equal => self.y2.cmp(&other.y2),
+        }
+    }
+}
+
+impl PartialOrd for Coords {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.x.partial_cmp(&other.x) {
+            None => None,
+            Some(Ordering::Less) => Some(Ordering::Less),
+            Some(Ordering::Greater) => Some(Ordering::Greater),
+            Some(Ordering::Equal) => self.y2.partial_cmp(&other.y2),
+        }
+    }
+}
+
+impl fmt::Display for Coords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y2)
+    }
+}
+
+type CoordsPair = (Coords, Coords);
+/// A number of jumps, or of routes, for each kind of route
+pub type RouteCounter = HashMap<Route, u64>;
+
+#[derive(Clone, Debug, Eq)]
+pub struct World {
+    sector_location: (i64, i64),
+    sector_name: String,
+    hex: String,
+    name: String,
+    uwp: String,
+    trade_classifications: HashSet<String>,
+    importance: i64,
+    economic: String,
+    cultural: String,
+    nobles: String,
+    bases: HashSet<String>,
+    zone: char,
+    pbg: String,
+    worlds: u64,
+    allegiance: String,
+    stars: Vec<String>,
+    endpoint_trade_credits: u64,
+    transient_trade_credits: u64,
+    xboat_routes: HashSet<Coords>,
+    dbtn_to_coords: Vec<HashSet<Coords>>,
+    major_routes: HashSet<Coords>,
+    main_routes: HashSet<Coords>,
+    intermediate_routes: HashSet<Coords>,
+    feeder_routes: HashSet<Coords>,
+    minor_routes: HashSet<Coords>,
+    neighbors: Vec<HashSet<Coords>>,
+    index: Option<usize>,
+}
+
+impl World {
+    /// Build a World from a line of fixed-width column data, using fields
+    /// from parse_header_and_separator.
+    fn new(
+        line: String,
+        fields: &[(usize, usize, String)],
+        sector_location: (i64, i64),
+        sector_name: String,
+    ) -> World {
+        let mut values: Vec<(&str, String)> = Vec::new();
+        let mut iter = line.chars().enumerate();
+        for (start, end, field) in fields.iter() {
+            // This intricate loop is to handle the occasional multi-byte
+            // UTF-8 character like in Khiinra Ash/Core
+            let mut value: String = "".to_string();
+            loop {
+                let tup_opt: Option<(usize, char)> = iter.next();
+                if let Some((ii, ch)) = tup_opt {
+                    if ii >= *start && ii < *end {
+                        value.push(ch);
+                    } else if ii >= *end {
+                        break;
+                    }
+                } else {
+                    // end of line
+                    break;
+                }
+            }
+            values.push((field, value));
+        }
+
+        World::from_values(&values, sector_location, sector_name)
+    }
+
+    /// Build a World from a line of tab-delimited data, as published by
+    /// travellermap.com and the T5 Second Survey, using the column headers.
+    fn new_tab_delimited(
+        line: String,
+        headers: &[String],
+        sector_location: (i64, i64),
+        sector_name: String,
+    ) -> World {
+        let values: Vec<(&str, String)> = headers
+            .iter()
+            .zip(line.split('\t'))
+            .map(|(header, value)| (tab_field_name(header), value.trim().to_string()))
+            .collect();
+
+        World::from_values(&values, sector_location, sector_name)
+    }
+
+    /// Build a World from (field, value) pairs, where the field names are
+    /// the fixed-width column headers.
+    fn from_values(
+        values: &[(&str, String)],
+        sector_location: (i64, i64),
+        sector_name: String,
+    ) -> World {
+        let mut hex = "".to_string();
+        let mut name = "".to_string();
+        let mut uwp = "".to_string();
+        let mut trade_classifications = HashSet::new();
+        let mut importance = 0;
+        let mut economic = "".to_string();
+        let mut cultural = "".to_string();
+        let mut nobles = "".to_string();
+        let mut bases = HashSet::new();
+        let mut zone = 'G';
+        let mut pbg = "".to_string();
+        let mut worlds = 0;
+        let mut allegiance = "".to_string();
+        let mut stars = Vec::new();
+        let endpoint_trade_credits = 0;
+        let transient_trade_credits = 0;
+        let xboat_routes = HashSet::new();
+        let mut dbtn_to_coords = Vec::new();
+        // Pre-populate every dbtn bucket with an empty set so we don't need
+        // to deal with checking later.
+        for _ in 0..DBTN_TO_CREDITS.len() {
+            dbtn_to_coords.push(HashSet::new());
+        }
+        let major_routes = HashSet::new();
+        let main_routes = HashSet::new();
+        let intermediate_routes = HashSet::new();
+        let feeder_routes = HashSet::new();
+        let minor_routes = HashSet::new();
+        let neighbors = Vec::new();
+        let index = None;
+
+        for (field, value) in values.iter() {
+            match *field {
+                "Hex" => hex = value.to_string(),
+                "Name" => name = value.trim().to_string(),
+                "UWP" => uwp = value.to_string(),
+                "Remarks" => {
+                    for tc in value.split_whitespace() {
+                        trade_classifications.insert(tc.to_string());
+                    }
+                }
+                "{Ix}" => {
+                    let trimmed = value
+                        .trim_matches(|c| c == '{' || c == '}' || c == ' ')
+                        .to_string();
+                    if !trimmed.is_empty() {
+                        if let Ok(val) = trimmed.parse() {
+                            importance = val;
+                        }
+                    }
+                }
+                "(Ex)" => economic = value.trim_matches(|c| c == '(' || c == ')').to_string(),
+                "[Cx]" => cultural = value.trim_matches(|c| c == '[' || c == ']').to_string(),
+                "N" => nobles = value.trim_matches(|c| c == ' ' || c == '-').to_string(),
+                "B" => {
+                    let trimmed = value.trim_matches(|c| c == ' ' || c == '-').to_string();
+                    if !trimmed.is_empty() {
+                        for ch in trimmed.chars() {
+                            bases.insert(ch.to_string());
+                        }
+                    }
+                }
+                "Z" => {
+                    let trimmed = value.trim_matches(|c| c == ' ' || c == '-').to_string();
+                    if !trimmed.is_empty() {
+                        zone = trimmed.chars().next().unwrap();
+                    }
+                }
+                "PBG" => pbg = value.trim().to_string(),
+                "W" => {
+                    let trimmed = value
+                        .trim_matches(|c| c == '{' || c == '}' || c == ' ')
+                        .to_string();
+                    if !trimmed.is_empty() {
+                        if let Ok(val) = trimmed.parse() {
+                            worlds = val;
+                        }
+                    }
+                }
+                "A" => allegiance = value.to_string(),
+                "Stellar" => {
+                    let parts: Vec<&str> = value.split_whitespace().collect();
+                    let mut ii = 0;
+                    while ii < parts.len() {
+                        let star = parts[ii];
+                        if star == "BD" || star == "D" || star == "BH" {
+                            stars.push(star.to_owned());
+                            ii += 1;
+                        } else if parts.len() > ii + 1 {
+                            stars.push(star.to_owned() + " " + parts[ii + 1]);
+                            ii += 2;
+                        } else {
+                            stars.push(star.to_owned());
+                            ii += 1;
+                        }
+                    }
+                }
+                &_ => (),
+            }
+        }
+
+        World {
+            sector_location,
+            sector_name,
+            hex,
+            name,
+            uwp,
+            trade_classifications,
+            importance,
+            economic,
+            cultural,
+            nobles,
+            bases,
+            zone,
+            pbg,
+            worlds,
+            allegiance,
+            stars,
+            endpoint_trade_credits,
+            transient_trade_credits,
+            xboat_routes,
+            dbtn_to_coords,
+            major_routes,
+            main_routes,
+            intermediate_routes,
+            feeder_routes,
+            minor_routes,
+            neighbors,
+            index,
+        }
+    }
+
+    /// Return the name and value of the first field that would keep us from
+    /// using this world, or None if it's fine.
+    ///
+    /// The accessors for UWP and PBG digits assume this has passed.
+    fn bad_field(&self) -> Option<(&'static str, String)> {
+        if self.hex.len() != 4 || !self.hex.chars().all(|ch| ch.is_ascii_digit()) {
+            return Some(("Hex", self.hex.to_string()));
+        }
+        let uwp: Vec<char> = self.uwp.chars().collect();
+        let uwp_ok = uwp.len() >= 9
+            && (uwp[0] == '?' || STARPORT_TRAVELLER_TO_GURPS.contains_key(&uwp[0]))
+            && (!uwp[4].is_alphanumeric() || uwp[4] == 'X' || uwp[4].is_digit(MAX_POPULATION + 1))
+            && (uwp[8] == '?' || uwp[8] == 'X' || uwp[8].is_digit(MAX_TECH_LEVEL + 1));
+        if !uwp_ok {
+            return Some(("UWP", self.uwp.to_string()));
+        }
+        if self.pbg.chars().count() < 3 {
+            return Some(("PBG", self.pbg.to_string()));
+        }
+        None
+    }
+
+    /// Find and cache all neighbors within 3 hexes.
+    ///
+    /// This must be run after all Sectors and Worlds are mostly initialized.
+    fn populate_neighbors(
+        &mut self,
+        coords_to_world: &HashMap<Coords, World>,
+        max_jump: u64,
+        disallow_red_zones: bool,
+        scenario: &Scenario,
+    ) {
+        self.neighbors.clear();
+        // The 0 index is unused, but fill it in anyway to make the other
+        // indexes nicer.
+        for _jump in 0..=max_jump {
+            self.neighbors.push(HashSet::new());
+        }
+        if !self.can_refuel(disallow_red_zones) {
+            return;
+        }
+        let (x, y) = <(f64, f64)>::from(self.get_coords());
+        let mut xx = x - max_jump as f64;
+        while xx <= x + max_jump as f64 {
+            let mut yy = y - max_jump as f64;
+            while yy <= y + max_jump as f64 {
+                let world_opt = coords_to_world.get(&Coords::new(xx, yy));
+                if let Some(world) = world_opt {
+                    if world != self
+                        && world.can_refuel(disallow_red_zones)
+                        && !scenario.closed(&self.allegiance, &world.allegiance)
+                    {
+                        let distance = self.straight_line_distance(world);
+                        if distance <= max_jump as u32 {
+                            self.neighbors[distance as usize].insert(world.get_coords());
+                        }
+                    }
+                }
+                yy += 0.5;
+            }
+            xx += 1.0;
+        }
+    }
+
+    /// Forget the trade routes and credits from an earlier
+    /// populate_trade_routes, but keep neighbors and xboat routes.
+    fn clear_trade_routes(&mut self) {
+        self.endpoint_trade_credits = 0;
+        self.transient_trade_credits = 0;
+        for coords_set in self.dbtn_to_coords.iter_mut() {
+            coords_set.clear();
+        }
+        self.major_routes.clear();
+        self.main_routes.clear();
+        self.intermediate_routes.clear();
+        self.feeder_routes.clear();
+        self.minor_routes.clear();
+    }
+
+    fn starport(&self) -> char {
+        return self.uwp.chars().next().unwrap() as char;
+    }
+
+    fn g_starport(&self) -> String {
+        let mut starport = self.starport();
+        if starport == '?' {
+            starport = 'X';
+        }
+        let opt = STARPORT_TRAVELLER_TO_GURPS.get(&starport);
+        opt.unwrap().to_string()
+    }
+
+    fn size(&self) -> char {
+        return self.uwp.chars().nth(1).unwrap() as char;
+    }
+
+    fn atmosphere(&self) -> char {
+        return self.uwp.chars().nth(2).unwrap() as char;
+    }
+
+    fn hydrosphere(&self) -> char {
+        return self.uwp.chars().nth(3).unwrap() as char;
+    }
+
+    fn population(&self) -> char {
+        return self.uwp.chars().nth(4).unwrap() as char;
+    }
+
+    pub fn government(&self) -> char {
+        return self.uwp.chars().nth(5).unwrap() as char;
+    }
+
+    pub fn law_level(&self) -> char {
+        return self.uwp.chars().nth(6).unwrap() as char;
+    }
+
+    pub fn importance(&self) -> i64 {
+        self.importance
+    }
+
+    pub fn economic(&self) -> String {
+        self.economic.clone()
+    }
+
+    pub fn cultural(&self) -> String {
+        self.cultural.clone()
+    }
+
+    pub fn nobles(&self) -> String {
+        self.nobles.clone()
+    }
+
+    pub fn bases(&self) -> HashSet<String> {
+        self.bases.iter().cloned().collect()
+    }
+
+    pub fn worlds(&self) -> u64 {
+        self.worlds
+    }
+
+    pub fn stars(&self) -> Vec<String> {
+        self.stars.to_vec()
+    }
+
+    pub fn sector_name(&self) -> String {
+        self.sector_name.clone()
+    }
+
+    pub fn hex(&self) -> String {
+        self.hex.clone()
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn uwp(&self) -> String {
+        self.uwp.clone()
+    }
+
+    pub fn trade_classifications(&self) -> HashSet<String> {
+        self.trade_classifications.iter().cloned().collect()
+    }
+
+    pub fn zone(&self) -> char {
+        self.zone
+    }
+
+    pub fn allegiance(&self) -> String {
+        self.allegiance.clone()
+    }
+
+    /// Credits of trade that start or end at this world
+    pub fn endpoint_trade_credits(&self) -> u64 {
+        self.endpoint_trade_credits
+    }
+
+    /// Credits of trade that pass through this world
+    pub fn transient_trade_credits(&self) -> u64 {
+        self.transient_trade_credits
+    }
+
+    fn tech_level(&self) -> char {
+        return self.uwp.chars().nth(8).unwrap() as char;
+    }
+
+    fn g_tech_level(&self, rules: &Ruleset) -> u64 {
+        let mut tech_level_char = self.tech_level();
+        if tech_level_char == '?' || tech_level_char == 'X' {
+            tech_level_char = '0';
+        }
+        let tech_level_int = tech_level_char.to_digit(MAX_TECH_LEVEL + 1).unwrap() as usize;
+        rules.tech_level_traveller_to_gurps[tech_level_int]
+    }
+
+    fn gas_giants(&self) -> char {
+        self.pbg.chars().nth(2).unwrap()
+    }
+
+    fn can_refuel(&self, disallow_red_zones: bool) -> bool {
+        if disallow_red_zones && self.zone == 'R' {
+            false
+        } else {
+            self.gas_giants() != '0'
+                || (self.zone != 'R'
+                    && ((self.starport() != 'E' && self.starport() != 'X')
+                        || self.hydrosphere() != '0'))
+        }
+    }
+
+    fn uwtn(&self, rules: &Ruleset) -> f64 {
+        let gt3 = self.g_tech_level(rules) / 3;
+        let tl_mod = gt3 as f64 / 2.0 - 0.5;
+        let pop_char = self.population();
+        let mut pop_mod = 0.0;
+        if pop_char.is_alphanumeric() && pop_char != 'X' {
+            // ignore '?'
+            let pop_int = pop_char.to_digit(MAX_POPULATION + 1).unwrap();
+            pop_mod = pop_int as f64 / 2.0;
+        }
+        tl_mod + pop_mod as f64
+    }
+
+    fn wtn_port_modifier(&self, rules: &Ruleset) -> f64 {
+        rules.wtn_port_modifier(self.uwtn(rules), &self.g_starport())
+    }
+
+    pub fn wtn(&self, rules: &dyn TradeRules) -> f64 {
+        rules.wtn(self)
+    }
+
+    fn wtcm(&self, other: &World, rules: &Ruleset) -> f64 {
+        let mut result = 0.0;
+
+        if (self.trade_classifications.contains("Ag")
+            && (other.trade_classifications.contains("Ex")
+                || other.trade_classifications.contains("Na")))
+            || (other.trade_classifications.contains("Ag")
+                && (self.trade_classifications.contains("Ex")
+                    || self.trade_classifications.contains("Na")))
+        {
+            result += rules.ag_wtcm_bonus;
+        }
+
+        if (self.trade_classifications.contains("In") && other.trade_classifications.contains("Ni"))
+            || (other.trade_classifications.contains("In")
+                && self.trade_classifications.contains("Ni"))
+        {
+            result += rules.in_wtcm_bonus;
+        }
+        if !same_allegiance(
+            &self.allegiance,
+            &other.allegiance,
+            &rules.allegiance_groups,
+        ) {
+            result -= rules.different_allegiance_wtcm_penalty;
+        }
+        result -= rules.scenario.penalty(&self.allegiance, &other.allegiance);
+        result
+    }
+
+    fn get_coords(&self) -> Coords {
+        let hex = &self.hex;
+        let location = self.sector_location;
+        let mut scratch = String::new();
+        scratch.push(hex.chars().next().unwrap());
+        scratch.push(hex.chars().nth(1).unwrap());
+        let x: i64 = scratch.parse::<i64>().unwrap() + 32 * location.0;
+        scratch.clear();
+        scratch.push(hex.chars().nth(2).unwrap());
+        scratch.push(hex.chars().nth(3).unwrap());
+        let y: i64 = scratch.parse::<i64>().unwrap() + 40 * location.1;
+        let mut y2 = 2 * y;
+        if x & 1 == 0 {
+            y2 += 1;
+        }
+        Coords { x, y2 }
+    }
+
+    fn straight_line_distance(&self, other: &World) -> u32 {
+        let (x1, y1) = <(f64, f64)>::from(self.get_coords());
+        let (x2, y2) = <(f64, f64)>::from(other.get_coords());
+        let xdelta = f64::abs(x2 - x1);
+        let mut ydelta = f64::abs(y2 - y1) - xdelta / 2.0;
+        if ydelta < 0.0 {
+            ydelta = 0.0;
+        }
+        (f64::floor(xdelta + ydelta)) as u32
+    }
+
+    fn navigable_distance(&self, other: &World, dist: &ShortestPaths) -> u32 {
+        let ii = self.index.unwrap();
+        let jj = other.index.unwrap();
+        dist.distance(ii as u32, jj as u32)
+    }
+
+    /// Return the inclusive path from self to other.
+    fn navigable_path(
+        &self,
+        other: &World,
+        sorted_coords: &[Coords],
+        dist: &ShortestPaths,
+    ) -> Option<Vec<Coords>> {
+        let ii = self.index.unwrap();
+        let jj = other.index.unwrap();
+        let path = dist.path(ii as u32, jj as u32)?;
+        Some(
+            path.iter()
+                .map(|index| sorted_coords[*index as usize])
+                .collect(),
+        )
+    }
+
+    fn distance_modifier(
+        &self,
+        other: &World,
+        dist: &ShortestPaths,
+        rules: &dyn TradeRules,
+    ) -> f64 {
+        let distance = self.navigable_distance(other, dist);
+        rules.distance_modifier(distance)
+    }
+
+    fn btn(
+        &self,
+        other: &World,
+        dist: &ShortestPaths,
+        passenger: bool,
+        rules: &dyn TradeRules,
+    ) -> f64 {
+        let wtn1 = self.wtn(rules);
+        let wtn2 = other.wtn(rules);
+        let base_btn = wtn1 + wtn2 + rules.pair_modifier(self, other);
+        let mut btn = base_btn - self.distance_modifier(other, dist, rules);
+        if passenger {
+            btn += rules.passenger_modifier(self, other);
+        }
+        f64::max(rules.min_btn(), f64::min(btn, max_btn(wtn1, wtn2, rules)))
+    }
+
+    /// Build a map of CoordsPairs to a counter of trade routes between them, and a
+    /// map of Coords to its total transient (non-endpoint) trade credits.
+    fn find_route_paths(
+        &self,
+        sorted_coords: &[Coords],
+        coords_to_world: &HashMap<Coords, World>,
+        max_jumps: &RouteCounter,
+        min_route_btn: f64,
+        dists: &HashMap<u64, ShortestPaths>,
+        rules: &dyn TradeRules,
+    ) -> (HashMap<CoordsPair, RouteCounter>, HashMap<Coords, u64>) {
+        let mut route_paths: HashMap<CoordsPair, RouteCounter> = HashMap::new();
+        let mut coords_to_transient_credits: HashMap<Coords, u64> = HashMap::new();
+        let all_jumps_set: HashSet<u64> = max_jumps.values().cloned().collect();
+        let mut all_jumps: Vec<u64> = all_jumps_set.iter().cloned().collect();
+        all_jumps.sort_unstable();
+        for (dbtn, coords_set) in self.dbtn_to_coords.iter().enumerate() {
+            let btn = dbtn as f64 / 2.0;
+            let max_allowed_jump = find_max_allowed_jump(btn, max_jumps, min_route_btn);
+            let route_opt = btn_to_route(btn, min_route_btn);
+            let credits = rules.dbtn_to_credits()[dbtn];
+            for coords2 in coords_set {
+                let world2 = coords_to_world.get(coords2).unwrap();
+                let mut path: Vec<Coords> = Vec::new();
+                for jump in all_jumps.iter() {
+                    // Only allow jumps that are allowed based on the route size.
+                    if jump <= &max_allowed_jump {
+                        let dist = dists.get(jump).unwrap();
+                        let possible_path_opt = self.navigable_path(world2, sorted_coords, dist);
+                        if let Some(possible_path) = possible_path_opt {
+                            // Only use bigger jumps if that saves us a hop.
+                            if path.is_empty() || possible_path.len() < path.len() {
+                                path = possible_path;
+                            }
+                        }
+                    }
+                }
+                if path.len() >= 2 {
+                    for ii in 0..path.len() - 1 {
+                        let first = path.get(ii).unwrap();
+                        let second = path.get(ii + 1).unwrap();
+                        let coords_pair: CoordsPair = if first <= second {
+                            (*first, *second)
+                        } else {
+                            (*second, *first)
+                        };
+                        if let Some(route) = route_opt {
+                            route_paths
+                                .entry(coords_pair)
+                                .and_modify(|route_to_count| {
+                                    route_to_count
+                                        .entry(route)
+                                        .and_modify(|prev| *prev += 1)
+                                        .or_insert(1);
+                                })
+                                .or_insert_with(|| {
+                                    let mut route_to_count = HashMap::new();
+                                    route_to_count.insert(route, 1);
+                                    route_to_count
+                                });
+                        };
+                    }
+                    for jj in 1..path.len() - 2 {
+                        let coords3 = path.get(jj).unwrap();
+                        coords_to_transient_credits
+                            .entry(*coords3)
+                            .and_modify(|transient| *transient += credits)
+                            .or_insert(credits);
+                    }
+                }
+            }
+        }
+        (route_paths, coords_to_transient_credits)
+    }
+
+    fn imperial_affiliated(&self, groups: &AllegianceGroups) -> bool {
+        groups.group(&self.allegiance) == Some(IMPERIUM)
+    }
+
+    // This only works after trade routes are built.
+    fn port_size(&self, rules: &dyn TradeRules) -> u64 {
+        let mut port_size = self.wtn(rules);
+        if !self.imperial_affiliated(rules.allegiance_groups()) {
+            port_size -= NON_IMPERIAL_PORT_SIZE_PENALTY;
+        }
+        if !self.neighbors[1].is_empty() {
+            port_size += NEIGHBOR_1_PORT_SIZE_BONUS;
+        } else if self.neighbors.len() > 2 && !self.neighbors[2].is_empty() {
+            port_size += NEIGHBOR_2_PORT_SIZE_BONUS;
+        }
+        port_size = f64::ceil(port_size);
+        if !self.xboat_routes.is_empty() || !self.major_routes.is_empty() {
+            if port_size < XBOAT_MAJOR_ROUTE_MIN_PORT_SIZE {
+                port_size = XBOAT_MAJOR_ROUTE_MIN_PORT_SIZE;
+            }
+        } else if !self.main_routes.is_empty()
+            || !self.intermediate_routes.is_empty()
+            || !self.feeder_routes.is_empty()
+        {
+            if port_size < FEEDER_ROUTE_MIN_PORT_SIZE {
+                port_size = FEEDER_ROUTE_MIN_PORT_SIZE;
+            }
+        } else if !self.minor_routes.is_empty() && port_size < MINOR_ROUTE_MIN_PORT_SIZE {
+            port_size = MINOR_ROUTE_MIN_PORT_SIZE;
+        }
+        port_size as u64
+    }
+
+    /// Description of this world
+    fn desc(&self) -> String {
+        format!("{} ({} {})", self.name, self.sector_name, self.hex)
+    }
+}
+
+impl PartialEq for World {
+    fn eq(&self, other: &Self) -> bool {
+        self.hex == other.hex && self.name == other.name
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Sector {
+    name: String,
+    names: Vec<String>,
+    abbreviation: String,
+    location: (i64, i64),
+    subsector_letter_to_name: HashMap<char, String>,
+    allegiance_code_to_name: HashMap<String, String>,
+    hex_to_coords: HashMap<String, Coords>,
+    // Worlds with bad data that were left out in lenient mode.
+    skipped_worlds: Vec<ParseError>,
+    // Worlds that replaced an earlier world in the same hex.
+    duplicate_hexes: Vec<ParseError>,
+}
+
+impl Sector {
+    /// Build a Sector and its Worlds.
+    ///
+    /// Metadata comes from custom_opt for hand-authored sectors, else from
+    /// the XML metadata in source.
+    ///
+    /// If lenient is set, worlds with bad data are skipped and recorded in
+    /// skipped_worlds.  Otherwise the first one is an error.
+    fn new(
+        source: &dyn SectorSource,
+        sector_name: String,
+        custom_opt: Option<&CustomSector>,
+        lenient: bool,
+        coords_to_world: &mut HashMap<Coords, World>,
+    ) -> Result<Sector> {
+        let name = sector_name.to_owned();
+        let names = Vec::new();
+        let abbreviation = "".to_string();
+        let location = (-1, -1);
+        let subsector_letter_to_name = HashMap::new();
+        let allegiance_code_to_name = HashMap::new();
+        let hex_to_coords = HashMap::new();
+        let skipped_worlds = Vec::new();
+        let duplicate_hexes = Vec::new();
+        let mut sector = Sector {
+            name,
+            names,
+            abbreviation,
+            location,
+            subsector_letter_to_name,
+            allegiance_code_to_name,
+            hex_to_coords,
+            skipped_worlds,
+            duplicate_hexes,
+        };
+
+        if let Some(custom) = custom_opt {
+            sector.parse_custom_metadata(custom);
+        } else {
+            sector
+                .parse_xml_metadata(source, &sector_name)
+                .map_err(|err| anyhow!("{} metadata: {}", sector_name, err))?;
+        }
+        sector.parse_column_data(source, &sector_name, lenient, coords_to_world)?;
+        Ok(sector)
+    }
+
+    fn parse_custom_metadata(&mut self, custom: &CustomSector) {
+        self.names = custom.names.to_vec();
+        self.abbreviation = custom.abbreviation.to_string();
+        self.location = custom.location;
+        self.subsector_letter_to_name = custom.subsector_letter_to_name.clone();
+        self.allegiance_code_to_name = custom.allegiance_code_to_name.clone();
+    }
+
+    fn parse_xml_metadata(&mut self, source: &dyn SectorSource, sector_name: &str) -> Result<()> {
+        let xml = source.sector_metadata(sector_name)?;
+        let root = Element::from_reader(xml.as_bytes())?;
+
+        let abbreviation_opt = root.get_attr("Abbreviation");
+        if let Some(abbreviation) = abbreviation_opt {
+            self.abbreviation = abbreviation.to_string();
+        }
+
+        let mut x = i64::MAX;
+        let x_opt = root.find("X");
+        if let Some(x_element) = x_opt {
+            let x_text = x_element.text();
+            x = x_text.parse()?;
+        }
+        let mut y = i64::MAX;
+        let y_opt = root.find("Y");
+        if let Some(y_element) = y_opt {
+            let y_text = y_element.text();
+            y = y_text.parse()?;
+        }
+        self.location = (x, y);
+
+        let name_elements = root.find_all("Name");
+        for name_element in name_elements {
+            if !name_element.text().is_empty() {
+                self.names.push(name_element.text().to_string());
+            }
+        }
+
+        let subsectors_opt = root.find("Subsectors");
+        if let Some(subsectors_element) = subsectors_opt {
+            let subsector_elements = subsectors_element.find_all("Subsector");
+            for subsector_element in subsector_elements {
+                let index_opt = subsector_element.get_attr("Index");
+                if let Some(index) = index_opt {
+                    let letter = index.chars().next().unwrap();
+                    let subsector_name = subsector_element.text().to_string();
+                    if !subsector_name.is_empty() {
+                        self.subsector_letter_to_name.insert(letter, subsector_name);
+                    }
+                }
+            }
+        }
+
+        let allegiances_opt = root.find("Allegiances");
+        if let Some(allegiances_element) = allegiances_opt {
+            let allegiance_elements = allegiances_element.find_all("Allegiance");
+            for allegiance_element in allegiance_elements {
+                let code_opt = allegiance_element.get_attr("Code");
+                if let Some(code) = code_opt {
+                    let allegiance_name = allegiance_element.text().to_string();
+                    if !allegiance_name.is_empty() {
+                        self.allegiance_code_to_name
+                            .insert(code.to_string(), allegiance_name);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_column_data(
+        &mut self,
+        source: &dyn SectorSource,
+        sector_name: &str,
+        lenient: bool,
+        coords_to_world: &mut HashMap<Coords, World>,
+    ) -> Result<()> {
+        let blob = source.sector_data(sector_name)?;
+        let location = source.data_location(sector_name);
+        let mut header = "";
+        // We initialize fields here to make rustc happy, then overwrite it.
+        let mut fields: Vec<(usize, usize, String)> = Vec::new();
+        // Set if the first header line is tab-delimited rather than
+        // fixed-width.
+        let mut tab_headers: Option<Vec<String>> = None;
+        for (ii, line) in blob.lines().enumerate() {
+            let line_num = ii + 1;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let world_result = if let Some(ref headers) = tab_headers {
+                let world = World::new_tab_delimited(
+                    line.to_string(),
+                    headers,
+                    self.location,
+                    self.name.to_string(),
+                );
+                Ok(world)
+            } else if header.is_empty() && line.contains('\t') {
+                tab_headers = Some(line.split('\t').map(|st| st.trim().to_string()).collect());
+                continue;
+            } else if line.starts_with("Hex") {
+                header = line;
+                continue;
+            } else if line.starts_with("---") {
+                let separator = line;
+                fields = parse_header_and_separator(header, separator);
+                continue;
+            } else if fields.is_empty() {
+                Err(ParseError::NoHeader {
+                    location: location.to_string(),
+                    line: line_num,
+                })
+            } else {
+                let world = World::new(
+                    line.to_string(),
+                    &fields,
+                    self.location,
+                    self.name.to_string(),
+                );
+                Ok(world)
+            };
+            let world_result = world_result.and_then(|world| match world.bad_field() {
+                None => Ok(world),
+                Some((field, value)) => Err(ParseError::BadField {
+                    location: location.to_string(),
+                    line: line_num,
+                    hex: world.hex.to_string(),
+                    field: field.to_string(),
+                    value,
+                }),
+            });
+            match world_result {
+                Ok(world) => {
+                    // The later world replaces the earlier one, but lint
+                    // reports it.
+                    if self.hex_to_coords.contains_key(&world.hex) {
+                        self.duplicate_hexes.push(ParseError::DuplicateHex {
+                            location: location.to_string(),
+                            line: line_num,
+                            hex: world.hex.to_string(),
+                        });
+                    }
+                    self.hex_to_coords
+                        .insert(world.hex.clone(), world.get_coords());
+                    coords_to_world.insert(world.get_coords(), world);
+                }
+                Err(err) => {
+                    if lenient {
+                        self.skipped_worlds.push(err);
+                    } else {
+                        return Err(err.into());
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse the endpoints of Xboat routes from xml, as
+    /// ((start sector location, start hex), (end sector location, end hex))
+    fn parse_xml_route_endpoints(
+        &self,
+        source: &dyn SectorSource,
+    ) -> Result<Vec<(((i64, i64), String), ((i64, i64), String))>> {
+        let mut endpoints = Vec::new();
+        let xml = source.sector_metadata(&self.name)?;
+        let root = Element::from_reader(xml.as_bytes())?;
+        let routes_opt = root.find("Routes");
+        if let Some(routes_element) = routes_opt {
+            let route_elements = routes_element.find_all("Route");
+            for route_element in route_elements {
+                let start_hex_opt = route_element.get_attr("Start");
+                if let Some(start_hex) = start_hex_opt {
+                    let end_hex_opt = route_element.get_attr("End");
+                    if let Some(end_hex) = end_hex_opt {
+                        let start_offset_x_opt = route_element.get_attr("StartOffsetX");
+                        let mut start_offset_x = 0;
+                        if let Some(start_offset_x2) = start_offset_x_opt {
+                            start_offset_x = start_offset_x2.parse()?;
+                        };
+                        let start_offset_y_opt = route_element.get_attr("StartOffsetY");
+                        let mut start_offset_y = 0;
+                        if let Some(start_offset_y2) = start_offset_y_opt {
+                            start_offset_y = start_offset_y2.parse()?;
+                        }
+                        let end_offset_x_opt = route_element.get_attr("EndOffsetX");
+                        let mut end_offset_x = 0;
+                        if let Some(end_offset_x2) = end_offset_x_opt {
+                            end_offset_x = end_offset_x2.parse()?;
+                        }
+                        let end_offset_y_opt = route_element.get_attr("EndOffsetY");
+                        let mut end_offset_y = 0;
+                        if let Some(end_offset_y2) = end_offset_y_opt {
+                            end_offset_y = end_offset_y2.parse()?;
+                        }
+                        let start_location = (
+                            self.location.0 + start_offset_x,
+                            self.location.1 + start_offset_y,
+                        );
+                        let end_location = (
+                            self.location.0 + end_offset_x,
+                            self.location.1 + end_offset_y,
+                        );
+                        endpoints.push((
+                            (start_location, start_hex.to_string()),
+                            (end_location, end_hex.to_string()),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(endpoints)
+    }
+
+    /// Parse Xboat routes from xml
+    /// Must be called after all Sectors and Worlds are built
+    fn parse_xml_routes(
+        &self,
+        source: &dyn SectorSource,
+        location_to_sector: &HashMap<(i64, i64), Sector>,
+        coords_to_world: &mut HashMap<Coords, World>,
+    ) -> Result<()> {
+        for ((start_location, start_hex), (end_location, end_hex)) in
+            self.parse_xml_route_endpoints(source)?
+        {
+            let start_sector_opt = location_to_sector.get(&start_location);
+            let end_sector_opt = location_to_sector.get(&end_location);
+            if let Some(start_sector) = start_sector_opt {
+                if let Some(end_sector) = end_sector_opt {
+                    if let Some(start_coords) = start_sector.hex_to_coords.get(&start_hex) {
+                        if let Some(end_coords) = end_sector.hex_to_coords.get(&end_hex) {
+                            // Need to do these one at a time to avoid holding two
+                            // mutable references at once.
+                            if let Some(_start_world) = coords_to_world.get(start_coords) {
+                                if let Some(end_world) = coords_to_world.get_mut(end_coords) {
+                                    end_world.xboat_routes.insert(*start_coords);
+                                }
+                            }
+                            if let Some(_end_world) = coords_to_world.get(end_coords) {
+                                if let Some(start_world) = coords_to_world.get_mut(start_coords) {
+                                    start_world.xboat_routes.insert(*end_coords);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn hex_to_world<'a>(
+        &'a self,
+        hex: String,
+        coords_to_world: &'a HashMap<Coords, World>,
+    ) -> Option<&World> {
+        let coords_opt = self.hex_to_coords.get(&hex);
+        if let Some(coords) = coords_opt {
+            return coords_to_world.get(coords);
+        }
+        None
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// All names, including the canonical one and alternates in other
+    /// languages
+    pub fn names(&self) -> Vec<String> {
+        self.names.to_vec()
+    }
+
+    pub fn abbreviation(&self) -> String {
+        self.abbreviation.clone()
+    }
+
+    /// (x, y) of this sector, in sectors from Core
+    pub fn location(&self) -> (i64, i64) {
+        self.location
+    }
+
+    /// Worlds with bad data that were left out in lenient mode
+    pub fn skipped_worlds(&self) -> Vec<ParseError> {
+        self.skipped_worlds.to_vec()
+    }
+}
+
+/// Read a file with one sector name per line.
+pub fn parse_file_of_sectors(file_of_sectors: PathBuf) -> Result<HashSet<String>> {
+    let mut sector_names: HashSet<String> = HashSet::new();
+    let mut file = File::open(file_of_sectors)?;
+    let mut buf = String::new();
+    file.read_to_string(&mut buf)?;
+
+    for line in buf.lines() {
+        sector_names.insert(line.trim().to_string());
+    }
+
+    Ok(sector_names)
+}
+
+fn generate_text_btns(
+    output_dir: &Path,
+    location_to_sector: &HashMap<(i64, i64), Sector>,
+    coords_to_world: &HashMap<Coords, World>,
+    passenger: bool,
+    max_max_jump: u64,
+    ignore_xboat_routes: bool,
+    dists: &HashMap<u64, ShortestPaths>,
+    rules: &dyn TradeRules,
+) -> Result<()> {
+    let dist = dists.get(&max_max_jump).unwrap();
+    let mut coords_pairs: Vec<CoordsPair> = Vec::new();
+    for world in coords_to_world.values() {
+        for jump in 1..=max_max_jump {
+            for coords2 in &world.neighbors[jump as usize] {
+                coords_pairs.push((world.get_coords(), *coords2));
+            }
+        }
+        if !ignore_xboat_routes {
+            for coords2 in &world.xboat_routes {
+                coords_pairs.push((world.get_coords(), *coords2));
+            }
+        }
+    }
+    prepare_navigable_distances(&coords_pairs, coords_to_world, dist);
+    for sector in location_to_sector.values() {
+        let mut output_path = output_dir.to_path_buf();
+        output_path.push(format!("{}.txt", &sector.name));
+        let mut output_file = File::create(output_path)?;
+        let mut sorted_coords = Vec::new();
+        for coords in sector.hex_to_coords.values() {
+            sorted_coords.push(coords);
+        }
+        sorted_coords.sort();
+        for coords in sorted_coords.into_iter() {
+            let world = coords_to_world.get(coords).unwrap();
+            let mut neighbors = HashSet::new();
+            for jump in 1..=max_max_jump {
+                for coords2 in &world.neighbors[jump as usize] {
+                    neighbors.insert(coords2);
+                }
+            }
+            if !ignore_xboat_routes {
+                for coords2 in &world.xboat_routes {
+                    neighbors.insert(coords2);
+                }
+            }
+            let mut sorted_neighbors = Vec::new();
+            for neighbor in neighbors.into_iter() {
+                sorted_neighbors.push(neighbor);
+            }
+            sorted_neighbors.sort();
+            for coords2 in sorted_neighbors {
+                let neighbor = coords_to_world.get(coords2).unwrap();
+                let btn = world.btn(neighbor, dist, passenger, rules);
+                writeln!(output_file, "{} {} {}", world.desc(), neighbor.desc(), btn)?
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,295 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::source::SectorSource;
+use crate::{Coords, Sector, World, MAX_POPULATION, MAX_TECH_LEVEL};
+
+/// Something suspicious in sector data that could distort the results.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct LintIssue {
+    pub sector_name: String,
+    pub hex: String,
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.hex.is_empty() {
+            write!(f, "{}: {}", self.sector_name, self.message)
+        } else {
+            write!(f, "{} {}: {}", self.sector_name, self.hex, self.message)
+        }
+    }
+}
+
+/// (name, index in UWP, maximum value) for each UWP digit after the starport
+const UWP_DIGITS: [(&str, usize, u32); 7] = [
+    ("size", 1, 15),
+    ("atmosphere", 2, 15),
+    ("hydrosphere", 3, 10),
+    ("population", 4, MAX_POPULATION),
+    ("government", 5, 15),
+    ("law level", 6, 19),
+    ("tech level", 8, MAX_TECH_LEVEL),
+];
+
+const STARPORTS: &str = "ABCDEX?";
+
+/// Return the value of the UWP digit at index, or None if it is unknown
+/// ('?' or 'X') or not a digit.
+fn uwp_digit(uwp: &[char], index: usize) -> Option<u32> {
+    match uwp.get(index) {
+        Some('?') | Some('X') => None,
+        Some(ch) => ch.to_digit(36),
+        None => None,
+    }
+}
+
+/// Return a message for each problem with a UWP.
+pub fn lint_uwp(uwp: &str) -> Vec<String> {
+    let chars: Vec<char> = uwp.chars().collect();
+    if chars.len() != 9 || chars[7] != '-' {
+        return vec![format!("UWP '{}' is not like 'A123456-7'", uwp)];
+    }
+    let mut messages = Vec::new();
+    if !STARPORTS.contains(chars[0]) {
+        messages.push(format!("UWP {} has unknown starport '{}'", uwp, chars[0]));
+    }
+    for (name, index, max) in UWP_DIGITS {
+        let ch = chars[index];
+        if ch == '?' || ch == 'X' {
+            continue;
+        }
+        match uwp_digit(&chars, index) {
+            Some(val) if val <= max => (),
+            _ => messages.push(format!(
+                "UWP {} has {} '{}' out of range 0-{}",
+                uwp,
+                name,
+                ch,
+                char::from_digit(max, 36).unwrap().to_ascii_uppercase()
+            )),
+        }
+    }
+    messages
+}
+
+/// Return a message for each problem with a PBG.
+pub fn lint_pbg(pbg: &str) -> Vec<String> {
+    if pbg.chars().count() != 3 || !pbg.chars().all(|ch| ch.is_ascii_digit() || ch == '?') {
+        vec![format!("PBG '{}' is not three digits", pbg)]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Return a message if a hex is outside the 32x40 grid of a sector.
+pub fn lint_hex(hex: &str) -> Vec<String> {
+    let x_opt: Option<u32> = hex.get(0..2).and_then(|st| st.parse().ok());
+    let y_opt: Option<u32> = hex.get(2..4).and_then(|st| st.parse().ok());
+    match (x_opt, y_opt) {
+        (Some(x), Some(y)) if hex.len() == 4 && (1..=32).contains(&x) && (1..=40).contains(&y) => {
+            Vec::new()
+        }
+        _ => vec![format!("hex {} is outside 0101-3240", hex)],
+    }
+}
+
+/// Return whether a UWP qualifies for a trade classification, or None if
+/// the UWP doesn't say or we don't check that classification.
+///
+/// These follow the T5 rules that travellermap.com uses.
+fn trade_classification_fits(tc: &str, uwp: &str) -> Option<bool> {
+    let chars: Vec<char> = uwp.chars().collect();
+    let siz = || uwp_digit(&chars, 1);
+    let atm = || uwp_digit(&chars, 2);
+    let hyd = || uwp_digit(&chars, 3);
+    let pop = || uwp_digit(&chars, 4);
+    let gov = || uwp_digit(&chars, 5);
+    let law = || uwp_digit(&chars, 6);
+    let fits = match tc {
+        "Ag" => (4..=9).contains(&atm()?) && (4..=8).contains(&hyd()?) && (5..=7).contains(&pop()?),
+        "As" => siz()? == 0 && atm()? == 0 && hyd()? == 0,
+        "Ba" => pop()? == 0 && gov()? == 0 && law()? == 0,
+        "De" => (2..=9).contains(&atm()?) && hyd()? == 0,
+        "Fl" => (10..=12).contains(&atm()?) && hyd()? >= 1,
+        "Ga" => {
+            (6..=8).contains(&siz()?) && [5, 6, 8].contains(&atm()?) && (5..=7).contains(&hyd()?)
+        }
+        "He" => {
+            (3..=12).contains(&siz()?) && [2, 4, 7, 9, 10, 11, 12].contains(&atm()?) && hyd()? <= 2
+        }
+        "Hi" => pop()? >= 9,
+        "Ic" => atm()? <= 1 && hyd()? >= 1,
+        "In" => [0, 1, 2, 4, 7, 9, 10, 11, 12].contains(&atm()?) && pop()? >= 9,
+        "Lo" => (1..=3).contains(&pop()?),
+        "Na" => atm()? <= 3 && hyd()? <= 3 && pop()? >= 6,
+        "Ni" => (4..=6).contains(&pop()?),
+        "Po" => (2..=5).contains(&atm()?) && hyd()? <= 3,
+        "Ri" => [6, 8].contains(&atm()?) && (6..=8).contains(&pop()?) && (4..=9).contains(&gov()?),
+        "Va" => atm()? == 0,
+        "Wa" => hyd()? == 10,
+        _ => return None,
+    };
+    Some(fits)
+}
+
+/// Return a message for each trade classification that contradicts the UWP.
+pub fn lint_trade_classifications(world: &World) -> Vec<String> {
+    let mut tcs: Vec<&String> = world.trade_classifications.iter().collect();
+    tcs.sort();
+    tcs.into_iter()
+        .filter(|tc| trade_classification_fits(tc, &world.uwp) == Some(false))
+        .map(|tc| format!("trade code {} contradicts UWP {}", tc, world.uwp))
+        .collect()
+}
+
+/// Check all the worlds in location_to_sector, and the xboat routes in the
+/// metadata from source, and return the issues sorted by sector and hex.
+///
+/// Worlds that failed to parse should be in each Sector's skipped_worlds.
+/// Worlds in the same hex as an earlier one are reported too, though they
+/// don't stop a trade run.
+pub fn lint_sectors(
+    source: &dyn SectorSource,
+    location_to_sector: &HashMap<(i64, i64), Sector>,
+    coords_to_world: &HashMap<Coords, World>,
+    custom_sector_names: &[String],
+) -> Result<Vec<LintIssue>> {
+    let mut issues = Vec::new();
+    for sector in location_to_sector.values() {
+        let mut add_issue = |hex: &str, message: String| {
+            issues.push(LintIssue {
+                sector_name: sector.name.to_string(),
+                hex: hex.to_string(),
+                message,
+            });
+        };
+        for err in sector
+            .skipped_worlds
+            .iter()
+            .chain(sector.duplicate_hexes.iter())
+        {
+            add_issue("", err.to_string());
+        }
+        for (hex, coords) in sector.hex_to_coords.iter() {
+            let world = coords_to_world.get(coords).unwrap();
+            let mut messages = lint_hex(hex);
+            messages.extend(lint_uwp(&world.uwp));
+            messages.extend(lint_pbg(&world.pbg));
+            messages.extend(lint_trade_classifications(world));
+            if !world.allegiance.is_empty()
+                && !sector
+                    .allegiance_code_to_name
+                    .contains_key(&world.allegiance)
+            {
+                messages.push(format!("unknown allegiance code {}", world.allegiance));
+            }
+            for message in messages {
+                add_issue(hex, message);
+            }
+        }
+
+        // Custom sectors have no XML, so no xboat routes.
+        if custom_sector_names.contains(&sector.name) {
+            continue;
+        }
+        for (start, end) in sector.parse_xml_route_endpoints(source)? {
+            for (location, hex) in [&start, &end] {
+                // We can't tell whether hexes in unloaded sectors are empty.
+                if let Some(sector2) = location_to_sector.get(location) {
+                    if !sector2.hex_to_coords.contains_key(hex) {
+                        add_issue(
+                            &start.1,
+                            format!(
+                                "xboat route {} {} to {} {} ends at empty hex {} {}",
+                                location_name(location_to_sector, &start.0),
+                                start.1,
+                                location_name(location_to_sector, &end.0),
+                                end.1,
+                                sector2.name,
+                                hex
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    issues.sort();
+    Ok(issues)
+}
+
+fn location_name(
+    location_to_sector: &HashMap<(i64, i64), Sector>,
+    location: &(i64, i64),
+) -> String {
+    match location_to_sector.get(location) {
+        Some(sector) => sector.name.to_string(),
+        None => format!("{:?}", location),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_uwp() {
+        assert!(lint_uwp("A788899-C").is_empty());
+        assert!(lint_uwp("X200000-0").is_empty());
+        assert!(lint_uwp("???????-?").is_empty());
+        assert_eq!(
+            lint_uwp("A788899C"),
+            vec!["UWP 'A788899C' is not like 'A123456-7'"]
+        );
+        assert_eq!(
+            lint_uwp("Q78B899-P"),
+            vec![
+                "UWP Q78B899-P has unknown starport 'Q'",
+                "UWP Q78B899-P has hydrosphere 'B' out of range 0-A",
+                "UWP Q78B899-P has tech level 'P' out of range 0-N",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_pbg() {
+        assert!(lint_pbg("703").is_empty());
+        assert!(lint_pbg("?0?").is_empty());
+        assert_eq!(lint_pbg("70"), vec!["PBG '70' is not three digits"]);
+        assert_eq!(lint_pbg("7A3"), vec!["PBG '7A3' is not three digits"]);
+    }
+
+    #[test]
+    fn test_lint_hex() {
+        assert!(lint_hex("0101").is_empty());
+        assert!(lint_hex("3240").is_empty());
+        assert_eq!(lint_hex("3301"), vec!["hex 3301 is outside 0101-3240"]);
+        assert_eq!(lint_hex("0141"), vec!["hex 0141 is outside 0101-3240"]);
+        assert_eq!(lint_hex("0000"), vec!["hex 0000 is outside 0101-3240"]);
+    }
+
+    #[test]
+    fn test_trade_classification_fits() {
+        // Regina
+        assert_eq!(trade_classification_fits("Ri", "A788899-C"), Some(true));
+        assert_eq!(trade_classification_fits("Pa", "A788899-C"), None);
+        assert_eq!(trade_classification_fits("Hi", "A788899-C"), Some(false));
+        assert_eq!(trade_classification_fits("Hi", "A788?99-C"), None);
+        // Tinder
+        assert_eq!(trade_classification_fits("Ba", "X200000-0"), Some(true));
+        assert_eq!(trade_classification_fits("Va", "X200000-0"), Some(true));
+        assert_eq!(trade_classification_fits("Wa", "X200000-0"), Some(false));
+        // Unknown population
+        for tc in ["Lo", "Ni", "Ba", "Hi"] {
+            assert_eq!(trade_classification_fits(tc, "X200X00-0"), None);
+        }
+        // Ri needs a government from 4 to 9.
+        assert_eq!(trade_classification_fits("Ri", "A788839-C"), Some(false));
+        assert_eq!(trade_classification_fits("Ri", "A788849-C"), Some(true));
+        assert_eq!(trade_classification_fits("Ri", "A7888A9-C"), Some(false));
+        assert_eq!(trade_classification_fits("Ri", "A7888X9-C"), None);
+    }
+}
@@ -14,7 +14,8 @@ use cairo::{Context, FontFace, FontSlant, FontWeight, PdfSurface};
 extern crate rand;
 use rand::{random, thread_rng, Rng};
 
-use crate::{Coords, Sector, World, DBTN_TO_CREDITS};
+use crate::rules::TradeRules;
+use crate::{Coords, Sector, World};
 
 const SQRT3: f64 = 1.7320508075688772;
 
@@ -384,22 +385,29 @@ fn draw_world_name(ctx: &Context, font_face: &FontFace, world: &World, cx: f64,
 }
 
 /// Draw DWTN, endpoint trace BTN, transient trade BTN, and port size.
-fn draw_trade_info(ctx: &Context, font_face: &FontFace, world: &World, cx: f64, cy: f64) {
+fn draw_trade_info(
+    ctx: &Context,
+    font_face: &FontFace,
+    world: &World,
+    rules: &dyn TradeRules,
+    cx: f64,
+    cy: f64,
+) {
     ctx.set_font_size(0.35 * SCALE);
     ctx.set_font_face(font_face);
     let rgba = WHITE;
     ctx.set_source_rgba(rgba.0, rgba.1, rgba.2, rgba.3);
-    let dwtn = (world.wtn() * 2.0) as u64;
-    let endpoint_dbtn = bisect_left(&DBTN_TO_CREDITS, &world.endpoint_trade_credits);
+    let dwtn = (world.wtn(rules) * 2.0) as u64;
+    let endpoint_dbtn = bisect_left(rules.dbtn_to_credits(), &world.endpoint_trade_credits);
     let endpoint_btn = endpoint_dbtn / 2;
-    let transient_dbtn = bisect_left(&DBTN_TO_CREDITS, &world.transient_trade_credits);
+    let transient_dbtn = bisect_left(rules.dbtn_to_credits(), &world.transient_trade_credits);
     let transient_btn = transient_dbtn / 2;
     let text = format!(
         "{:X}{:X}{:X}{:X}",
         dwtn,
         endpoint_btn,
         transient_btn,
-        world.port_size()
+        world.port_size(rules)
     );
     let extents = ctx.text_extents(&text).unwrap();
     ctx.move_to(
@@ -503,6 +511,7 @@ fn draw_worlds(
     bold_font_face: &FontFace,
     sector: &Sector,
     coords_to_world: &HashMap<Coords, World>,
+    rules: &dyn TradeRules,
 ) {
     for x in 1..SECTOR_HEX_WIDTH + 1 {
         for y in 1..SECTOR_HEX_HEIGHT + 1 {
@@ -514,7 +523,7 @@ fn draw_worlds(
                 if let Some(world) = coords_to_world.get(coords) {
                     draw_uwp(ctx, normal_font_face, world, cx, cy);
                     draw_world_name(ctx, bold_font_face, world, cx, cy);
-                    draw_trade_info(ctx, normal_font_face, world, cx, cy);
+                    draw_trade_info(ctx, normal_font_face, world, rules, cx, cy);
                     draw_world_circle(ctx, world, center);
                     draw_gas_giant(ctx, world, center);
                     draw_zones(ctx, world, center);
@@ -530,6 +539,7 @@ fn generate_pdf(
     output_dir: &Path,
     location_to_sector: &HashMap<(i64, i64), Sector>,
     coords_to_world: &HashMap<Coords, World>,
+    rules: &dyn TradeRules,
 ) {
     let width = 60.0 * SECTOR_HEX_WIDTH as f64 * SCALE;
     let height = 35.0 * SQRT3 * SECTOR_HEX_HEIGHT as f64 * SCALE;
@@ -567,6 +577,7 @@ fn generate_pdf(
         &bold_font_face,
         sector,
         coords_to_world,
+        rules,
     );
 
     surface.finish();
@@ -576,10 +587,19 @@ pub fn generate_pdfs(
     output_dir: &Path,
     location_to_sector: &HashMap<(i64, i64), Sector>,
     coords_to_world: &HashMap<Coords, World>,
+    rules: &dyn TradeRules,
 ) {
     debug!("(parallel) generate_pdfs");
     location_to_sector
         .par_iter()
-        .map(|(_, sector)| generate_pdf(sector, output_dir, location_to_sector, coords_to_world))
+        .map(|(_, sector)| {
+            generate_pdf(
+                sector,
+                output_dir,
+                location_to_sector,
+                coords_to_world,
+                rules,
+            )
+        })
         .collect::<Vec<()>>();
 }
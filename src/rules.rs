@@ -0,0 +1,801 @@
+use anyhow::{anyhow, bail, Context, Result};
+use bisection::bisect_left;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::scenario::{Scenario, NO_SCENARIO};
+use crate::{World, MAX_DISTANCE_PENALTY, MAX_TECH_LEVEL};
+
+pub const FAR_TRADER: &str = "far-trader";
+pub const INTERSTELLAR_WARS: &str = "interstellar-wars";
+
+/// Names of the built-in rulesets, default first.
+pub const RULESET_NAMES: [&str; 2] = [FAR_TRADER, INTERSTELLAR_WARS];
+
+/// The allegiance group whose worlds count as Imperial for port size.
+pub const IMPERIUM: &str = "Imperium";
+
+const GURPS_STARPORTS: [&str; 6] = ["V", "IV", "III", "II", "I", "0"];
+
+const TECH_LEVEL_TRAVELLER_TO_GURPS: [u64; MAX_TECH_LEVEL as usize + 1] = [
+    2, // actually 1-3
+    4, 5, 5, 5, 6, 6, 7, 8, 9, 9, 9, 10, 10, 11, 12, 13, 13, 14, 14, 14, 14, 14, 14,
+];
+
+lazy_static! {
+    static ref WTN_PORT_MODIFIER_TABLE: HashMap<(u64, String), f64> = {
+        let mut wpmt: HashMap<(u64, String), f64> = HashMap::new();
+        wpmt.insert((7, "V".to_string()), 0.0);
+        wpmt.insert((7, "IV".to_string()), -1.0);
+        wpmt.insert((7, "III".to_string()), -1.5);
+        wpmt.insert((7, "II".to_string()), -2.0);
+        wpmt.insert((7, "I".to_string()), -2.5);
+        wpmt.insert((7, "0".to_string()), -5.0);
+        wpmt.insert((6, "V".to_string()), 0.0);
+        wpmt.insert((6, "IV".to_string()), -0.5);
+        wpmt.insert((6, "III".to_string()), -1.0);
+        wpmt.insert((6, "II".to_string()), -1.5);
+        wpmt.insert((6, "I".to_string()), -2.0);
+        wpmt.insert((6, "0".to_string()), -4.5);
+        wpmt.insert((5, "V".to_string()), 0.0);
+        wpmt.insert((5, "IV".to_string()), 0.0);
+        wpmt.insert((5, "III".to_string()), -0.5);
+        wpmt.insert((5, "II".to_string()), -1.0);
+        wpmt.insert((5, "I".to_string()), -1.5);
+        wpmt.insert((5, "0".to_string()), -4.0);
+        wpmt.insert((4, "V".to_string()), 0.5);
+        wpmt.insert((4, "IV".to_string()), 0.0);
+        wpmt.insert((4, "III".to_string()), 0.0);
+        wpmt.insert((4, "II".to_string()), -0.5);
+        wpmt.insert((4, "I".to_string()), -1.0);
+        wpmt.insert((4, "0".to_string()), -3.5);
+        wpmt.insert((3, "V".to_string()), 0.5);
+        wpmt.insert((3, "IV".to_string()), 0.5);
+        wpmt.insert((3, "III".to_string()), 0.0);
+        wpmt.insert((3, "II".to_string()), 0.0);
+        wpmt.insert((3, "I".to_string()), -0.5);
+        wpmt.insert((3, "0".to_string()), -3.0);
+        wpmt.insert((2, "V".to_string()), 1.0);
+        wpmt.insert((2, "IV".to_string()), 0.5);
+        wpmt.insert((2, "III".to_string()), 0.5);
+        wpmt.insert((2, "II".to_string()), 0.0);
+        wpmt.insert((2, "I".to_string()), 0.0);
+        wpmt.insert((2, "0".to_string()), -2.5);
+        wpmt.insert((1, "V".to_string()), 1.0);
+        wpmt.insert((1, "IV".to_string()), 1.0);
+        wpmt.insert((1, "III".to_string()), 0.5);
+        wpmt.insert((1, "II".to_string()), 0.0);
+        wpmt.insert((1, "I".to_string()), 0.0);
+        wpmt.insert((1, "0".to_string()), 0.0);
+        wpmt.insert((0, "V".to_string()), 1.5);
+        wpmt.insert((0, "IV".to_string()), 1.0);
+        wpmt.insert((0, "III".to_string()), 1.0);
+        wpmt.insert((0, "II".to_string()), 0.5);
+        wpmt.insert((0, "I".to_string()), 0.5);
+        wpmt.insert((0, "0".to_string()), 0.0);
+        wpmt
+    };
+
+    static ref STANDARD_ALLEGIANCE_GROUPS: AllegianceGroups = AllegianceGroups::standard();
+
+    // Values on GTFT16 are ranges, but we use averages for repeatability.
+    pub(crate) static ref DBTN_TO_CREDITS: Vec<u64> = vec![
+        0,  // GTFT16 says 0-5, but 0 DBTN can also mean unreachable, so use 0.
+        7,
+        30,
+        75,
+        300,
+        750,
+        3_000,
+        7_500,
+        30_000,
+        75_000,
+        300_000,
+        750_000,
+        3_000_000,
+        7_500_000,
+        30_000_000,
+        75_000_000,
+        300_000_000,
+        750_000_000,
+        3_000_000_000,
+        7_500_000_000,
+        30_000_000_000,
+        75_000_000_000,
+        300_000_000_000,
+        750_000_000_000,
+        3_000_000_000_000,
+        7_500_000_000_000,
+    ];
+}
+
+/// A system of rules for how much trade there is between two worlds.
+///
+/// The bilateral trade number (BTN) between two worlds is the sum of their
+/// world trade numbers (WTN), plus a modifier for the pair (and another for
+/// passenger traffic), minus a modifier for the navigable distance between
+/// them.  It is then capped, and can't go below a floor.  Each half point of
+/// BTN is worth a number of credits of trade per year.
+///
+/// [`Ruleset`] implements this for the GURPS Traveller rules.  Other rule
+/// systems can implement it and be passed to TradeModel::rules.
+pub trait TradeRules: fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+
+    /// World trade number
+    fn wtn(&self, world: &World) -> f64;
+
+    /// Modifier to BTN for trade between this particular pair of worlds,
+    /// like complementary trade classifications.
+    fn pair_modifier(&self, world1: &World, world2: &World) -> f64;
+
+    /// The most that pair_modifier can add to BTN.  This is used to skip
+    /// pairs of worlds that can't trade enough to matter, so it must not be
+    /// too low.
+    fn max_pair_bonus(&self) -> f64;
+
+    /// The most that pair_modifier can take away from BTN.
+    fn max_pair_penalty(&self) -> f64;
+
+    /// Extra BTN for passenger rather than freight traffic.
+    fn passenger_modifier(&self, world1: &World, world2: &World) -> f64;
+
+    /// BTN modifier (subtracted) for a navigable distance in parsecs, which
+    /// is apsp::INFINITY if there's no path.
+    fn distance_modifier(&self, distance: u32) -> f64;
+
+    /// The highest BTN allowed between worlds with these WTNs.  This must
+    /// not decrease when either WTN increases.
+    fn btn_cap(&self, wtn1: f64, wtn2: f64) -> f64;
+
+    /// The lowest BTN allowed.
+    fn min_btn(&self) -> f64;
+
+    /// Credits of trade for each doubled BTN from 0 through 12.5 (so 26
+    /// entries), not decreasing.
+    fn dbtn_to_credits(&self) -> &[u64];
+
+    /// Which allegiance codes count as the same allegiance.
+    fn allegiance_groups(&self) -> &AllegianceGroups {
+        &STANDARD_ALLEGIANCE_GROUPS
+    }
+
+    /// Closed and penalized allegiance borders.  Closed borders stop jumps
+    /// as well as trade.
+    fn scenario(&self) -> &Scenario {
+        &NO_SCENARIO
+    }
+}
+
+/// Return true if allegiance is code, or starts with code if it ends in '*'.
+pub(crate) fn allegiance_matches(code: &str, allegiance: &str) -> bool {
+    match code.strip_suffix('*') {
+        Some(prefix) => allegiance.starts_with(prefix),
+        None => allegiance == code,
+    }
+}
+
+/// Named groups of allegiance codes that count as one allegiance, so that
+/// trade between them isn't penalized as foreign.
+///
+/// Each code in a group is either a whole allegiance code like "CsIm" or a
+/// prefix ending in '*' like "Im*".  If a code is in more than one group,
+/// the first one wins.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AllegianceGroups {
+    groups: Vec<(String, Vec<String>)>,
+}
+
+impl AllegianceGroups {
+    /// The Third Imperium with its client states, the Zhodani Consulate with
+    /// its client states, the Aslan Hierate, and the Vargr Extents.
+    pub fn standard() -> AllegianceGroups {
+        let mut groups = AllegianceGroups::default();
+        groups.set(IMPERIUM, &["Im*", "CsIm"]);
+        groups.set("Zhodani", &["Zh*", "CsZh"]);
+        groups.set("Aslan", &["As*"]);
+        groups.set("Vargr", &["V*"]);
+        groups
+    }
+
+    /// Replace the group called name with codes, or add it at the end if
+    /// there isn't one.  No codes removes the group.
+    pub fn set<S: AsRef<str>>(&mut self, name: &str, codes: &[S]) {
+        let codes: Vec<String> = codes.iter().map(|code| code.as_ref().to_string()).collect();
+        let index = self.groups.iter().position(|(name2, _)| name2 == name);
+        match (index, codes.is_empty()) {
+            (Some(index), true) => {
+                self.groups.remove(index);
+            }
+            (Some(index), false) => self.groups[index].1 = codes,
+            (None, true) => (),
+            (None, false) => self.groups.push((name.to_string(), codes)),
+        }
+    }
+
+    /// Return the name of the group that allegiance is in, if any.
+    pub fn group(&self, allegiance: &str) -> Option<&str> {
+        for (name, codes) in self.groups.iter() {
+            if codes
+                .iter()
+                .any(|code| allegiance_matches(code, allegiance))
+            {
+                return Some(name);
+            }
+        }
+        None
+    }
+}
+
+/// The tables and constants that turn worlds into trade numbers.
+///
+/// The built-in rulesets are GURPS Traveller: Far Trader and GURPS
+/// Traveller: Interstellar Wars.  A ruleset file can start from either and
+/// override any of the fields; see [`Ruleset::load`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ruleset {
+    pub name: String,
+    /// GURPS tech level for each Traveller tech level, 0 through 23
+    pub tech_level_traveller_to_gurps: Vec<u64>,
+    /// WTN modifier for each UWTN (0 through 7+) and GURPS starport
+    pub wtn_port_modifier_table: HashMap<(u64, String), f64>,
+    /// Credits of trade for each doubled BTN, 0 through 12.5
+    pub dbtn_to_credits: Vec<u64>,
+    /// Upper bounds of the navigable distances for each half point of
+    /// distance modifier.  Longer distances get half a point more than the
+    /// last one.
+    pub distance_modifier_table: Vec<u32>,
+    /// Worlds this many parsecs or more apart can't trade at all.
+    pub max_distance: u32,
+    pub ag_wtcm_bonus: f64,
+    pub in_wtcm_bonus: f64,
+    pub different_allegiance_wtcm_penalty: f64,
+    pub ri_pbtn_bonus: f64,
+    pub cp_pbtn_bonus: f64,
+    pub cs_pbtn_bonus: f64,
+    // Rules don't say BTN can't be negative but it seems reasonable to me.
+    pub absolute_min_btn: f64,
+    /// BTN can't be more than max_btn_wtn_multiplier times the smaller WTN
+    /// plus max_btn_wtn_delta.
+    pub max_btn_wtn_multiplier: f64,
+    pub max_btn_wtn_delta: f64,
+    pub allegiance_groups: AllegianceGroups,
+    /// Not part of ruleset files; see [`Scenario::load`].
+    pub scenario: Scenario,
+}
+
+impl Default for Ruleset {
+    fn default() -> Ruleset {
+        Ruleset::far_trader()
+    }
+}
+
+impl Ruleset {
+    /// GURPS Traveller: Far Trader
+    pub fn far_trader() -> Ruleset {
+        Ruleset {
+            name: FAR_TRADER.to_string(),
+            tech_level_traveller_to_gurps: TECH_LEVEL_TRAVELLER_TO_GURPS.to_vec(),
+            wtn_port_modifier_table: WTN_PORT_MODIFIER_TABLE.clone(),
+            dbtn_to_credits: DBTN_TO_CREDITS.clone(),
+            distance_modifier_table: vec![1, 2, 5, 9, 19, 29, 59, 99, 199, 299, 599, 999],
+            max_distance: u32::MAX,
+            ag_wtcm_bonus: 0.5,
+            in_wtcm_bonus: 0.5,
+            different_allegiance_wtcm_penalty: 0.5,
+            ri_pbtn_bonus: 0.5,
+            cp_pbtn_bonus: 0.5,
+            cs_pbtn_bonus: 0.5,
+            absolute_min_btn: 0.0,
+            max_btn_wtn_multiplier: 1.0,
+            max_btn_wtn_delta: 5.0,
+            allegiance_groups: AllegianceGroups::standard(),
+            scenario: Scenario::default(),
+        }
+    }
+
+    /// GURPS Traveller: Interstellar Wars, which has a shorter distance
+    /// table, and caps BTN at twice the smaller WTN plus 1.
+    pub fn interstellar_wars() -> Ruleset {
+        Ruleset {
+            name: INTERSTELLAR_WARS.to_string(),
+            distance_modifier_table: vec![0, 2, 5, 9, 19],
+            max_distance: 20,
+            max_btn_wtn_multiplier: 2.0,
+            max_btn_wtn_delta: 1.0,
+            ..Ruleset::far_trader()
+        }
+    }
+
+    /// Return the built-in ruleset with this name, if there is one.
+    pub fn named(name: &str) -> Option<Ruleset> {
+        match name {
+            FAR_TRADER => Some(Ruleset::far_trader()),
+            INTERSTELLAR_WARS => Some(Ruleset::interstellar_wars()),
+            _ => None,
+        }
+    }
+
+    /// Return the built-in ruleset called name_or_path, or else load the
+    /// ruleset file at that path.
+    pub fn find(name_or_path: &str) -> Result<Ruleset> {
+        match Ruleset::named(name_or_path) {
+            Some(rules) => Ok(rules),
+            None => {
+                let path = Path::new(name_or_path);
+                if !path.exists() {
+                    bail!(
+                        "No ruleset file {} (built-in rulesets are {})",
+                        name_or_path,
+                        RULESET_NAMES.join(", ")
+                    );
+                }
+                Ruleset::load(path)
+            }
+        }
+    }
+
+    /// Load a ruleset file, in TOML if its name ends in ".toml" and in JSON
+    /// otherwise.
+    ///
+    /// The file starts from the built-in ruleset named by its "base" key, or
+    /// Far Trader if it doesn't have one, and each other key overrides the
+    /// field of the same name.  The WTN port modifier table is a table of
+    /// UWTNs to tables of GURPS starports, and only the entries given are
+    /// overridden:
+    ///
+    /// ```toml
+    /// base = "interstellar-wars"
+    /// name = "Our house rules"
+    /// ag_wtcm_bonus = 1.0
+    /// distance_modifier_table = [1, 3, 6, 9, 19]
+    ///
+    /// [wtn_port_modifier_table.7]
+    /// "0" = -4.0
+    /// ```
+    ///
+    /// Allegiance groups work the same way: each group given replaces the
+    /// built-in group of that name, or is added after them, and an empty
+    /// group removes it:
+    ///
+    /// ```toml
+    /// [allegiance_groups]
+    /// Solomani = ["So*", "CsSo"]
+    /// Vargr = []
+    /// ```
+    ///
+    /// The name defaults to the file's name without its extension.
+    pub fn load(path: &Path) -> Result<Ruleset> {
+        let mut value = read_value(path, "ruleset")?;
+        if let Some(obj) = value.as_object_mut() {
+            if !obj.contains_key("name") {
+                if let Some(stem) = path.file_stem() {
+                    obj.insert(
+                        "name".to_string(),
+                        Value::String(stem.to_string_lossy().to_string()),
+                    );
+                }
+            }
+        }
+        Ruleset::from_value(&value).with_context(|| format!("Bad ruleset {}", path.display()))
+    }
+
+    /// Build a ruleset from a parsed ruleset file.
+    pub fn from_value(value: &Value) -> Result<Ruleset> {
+        let obj = value
+            .as_object()
+            .ok_or_else(|| anyhow!("Ruleset is not a table"))?;
+        let mut rules = match obj.get("base") {
+            None => Ruleset::far_trader(),
+            Some(base) => {
+                let base = base
+                    .as_str()
+                    .ok_or_else(|| anyhow!("base must be a string"))?;
+                Ruleset::named(base).ok_or_else(|| {
+                    anyhow!(
+                        "Unknown base ruleset {} (built-in rulesets are {})",
+                        base,
+                        RULESET_NAMES.join(", ")
+                    )
+                })?
+            }
+        };
+        for (key, value) in obj.iter() {
+            match key.as_str() {
+                "base" => (),
+                "name" => rules.name = as_str(key, value)?.to_string(),
+                "tech_level_traveller_to_gurps" => {
+                    rules.tech_level_traveller_to_gurps = as_u64_vec(key, value)?
+                }
+                "wtn_port_modifier_table" => {
+                    for ((uwtn, starport), modifier) in as_port_modifiers(key, value)? {
+                        rules
+                            .wtn_port_modifier_table
+                            .insert((uwtn, starport), modifier);
+                    }
+                }
+                "dbtn_to_credits" => rules.dbtn_to_credits = as_u64_vec(key, value)?,
+                "distance_modifier_table" => {
+                    rules.distance_modifier_table = as_u64_vec(key, value)?
+                        .iter()
+                        .map(|distance| as_u32(key, *distance))
+                        .collect::<Result<Vec<u32>>>()?
+                }
+                "max_distance" => {
+                    rules.max_distance = as_u32(key, as_u64(key, value)?)?;
+                }
+                "ag_wtcm_bonus" => rules.ag_wtcm_bonus = as_f64(key, value)?,
+                "in_wtcm_bonus" => rules.in_wtcm_bonus = as_f64(key, value)?,
+                "different_allegiance_wtcm_penalty" => {
+                    rules.different_allegiance_wtcm_penalty = as_f64(key, value)?
+                }
+                "ri_pbtn_bonus" => rules.ri_pbtn_bonus = as_f64(key, value)?,
+                "cp_pbtn_bonus" => rules.cp_pbtn_bonus = as_f64(key, value)?,
+                "cs_pbtn_bonus" => rules.cs_pbtn_bonus = as_f64(key, value)?,
+                "absolute_min_btn" => rules.absolute_min_btn = as_f64(key, value)?,
+                "max_btn_wtn_multiplier" => rules.max_btn_wtn_multiplier = as_f64(key, value)?,
+                "max_btn_wtn_delta" => rules.max_btn_wtn_delta = as_f64(key, value)?,
+                "allegiance_groups" => {
+                    for (name, codes) in as_allegiance_groups(key, value)? {
+                        rules.allegiance_groups.set(&name, &codes);
+                    }
+                }
+                _ => bail!("Unknown ruleset key {}", key),
+            }
+        }
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.tech_level_traveller_to_gurps.len() != TECH_LEVEL_TRAVELLER_TO_GURPS.len() {
+            bail!(
+                "tech_level_traveller_to_gurps needs {} entries, not {}",
+                TECH_LEVEL_TRAVELLER_TO_GURPS.len(),
+                self.tech_level_traveller_to_gurps.len()
+            );
+        }
+        if self.dbtn_to_credits.len() != DBTN_TO_CREDITS.len() {
+            bail!(
+                "dbtn_to_credits needs {} entries, not {}",
+                DBTN_TO_CREDITS.len(),
+                self.dbtn_to_credits.len()
+            );
+        }
+        if self
+            .dbtn_to_credits
+            .windows(2)
+            .any(|pair| pair[0] > pair[1])
+        {
+            bail!("dbtn_to_credits must not decrease");
+        }
+        if self
+            .distance_modifier_table
+            .windows(2)
+            .any(|pair| pair[0] >= pair[1])
+        {
+            bail!("distance_modifier_table must increase");
+        }
+        if self.max_btn_wtn_multiplier < 0.0 {
+            bail!("max_btn_wtn_multiplier must not be negative");
+        }
+        Ok(())
+    }
+
+    /// Return the WTN modifier for a UWTN and GURPS starport.
+    pub fn wtn_port_modifier(&self, uwtn: f64, g_starport: &str) -> f64 {
+        // The table's last row is for UWTN 7+
+        let iuwtn = u64::min(7, u64::max(0, uwtn as u64));
+        *self
+            .wtn_port_modifier_table
+            .get(&(iuwtn, g_starport.to_string()))
+            .unwrap()
+    }
+}
+
+/// The GURPS Traveller trade rules, Far Trader or Interstellar Wars
+/// depending on the tables.
+impl TradeRules for Ruleset {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn wtn(&self, world: &World) -> f64 {
+        world.uwtn(self) + world.wtn_port_modifier(self)
+    }
+
+    fn pair_modifier(&self, world1: &World, world2: &World) -> f64 {
+        world1.wtcm(world2, self)
+    }
+
+    fn max_pair_bonus(&self) -> f64 {
+        self.ag_wtcm_bonus + self.in_wtcm_bonus
+    }
+
+    fn max_pair_penalty(&self) -> f64 {
+        self.different_allegiance_wtcm_penalty + self.scenario.max_penalty()
+    }
+
+    fn passenger_modifier(&self, world1: &World, world2: &World) -> f64 {
+        let mut modifier = 0.0;
+        for world in [world1, world2] {
+            if world.trade_classifications.contains("Ri") {
+                modifier += self.ri_pbtn_bonus;
+            }
+            if world.trade_classifications.contains("Cp") {
+                modifier += self.cp_pbtn_bonus;
+            }
+            if world.trade_classifications.contains("Cs") {
+                modifier += self.cs_pbtn_bonus;
+            }
+        }
+        modifier
+    }
+
+    fn distance_modifier(&self, distance: u32) -> f64 {
+        if distance >= self.max_distance {
+            return MAX_DISTANCE_PENALTY;
+        }
+        let index = bisect_left(&self.distance_modifier_table, &distance);
+        index as f64 / 2.0
+    }
+
+    fn btn_cap(&self, wtn1: f64, wtn2: f64) -> f64 {
+        self.max_btn_wtn_multiplier * f64::min(wtn1, wtn2) + self.max_btn_wtn_delta
+    }
+
+    fn min_btn(&self) -> f64 {
+        self.absolute_min_btn
+    }
+
+    fn dbtn_to_credits(&self) -> &[u64] {
+        &self.dbtn_to_credits
+    }
+
+    fn allegiance_groups(&self) -> &AllegianceGroups {
+        &self.allegiance_groups
+    }
+
+    fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+}
+
+fn parse_toml(text: &str) -> Result<Value> {
+    let value: toml::Value = toml::from_str(text)?;
+    Ok(serde_json::to_value(value)?)
+}
+
+fn parse_json(text: &str) -> Result<Value> {
+    Ok(serde_json::from_str(text)?)
+}
+
+/// Read a TOML file if its name ends in ".toml", or else a JSON file, and
+/// return it as JSON.  kind is what the file is, for error messages.
+pub(crate) fn read_value(path: &Path, kind: &str) -> Result<Value> {
+    let text = read_to_string(path)
+        .with_context(|| format!("Could not read {} {}", kind, path.display()))?;
+    let value = if path.extension() == Some(OsStr::new("toml")) {
+        parse_toml(&text)
+    } else {
+        parse_json(&text)
+    };
+    value.with_context(|| format!("Bad {} {}", kind, path.display()))
+}
+
+pub(crate) fn as_str<'a>(key: &str, value: &'a Value) -> Result<&'a str> {
+    value
+        .as_str()
+        .ok_or_else(|| anyhow!("{} must be a string", key))
+}
+
+pub(crate) fn as_f64(key: &str, value: &Value) -> Result<f64> {
+    value
+        .as_f64()
+        .ok_or_else(|| anyhow!("{} must be a number", key))
+}
+
+fn as_u64(key: &str, value: &Value) -> Result<u64> {
+    value
+        .as_u64()
+        .ok_or_else(|| anyhow!("{} must be a non-negative integer", key))
+}
+
+fn as_u32(key: &str, value: u64) -> Result<u32> {
+    u32::try_from(value).map_err(|_| anyhow!("{} is too big: {}", key, value))
+}
+
+fn as_u64_vec(key: &str, value: &Value) -> Result<Vec<u64>> {
+    value
+        .as_array()
+        .ok_or_else(|| anyhow!("{} must be a list", key))?
+        .iter()
+        .map(|element| as_u64(key, element))
+        .collect()
+}
+
+fn as_port_modifiers(key: &str, value: &Value) -> Result<Vec<((u64, String), f64)>> {
+    let table = value
+        .as_object()
+        .ok_or_else(|| anyhow!("{} must be a table", key))?;
+    let mut modifiers = Vec::new();
+    for (uwtn_str, row) in table {
+        let uwtn: u64 = uwtn_str
+            .parse()
+            .ok()
+            .filter(|uwtn| *uwtn <= 7)
+            .ok_or_else(|| anyhow!("{} has bad UWTN {}", key, uwtn_str))?;
+        let row = row
+            .as_object()
+            .ok_or_else(|| anyhow!("{} UWTN {} must be a table", key, uwtn))?;
+        for (starport, modifier) in row {
+            if !GURPS_STARPORTS.contains(&starport.as_str()) {
+                bail!("{} has bad starport {}", key, starport);
+            }
+            modifiers.push(((uwtn, starport.to_string()), as_f64(key, modifier)?));
+        }
+    }
+    Ok(modifiers)
+}
+
+fn as_allegiance_groups(key: &str, value: &Value) -> Result<Vec<(String, Vec<String>)>> {
+    let table = value
+        .as_object()
+        .ok_or_else(|| anyhow!("{} must be a table", key))?;
+    let mut groups = Vec::new();
+    for (name, codes) in table {
+        let codes = codes
+            .as_array()
+            .ok_or_else(|| anyhow!("{} group {} must be a list", key, name))?
+            .iter()
+            .map(|code| as_str(key, code).map(|code| code.to_string()))
+            .collect::<Result<Vec<String>>>()?;
+        if codes.iter().any(|code| code.is_empty() || code == "*") {
+            bail!("{} group {} has an empty code", key, name);
+        }
+        groups.push((name.to_string(), codes));
+    }
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apsp::INFINITY;
+    use std::fs::write;
+    use tempfile::tempdir;
+
+    const EXAMPLE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/rulesets/example.toml");
+
+    #[test]
+    fn test_builtin_rulesets() -> Result<()> {
+        for name in RULESET_NAMES {
+            let rules = Ruleset::find(name)?;
+            assert_eq!(rules.name, name);
+            assert!(rules.validate().is_ok());
+        }
+        assert_eq!(Ruleset::default(), Ruleset::far_trader());
+        assert!(Ruleset::named("gurps").is_none());
+        assert!(Ruleset::find("no/such/rules.toml").is_err());
+
+        let ft = Ruleset::far_trader();
+        assert_eq!(ft.distance_modifier(999), 5.5);
+        assert_eq!(ft.distance_modifier(1000), 6.0);
+        assert_eq!(ft.distance_modifier(INFINITY), MAX_DISTANCE_PENALTY);
+        assert_eq!(ft.btn_cap(5.0, 7.0), 10.0);
+        assert_eq!(ft.dbtn_to_credits()[25], 7_500_000_000_000);
+        let iw = Ruleset::interstellar_wars();
+        assert_eq!(iw.btn_cap(4.0, 3.0), 7.0);
+        assert_eq!(iw.max_pair_bonus(), 1.0);
+        assert_eq!(iw.max_pair_penalty(), 0.5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_example() -> Result<()> {
+        let rules = Ruleset::find(EXAMPLE_PATH)?;
+        assert_eq!(rules.name, "example house rules");
+        assert_eq!(rules.ag_wtcm_bonus, 1.0);
+        assert_eq!(rules.in_wtcm_bonus, 0.5);
+        assert_eq!(rules.max_pair_bonus(), 1.5);
+        assert_eq!(rules.distance_modifier(3), 1.0);
+        assert_eq!(rules.distance_modifier(60), MAX_DISTANCE_PENALTY);
+        assert_eq!(rules.wtn_port_modifier(7.5, "0"), -4.5);
+        assert_eq!(rules.wtn_port_modifier(7.5, "I"), -2.5);
+        assert_eq!(rules.wtn_port_modifier(6.0, "0"), -4.0);
+        assert_eq!(rules.wtn_port_modifier(5.0, "0"), -4.0);
+        assert_eq!(rules.allegiance_groups.group("SoCf"), Some("Solomani"));
+        assert_eq!(rules.allegiance_groups.group("ImDd"), Some(IMPERIUM));
+        assert_eq!(rules.allegiance_groups.group("VAug"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_allegiance_groups() {
+        let mut groups = AllegianceGroups::standard();
+        assert_eq!(groups.group("ImDd"), Some(IMPERIUM));
+        assert_eq!(groups.group("CsIm"), Some(IMPERIUM));
+        assert_eq!(groups.group("ZhCo"), Some("Zhodani"));
+        assert_eq!(groups.group("CsZh"), Some("Zhodani"));
+        assert_eq!(groups.group("AsT3"), Some("Aslan"));
+        assert_eq!(groups.group("VDeG"), Some("Vargr"));
+        assert_eq!(groups.group("CsAs"), None);
+        assert_eq!(groups.group("NaHu"), None);
+        assert_eq!(groups.group("SwCf"), None);
+        assert_eq!(groups.group(""), None);
+        assert_eq!(AllegianceGroups::default().group("ImDd"), None);
+
+        // First match wins.
+        groups.set("Domain of Deneb", &["ImDd"]);
+        assert_eq!(groups.group("ImDd"), Some(IMPERIUM));
+        groups.set(IMPERIUM, &["ImDv", "CsIm"]);
+        assert_eq!(groups.group("ImDv"), Some(IMPERIUM));
+        assert_eq!(groups.group("ImDd"), Some("Domain of Deneb"));
+        groups.set("Domain of Deneb", &[] as &[&str]);
+        assert_eq!(groups.group("ImDd"), None);
+    }
+
+    #[test]
+    fn test_load_json() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("referee.json");
+        write(
+            &path,
+            r#"{"base": "interstellar-wars", "max_btn_wtn_delta": 2,
+                "tech_level_traveller_to_gurps":
+                    [3, 4, 5, 5, 5, 6, 6, 7, 8, 9, 9, 9, 10, 10, 11, 12, 13, 13,
+                     14, 14, 14, 14, 14, 14]}"#,
+        )?;
+        let rules = Ruleset::load(&path)?;
+        // Named for the file
+        assert_eq!(rules.name, "referee");
+        assert_eq!(rules.max_btn_wtn_multiplier, 2.0);
+        assert_eq!(rules.max_btn_wtn_delta, 2.0);
+        assert_eq!(rules.tech_level_traveller_to_gurps[0], 3);
+        assert_eq!(rules.max_distance, 20);
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_rulesets() -> Result<()> {
+        for (text, message) in [
+            (
+                "ag_wtcm_bonus = 1.0\nag_wtcm_bonnus = 1.0",
+                "Unknown ruleset key",
+            ),
+            ("base = \"traveller5\"", "Unknown base ruleset"),
+            ("ag_wtcm_bonus = \"lots\"", "must be a number"),
+            ("dbtn_to_credits = [0, 7, 30]", "needs 26 entries"),
+            ("distance_modifier_table = [1, 5, 2]", "must increase"),
+            ("max_distance = -1", "non-negative integer"),
+            ("[wtn_port_modifier_table.8]\nV = 0.0", "bad UWTN"),
+            ("[wtn_port_modifier_table.7]\nVI = 0.0", "bad starport"),
+            ("[allegiance_groups]\nImperium = \"Im*\"", "must be a list"),
+            ("[allegiance_groups]\nImperium = [\"*\"]", "empty code"),
+            ("ag_wtcm_bonus = ", ""),
+        ] {
+            let temp_dir = tempdir()?;
+            let path = temp_dir.path().join("bad.toml");
+            write(&path, text)?;
+            let err = Ruleset::load(&path).unwrap_err();
+            assert!(
+                format!("{:#}", err).contains(message),
+                "{}: {:#}",
+                text,
+                err
+            );
+            temp_dir.close()?;
+        }
+
+        Ok(())
+    }
+}
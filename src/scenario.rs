@@ -0,0 +1,288 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::rules::{allegiance_matches, as_f64, as_str, read_value};
+use crate::MAX_DISTANCE_PENALTY;
+
+lazy_static! {
+    pub(crate) static ref NO_SCENARIO: Scenario = Scenario::default();
+}
+
+/// A border between two sets of allegiance codes, which is either closed or
+/// costs extra BTN to trade across.
+///
+/// Each code is a whole allegiance code like "CsIm" or a prefix ending in '*'
+/// like "Im*".
+#[derive(Clone, Debug, PartialEq)]
+pub struct Border {
+    pub between: Vec<String>,
+    pub and: Vec<String>,
+    /// No jumps and no trade across the border
+    pub closed: bool,
+    /// Taken off the BTN of trade across the border
+    pub penalty: f64,
+}
+
+impl Border {
+    /// Return true if worlds with these allegiances are on opposite sides of
+    /// this border.
+    pub fn crosses(&self, allegiance1: &str, allegiance2: &str) -> bool {
+        let on = |codes: &[String], allegiance: &str| {
+            codes
+                .iter()
+                .any(|code| allegiance_matches(code, allegiance))
+        };
+        (on(&self.between, allegiance1) && on(&self.and, allegiance2))
+            || (on(&self.between, allegiance2) && on(&self.and, allegiance1))
+    }
+}
+
+/// A campaign situation, like a war, that closes some allegiance borders and
+/// penalizes trade across others.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scenario {
+    pub name: String,
+    pub borders: Vec<Border>,
+}
+
+impl Scenario {
+    /// Load a scenario file, in TOML if its name ends in ".toml" and in JSON
+    /// otherwise.  Each border lists the allegiance codes on each side, and
+    /// is either closed or has a BTN penalty:
+    ///
+    /// ```toml
+    /// name = "Fifth Frontier War"
+    ///
+    /// [[borders]]
+    /// between = ["Im*", "CsIm"]
+    /// and = ["Zh*", "CsZh"]
+    /// closed = true
+    ///
+    /// [[borders]]
+    /// between = ["Im*"]
+    /// and = ["SwCf"]
+    /// penalty = 1.0
+    /// ```
+    ///
+    /// The name defaults to the file's name without its extension.
+    pub fn load(path: &Path) -> Result<Scenario> {
+        let value = read_value(path, "scenario")?;
+        let mut scenario = Scenario::from_value(&value)
+            .with_context(|| format!("Bad scenario {}", path.display()))?;
+        if scenario.name.is_empty() {
+            if let Some(stem) = path.file_stem() {
+                scenario.name = stem.to_string_lossy().to_string();
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Build a scenario from a parsed scenario file.
+    pub fn from_value(value: &Value) -> Result<Scenario> {
+        let obj = value
+            .as_object()
+            .ok_or_else(|| anyhow!("Scenario is not a table"))?;
+        let mut scenario = Scenario::default();
+        for (key, value) in obj.iter() {
+            match key.as_str() {
+                "name" => scenario.name = as_str(key, value)?.to_string(),
+                "borders" => {
+                    let borders = value
+                        .as_array()
+                        .ok_or_else(|| anyhow!("{} must be a list", key))?;
+                    for (ii, border) in borders.iter().enumerate() {
+                        scenario.borders.push(
+                            as_border(border).with_context(|| format!("Bad border {}", ii + 1))?,
+                        );
+                    }
+                }
+                _ => bail!("Unknown scenario key {}", key),
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Return true if worlds with these allegiances can't jump to each other
+    /// or trade.
+    pub fn closed(&self, allegiance1: &str, allegiance2: &str) -> bool {
+        self.borders
+            .iter()
+            .any(|border| border.closed && border.crosses(allegiance1, allegiance2))
+    }
+
+    /// Return the BTN penalty for trade between worlds with these
+    /// allegiances.
+    pub fn penalty(&self, allegiance1: &str, allegiance2: &str) -> f64 {
+        if self.closed(allegiance1, allegiance2) {
+            return MAX_DISTANCE_PENALTY;
+        }
+        self.borders
+            .iter()
+            .filter(|border| border.crosses(allegiance1, allegiance2))
+            .map(|border| border.penalty)
+            .sum()
+    }
+
+    /// The most that penalty can return.
+    pub fn max_penalty(&self) -> f64 {
+        if self.borders.iter().any(|border| border.closed) {
+            MAX_DISTANCE_PENALTY
+        } else {
+            self.borders.iter().map(|border| border.penalty).sum()
+        }
+    }
+
+    /// The closed borders, which are all that matter for navigation.
+    pub fn closed_borders(&self) -> Vec<&Border> {
+        self.borders.iter().filter(|border| border.closed).collect()
+    }
+}
+
+fn as_codes(key: &str, value: &Value) -> Result<Vec<String>> {
+    let codes = value
+        .as_array()
+        .ok_or_else(|| anyhow!("{} must be a list", key))?
+        .iter()
+        .map(|code| as_str(key, code).map(|code| code.to_string()))
+        .collect::<Result<Vec<String>>>()?;
+    if codes.is_empty() {
+        bail!("{} must not be empty", key);
+    }
+    if codes.iter().any(|code| code.is_empty() || code == "*") {
+        bail!("{} has an empty code", key);
+    }
+    Ok(codes)
+}
+
+fn as_border(value: &Value) -> Result<Border> {
+    let obj = value
+        .as_object()
+        .ok_or_else(|| anyhow!("Border is not a table"))?;
+    let mut between = None;
+    let mut and = None;
+    let mut closed = false;
+    let mut penalty = 0.0;
+    for (key, value) in obj.iter() {
+        match key.as_str() {
+            "between" => between = Some(as_codes(key, value)?),
+            "and" => and = Some(as_codes(key, value)?),
+            "closed" => {
+                closed = value
+                    .as_bool()
+                    .ok_or_else(|| anyhow!("{} must be true or false", key))?
+            }
+            "penalty" => {
+                penalty = as_f64(key, value)?;
+                if penalty < 0.0 {
+                    bail!("{} must not be negative", key);
+                }
+            }
+            _ => bail!("Unknown border key {}", key),
+        }
+    }
+    Ok(Border {
+        between: between.ok_or_else(|| anyhow!("Border needs between"))?,
+        and: and.ok_or_else(|| anyhow!("Border needs and"))?,
+        closed,
+        penalty,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+    use tempfile::tempdir;
+
+    const EXAMPLE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/example.toml");
+
+    #[test]
+    fn test_load_example() -> Result<()> {
+        let scenario = Scenario::load(Path::new(EXAMPLE_PATH))?;
+        assert_eq!(scenario.name, "Fifth Frontier War");
+        assert_eq!(scenario.borders.len(), 2);
+        assert!(scenario.closed("ImDd", "ZhCo"));
+        assert!(scenario.closed("CsZh", "CsIm"));
+        assert!(!scenario.closed("ImDd", "ImDv"));
+        assert!(!scenario.closed("ImDd", "SwCf"));
+        assert_eq!(scenario.penalty("ImDd", "ZhCo"), MAX_DISTANCE_PENALTY);
+        assert_eq!(scenario.penalty("SwCf", "ImDd"), 1.0);
+        assert_eq!(scenario.penalty("SwCf", "CsIm"), 0.0);
+        assert_eq!(scenario.penalty("ImDd", "CsIm"), 0.0);
+        assert_eq!(scenario.max_penalty(), MAX_DISTANCE_PENALTY);
+        assert_eq!(scenario.closed_borders().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_json() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("embargo.json");
+        write(
+            &path,
+            r#"{"borders": [
+                {"between": ["Im*"], "and": ["NaHu"], "penalty": 1.5},
+                {"between": ["ImDd"], "and": ["NaHu", "CsIm"], "penalty": 0.5}
+            ]}"#,
+        )?;
+        let scenario = Scenario::load(&path)?;
+        // Named for the file
+        assert_eq!(scenario.name, "embargo");
+        assert!(!scenario.closed("ImDd", "NaHu"));
+        assert_eq!(scenario.penalty("NaHu", "ImDd"), 2.0);
+        assert_eq!(scenario.penalty("NaHu", "ImDv"), 1.5);
+        assert_eq!(scenario.penalty("CsIm", "ImDd"), 0.5);
+        assert_eq!(scenario.max_penalty(), 2.0);
+        assert!(scenario.closed_borders().is_empty());
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_scenarios() -> Result<()> {
+        for (text, message) in [
+            ("nmae = \"war\"", "Unknown scenario key"),
+            ("borders = 3", "must be a list"),
+            ("[[borders]]\nbetween = [\"Im*\"]", "needs and"),
+            (
+                "[[borders]]\nbetween = [\"Im*\"]\nand = []",
+                "must not be empty",
+            ),
+            (
+                "[[borders]]\nbetween = [\"Im*\"]\nand = [\"*\"]",
+                "empty code",
+            ),
+            (
+                "[[borders]]\nbetween = [\"Im*\"]\nand = [\"Zh*\"]\nclosed = 1",
+                "true or false",
+            ),
+            (
+                "[[borders]]\nbetween = [\"Im*\"]\nand = [\"Zh*\"]\npenalty = -1.0",
+                "must not be negative",
+            ),
+            (
+                "[[borders]]\nbetween = [\"Im*\"]\nand = [\"Zh*\"]\nclsoed = true",
+                "Unknown border key",
+            ),
+            ("[[borders]\n", ""),
+        ] {
+            let temp_dir = tempdir()?;
+            let path = temp_dir.path().join("bad.toml");
+            write(&path, text)?;
+            let err = Scenario::load(&path).unwrap_err();
+            assert!(
+                format!("{:#}", err).contains(message),
+                "{}: {:#}",
+                text,
+                err
+            );
+            temp_dir.close()?;
+        }
+
+        Ok(())
+    }
+}
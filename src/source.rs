@@ -0,0 +1,565 @@
+use anyhow::{anyhow, bail, Result};
+use log::debug;
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+extern crate reqwest;
+
+pub const DEFAULT_BASE_URL: &str = "https://travellermap.com/data/";
+
+/// Somewhere we can get the raw data for a sector: its column data (the .sec
+/// file) and its XML metadata, which includes xboat routes.
+pub trait SectorSource: fmt::Debug {
+    /// Return the tab or column delimited world data for a sector.
+    fn sector_data(&self, sector_name: &str) -> Result<String>;
+
+    /// Return the XML metadata for a sector.
+    fn sector_metadata(&self, sector_name: &str) -> Result<String>;
+
+    /// Return the JSON index of all sectors and their locations.
+    fn universe(&self) -> Result<String>;
+
+    /// Describe where the column data for a sector comes from, for error
+    /// messages.
+    fn data_location(&self, sector_name: &str) -> String {
+        sector_name.to_string()
+    }
+}
+
+/// Return whether body looks like an HTML page, which is what servers tend
+/// to send for errors and rate limits, rather than data.
+pub fn looks_like_html(body: &str) -> bool {
+    let start: String = body.trim_start().chars().take(20).collect();
+    let start = start.to_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
+
+/// Return an error unless body could be column data for sector_name.
+pub fn check_sector_data(sector_name: &str, body: &str) -> Result<()> {
+    if looks_like_html(body) {
+        bail!(
+            "Got an HTML page instead of data for sector {}",
+            sector_name
+        );
+    }
+    if body.trim().is_empty() {
+        bail!("Got empty data for sector {}", sector_name);
+    }
+    Ok(())
+}
+
+/// Return an error unless body could be XML metadata for sector_name.
+pub fn check_sector_metadata(sector_name: &str, body: &str) -> Result<()> {
+    if looks_like_html(body) {
+        bail!(
+            "Got an HTML page instead of metadata for sector {}",
+            sector_name
+        );
+    }
+    if !body.contains("<Sector") {
+        bail!(
+            "Got something other than metadata for sector {}",
+            sector_name
+        );
+    }
+    Ok(())
+}
+
+/// Return an error unless body could be the JSON universe index.
+pub fn check_universe(body: &str) -> Result<()> {
+    if looks_like_html(body) {
+        bail!("Got an HTML page instead of the universe");
+    }
+    if !body.trim_start().starts_with('{') {
+        bail!("Got something other than JSON for the universe");
+    }
+    Ok(())
+}
+
+/// Return whether err might go away if we try again: a network failure, a
+/// server error, a rate limit, or a body that isn't what we asked for.
+///
+/// Client errors like 404 Not Found will just happen again.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    match err
+        .downcast_ref::<reqwest::Error>()
+        .and_then(|req_err| req_err.status())
+    {
+        Some(status) => {
+            !status.is_client_error()
+                || status == StatusCode::REQUEST_TIMEOUT
+                || status == StatusCode::TOO_MANY_REQUESTS
+        }
+        None => true,
+    }
+}
+
+/// Read sector data from files named "<sector>.sec" and "<sector>.xml" in a
+/// directory.
+///
+/// If a milieu is given, the files are "<sector>.<milieu>.sec" and
+/// "<sector>.<milieu>.xml" instead, so data for different eras can live side
+/// by side.
+#[derive(Clone, Debug)]
+pub struct FsSource {
+    data_dir: PathBuf,
+    milieu: Option<String>,
+}
+
+impl FsSource {
+    pub fn new(data_dir: &Path, milieu: Option<&str>) -> FsSource {
+        FsSource {
+            data_dir: data_dir.to_path_buf(),
+            milieu: milieu.map(|st| st.to_string()),
+        }
+    }
+
+    fn path(&self, sector_name: &str, extension: &str) -> PathBuf {
+        let mut path = self.data_dir.to_path_buf();
+        let mut filename = sector_name.to_owned();
+        if let Some(milieu) = &self.milieu {
+            filename += ".";
+            filename += milieu;
+        }
+        filename += ".";
+        filename += extension;
+        path.push(filename);
+        path
+    }
+
+    /// Return the paths of the column data and XML metadata files for a
+    /// sector.
+    pub fn paths(&self, sector_name: &str) -> (PathBuf, PathBuf) {
+        (self.path(sector_name, "sec"), self.path(sector_name, "xml"))
+    }
+
+    /// Return the path of the sidecar "<sector>.meta" file that describes a
+    /// hand-authored sector in place of XML metadata.
+    pub fn custom_metadata_path(&self, sector_name: &str) -> PathBuf {
+        self.path(sector_name, "meta")
+    }
+
+    /// Return the path of the "universe.json" sector index.
+    pub fn universe_path(&self) -> PathBuf {
+        self.path("universe", "json")
+    }
+}
+
+impl SectorSource for FsSource {
+    fn sector_data(&self, sector_name: &str) -> Result<String> {
+        let (data_path, _) = self.paths(sector_name);
+        Ok(read_to_string(data_path)?)
+    }
+
+    fn sector_metadata(&self, sector_name: &str) -> Result<String> {
+        let (_, metadata_path) = self.paths(sector_name);
+        Ok(read_to_string(metadata_path)?)
+    }
+
+    fn universe(&self) -> Result<String> {
+        Ok(read_to_string(self.universe_path())?)
+    }
+
+    fn data_location(&self, sector_name: &str) -> String {
+        let (data_path, _) = self.paths(sector_name);
+        data_path.display().to_string()
+    }
+}
+
+/// Fetch sector data over HTTP from a server with the same layout as
+/// travellermap.com: "<base>/<sector>" and "<base>/<sector>/metadata", with
+/// the sector index at "<base>/../api/universe"
+///
+/// If a milieu is given, it is passed as the "milieu" query parameter, and
+/// the server picks the data for that era.
+///
+/// Error statuses, and bodies that are plainly not what we asked for (like
+/// an HTML error page served with 200 OK), are errors.
+#[derive(Clone, Debug)]
+pub struct HttpSource {
+    base_url: Url,
+    milieu: Option<String>,
+    // Shared so parallel downloads can reuse connections.
+    client: Client,
+}
+
+impl HttpSource {
+    pub fn new(base_url: &str, milieu: Option<&str>) -> Result<HttpSource> {
+        let mut base_url = Url::parse(base_url)?;
+        // Without a trailing slash, Url::join would replace the last path
+        // segment rather than append to it.
+        if !base_url.path().ends_with('/') {
+            let path = base_url.path().to_owned() + "/";
+            base_url.set_path(&path);
+        }
+        let milieu = milieu.map(|st| st.to_string());
+        let client = Client::new();
+        Ok(HttpSource {
+            base_url,
+            milieu,
+            client,
+        })
+    }
+
+    fn get(&self, path: &str) -> Result<String> {
+        let mut url = self.base_url.join(path)?;
+        if let Some(milieu) = &self.milieu {
+            url.query_pairs_mut().append_pair("milieu", milieu);
+        }
+        debug!("downloading {}", url);
+        let body = self.client.get(url).send()?.error_for_status()?.text()?;
+        Ok(body)
+    }
+}
+
+impl SectorSource for HttpSource {
+    fn sector_data(&self, sector_name: &str) -> Result<String> {
+        let body = self.get(sector_name)?;
+        check_sector_data(sector_name, &body)?;
+        Ok(body)
+    }
+
+    fn sector_metadata(&self, sector_name: &str) -> Result<String> {
+        let body = self.get(&(sector_name.to_owned() + "/metadata"))?;
+        check_sector_metadata(sector_name, &body)?;
+        Ok(body)
+    }
+
+    fn universe(&self) -> Result<String> {
+        let body = self.get("../api/universe")?;
+        check_universe(&body)?;
+        Ok(body)
+    }
+
+    fn data_location(&self, sector_name: &str) -> String {
+        match self.base_url.join(sector_name) {
+            Ok(url) => url.to_string(),
+            Err(_) => sector_name.to_string(),
+        }
+    }
+}
+
+/// Hold sector data in memory, for tests and for callers that build sectors
+/// without touching the filesystem or network.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySource {
+    sector_name_to_data: HashMap<String, (String, String)>,
+    universe: Option<String>,
+}
+
+impl MemorySource {
+    pub fn new() -> MemorySource {
+        MemorySource::default()
+    }
+
+    pub fn insert(&mut self, sector_name: &str, data: String, metadata: String) {
+        self.sector_name_to_data
+            .insert(sector_name.to_string(), (data, metadata));
+    }
+
+    pub fn set_universe(&mut self, universe: String) {
+        self.universe = Some(universe);
+    }
+
+    fn get(&self, sector_name: &str) -> Result<&(String, String)> {
+        self.sector_name_to_data
+            .get(sector_name)
+            .ok_or_else(|| anyhow!("No data for sector {}", sector_name))
+    }
+}
+
+impl SectorSource for MemorySource {
+    fn sector_data(&self, sector_name: &str) -> Result<String> {
+        Ok(self.get(sector_name)?.0.to_string())
+    }
+
+    fn sector_metadata(&self, sector_name: &str) -> Result<String> {
+        Ok(self.get(sector_name)?.1.to_string())
+    }
+
+    fn universe(&self) -> Result<String> {
+        self.universe.clone().ok_or_else(|| anyhow!("No universe"))
+    }
+}
+
+/// A tiny HTTP server that stands in for travellermap.com in tests.
+#[cfg(test)]
+pub mod stand_in {
+    use super::SectorSource;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    /// Serve source on a local port from a background thread, and return
+    /// the base URL to pass to HttpSource::new.
+    ///
+    /// The server lives until the test process exits.
+    pub fn serve<S: SectorSource + Clone + Send + 'static>(source: S) -> String {
+        serve_milieux(move |_milieu| source.clone())
+    }
+
+    /// Like serve, but build the source for each request from its milieu
+    /// query parameter, if any.
+    pub fn serve_milieux<S, F>(make_source: F) -> String
+    where
+        S: SectorSource,
+        F: Fn(Option<&str>) -> S + Send + 'static,
+    {
+        serve_with_failures(make_source, 0)
+    }
+
+    /// Like serve, but answer the first failures requests with 503 Service
+    /// Unavailable, like an overloaded server.
+    pub fn serve_flaky<S: SectorSource + Clone + Send + 'static>(
+        source: S,
+        failures: usize,
+    ) -> String {
+        serve_with_failures(move |_milieu| source.clone(), failures)
+    }
+
+    fn serve_with_failures<S, F>(make_source: F, failures: usize) -> String
+    where
+        S: SectorSource,
+        F: Fn(Option<&str>) -> S + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for (ii, stream) in listener.incoming().flatten().enumerate() {
+                // Keep serving even if one client misbehaves.
+                let _ = handle(&make_source, stream, ii < failures);
+            }
+        });
+        format!("http://{}/data/", addr)
+    }
+
+    fn handle<S, F>(make_source: &F, mut stream: TcpStream, fail: bool) -> std::io::Result<()>
+    where
+        S: SectorSource,
+        F: Fn(Option<&str>) -> S,
+    {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Drain the headers; we don't need them.
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+                break;
+            }
+        }
+
+        let target = request_line.split_whitespace().nth(1).unwrap_or("");
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let milieu_opt = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("milieu="))
+            .map(percent_decode);
+        let source = make_source(milieu_opt.as_deref());
+        let result = if path == "/api/universe" {
+            source.universe()
+        } else {
+            let path = percent_decode(path.strip_prefix("/data/").unwrap_or(""));
+            match path.strip_suffix("/metadata") {
+                Some(sector_name) => source.sector_metadata(sector_name),
+                None => source.sector_data(&path),
+            }
+        };
+        let (status, body) = match result {
+            _ if fail => ("503 Service Unavailable", "Try again later".to_string()),
+            Ok(body) => ("200 OK", body),
+            Err(err) => ("404 Not Found", err.to_string()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+
+    fn percent_decode(st: &str) -> String {
+        let bytes = st.as_bytes();
+        let mut decoded = Vec::new();
+        let mut ii = 0;
+        while ii < bytes.len() {
+            if bytes[ii] == b'%' && ii + 2 < bytes.len() {
+                if let Ok(hex) = std::str::from_utf8(&bytes[ii + 1..ii + 3]) {
+                    if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                        decoded.push(byte);
+                        ii += 3;
+                        continue;
+                    }
+                }
+            }
+            decoded.push(bytes[ii]);
+            ii += 1;
+        }
+        String::from_utf8_lossy(&decoded).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test_data");
+
+    #[test]
+    fn test_fs_source() {
+        let source = FsSource::new(Path::new(TEST_DATA_DIR), None);
+        let (data_path, metadata_path) = source.paths("Proving Ground");
+        assert!(data_path.ends_with("Proving Ground.sec"));
+        assert!(metadata_path.ends_with("Proving Ground.xml"));
+        assert!(source
+            .custom_metadata_path("Proving Ground")
+            .ends_with("Proving Ground.meta"));
+
+        let data = source.sector_data("Proving Ground").unwrap();
+        assert!(data.contains("Keystone"));
+        assert!(source
+            .data_location("Proving Ground")
+            .ends_with("Proving Ground.sec"));
+        let metadata = source.sector_metadata("Proving Ground").unwrap();
+        assert!(metadata.contains("<Name>Proving Ground</Name>"));
+
+        assert!(source.sector_data("Nowhere").is_err());
+        assert!(source.sector_metadata("Nowhere").is_err());
+
+        assert!(source.universe_path().ends_with("universe.json"));
+        assert!(source.universe().unwrap().contains("Proving Ground"));
+    }
+
+    #[test]
+    fn test_memory_source() {
+        let mut source = MemorySource::new();
+        source.insert("Somewhere", "data".to_string(), "<Sector/>".to_string());
+        assert_eq!(source.sector_data("Somewhere").unwrap(), "data");
+        assert_eq!(source.sector_metadata("Somewhere").unwrap(), "<Sector/>");
+        assert_eq!(source.data_location("Somewhere"), "Somewhere");
+        assert!(source.sector_data("Nowhere").is_err());
+        assert!(source.sector_metadata("Nowhere").is_err());
+        assert!(source.universe().is_err());
+        source.set_universe("{}".to_string());
+        assert_eq!(source.universe().unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_http_source() {
+        let fs_source = FsSource::new(Path::new(TEST_DATA_DIR), None);
+        let base_url = stand_in::serve(fs_source.clone());
+        let source = HttpSource::new(&base_url, None).unwrap();
+        // The space in the name has to survive URL encoding.
+        for sector_name in ["Mockingbird", "Spinward Marches"] {
+            assert_eq!(
+                source.sector_data(sector_name).unwrap(),
+                fs_source.sector_data(sector_name).unwrap()
+            );
+            assert_eq!(
+                source.sector_metadata(sector_name).unwrap(),
+                fs_source.sector_metadata(sector_name).unwrap()
+            );
+        }
+        assert!(source.sector_data("Nowhere").is_err());
+        assert!(source.sector_metadata("Nowhere").is_err());
+        assert_eq!(source.universe().unwrap(), fs_source.universe().unwrap());
+        assert_eq!(
+            source.data_location("Spinward Marches"),
+            base_url + "Spinward%20Marches"
+        );
+    }
+
+    #[test]
+    fn test_http_source_base_url() {
+        let mut memory_source = MemorySource::new();
+        memory_source.insert("Somewhere", "data".to_string(), "<Sector/>".to_string());
+        let base_url = stand_in::serve(memory_source);
+        let base_url = base_url.trim_end_matches('/');
+        let source = HttpSource::new(base_url, None).unwrap();
+        assert_eq!(source.sector_data("Somewhere").unwrap(), "data");
+        assert_eq!(source.sector_metadata("Somewhere").unwrap(), "<Sector/>");
+
+        assert!(HttpSource::new("not a url", None).is_err());
+    }
+
+    #[test]
+    fn test_fs_source_milieu() {
+        let source = FsSource::new(Path::new(TEST_DATA_DIR), Some("M0"));
+        let (data_path, metadata_path) = source.paths("Proving Ground");
+        assert!(data_path.ends_with("Proving Ground.M0.sec"));
+        assert!(metadata_path.ends_with("Proving Ground.M0.xml"));
+        assert!(source
+            .custom_metadata_path("Homebrew")
+            .ends_with("Homebrew.M0.meta"));
+        assert!(source.universe_path().ends_with("universe.M0.json"));
+    }
+
+    #[test]
+    fn test_http_source_milieu() {
+        let base_url = stand_in::serve_milieux(|milieu_opt| {
+            let mut memory_source = MemorySource::new();
+            let milieu = milieu_opt.unwrap_or("default").to_string();
+            let metadata = format!("<Sector>{}</Sector>", milieu);
+            memory_source.insert("Somewhere", milieu, metadata);
+            memory_source
+        });
+        let source = HttpSource::new(&base_url, None).unwrap();
+        assert_eq!(source.sector_data("Somewhere").unwrap(), "default");
+        let source = HttpSource::new(&base_url, Some("M1900")).unwrap();
+        assert_eq!(source.sector_data("Somewhere").unwrap(), "M1900");
+        assert_eq!(
+            source.sector_metadata("Somewhere").unwrap(),
+            "<Sector>M1900</Sector>"
+        );
+    }
+
+    #[test]
+    fn test_http_source_rejects_garbage() {
+        let error_page = "<!DOCTYPE html>\n<html><body>Too many requests</body></html>";
+        let mut memory_source = MemorySource::new();
+        memory_source.insert("Somewhere", error_page.to_string(), error_page.to_string());
+        memory_source.insert("Blank", " \n".to_string(), "not xml".to_string());
+        memory_source.set_universe(error_page.to_string());
+        let base_url = stand_in::serve(memory_source);
+        let source = HttpSource::new(&base_url, None).unwrap();
+        assert!(source.sector_data("Somewhere").is_err());
+        assert!(source.sector_metadata("Somewhere").is_err());
+        assert!(source.sector_data("Blank").is_err());
+        assert!(source.sector_metadata("Blank").is_err());
+        assert!(source.universe().is_err());
+    }
+
+    #[test]
+    fn test_check_bodies() {
+        assert!(looks_like_html("<!DOCTYPE html><html></html>"));
+        assert!(looks_like_html("\n  <HTML lang=\"en\">"));
+        assert!(!looks_like_html("Hex  Name  UWP"));
+        assert!(!looks_like_html("<?xml version=\"1.0\"?><Sector/>"));
+
+        assert!(check_sector_data("Somewhere", "Hex  Name  UWP").is_ok());
+        assert!(check_sector_data("Somewhere", "").is_err());
+        assert!(check_sector_metadata("Somewhere", "<?xml?><Sector></Sector>").is_ok());
+        assert!(check_sector_metadata("Somewhere", "<Error/>").is_err());
+        assert!(check_universe("{\"Sectors\":[]}").is_ok());
+        assert!(check_universe("Sectors").is_err());
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let base_url = stand_in::serve_flaky(MemorySource::new(), 1);
+        let source = HttpSource::new(&base_url, None).unwrap();
+        let err = source.sector_data("Somewhere").unwrap_err();
+        assert!(is_transient(&err));
+        let err = source.sector_data("Somewhere").unwrap_err();
+        assert!(!is_transient(&err));
+        assert!(is_transient(&anyhow!("Got an HTML page")));
+    }
+}
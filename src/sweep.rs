@@ -0,0 +1,356 @@
+use anyhow::{anyhow, bail, Result};
+use log::debug;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::Route::*;
+use crate::{Galaxy, RouteCounter, TradeMap, TradeModel};
+
+/// An inclusive range of values to sweep over, written like "0..8:0.5" for
+/// 0 to 8 in steps of 0.5.  The step defaults to 1, and a single value like
+/// "3" is allowed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SweepRange {
+    pub start: f64,
+    pub end: f64,
+    pub step: f64,
+}
+
+/// Return the number of digits after the decimal point in the shortest
+/// decimal form of value, like 1 for 0.1 and 0 for 3.
+fn decimal_places(value: f64) -> i32 {
+    match value.to_string().split_once('.') {
+        Some((_, fraction)) => fraction.len() as i32,
+        None => 0,
+    }
+}
+
+impl SweepRange {
+    /// Return the values from start to end, rounded to as many decimal
+    /// places as start and step have, so that "0..1:0.1" gives 0.3 and not
+    /// 0.30000000000000004.
+    pub fn values(&self) -> Vec<f64> {
+        // Allow for rounding error in the last step.
+        let steps = ((self.end - self.start) / self.step + 1e-9).floor() as u64;
+        let scale = 10f64.powi(decimal_places(self.start).max(decimal_places(self.step)));
+        (0..=steps)
+            .map(|ii| ((self.start + ii as f64 * self.step) * scale).round() / scale)
+            .collect()
+    }
+}
+
+impl FromStr for SweepRange {
+    type Err = anyhow::Error;
+
+    fn from_str(st: &str) -> Result<SweepRange> {
+        let (range, step) = match st.split_once(':') {
+            Some((range, step)) => (range, step.trim().parse()?),
+            None => (st, 1.0),
+        };
+        let (start, end) = match range.split_once("..") {
+            Some((start, end)) => (start.trim().parse()?, end.trim().parse()?),
+            None => {
+                let val = range.trim().parse()?;
+                (val, val)
+            }
+        };
+        if start > end {
+            bail!("Empty range {}", st);
+        }
+        if step <= 0.0 {
+            bail!("Step must be positive in '{}'", st);
+        }
+        Ok(SweepRange { start, end, step })
+    }
+}
+
+/// Turn a range of max jumps into one RouteCounter per jump, with that jump
+/// for every kind of route.
+pub fn max_jumps_for_range(range: &SweepRange) -> Result<Vec<RouteCounter>> {
+    let mut all_max_jumps = Vec::new();
+    for value in range.values() {
+        if value < 1.0 || value.fract() != 0.0 {
+            bail!("Max jump must be a positive integer, not {}", value);
+        }
+        let mut max_jumps = RouteCounter::new();
+        for route in [Minor, Feeder, Intermediate, Main, Major] {
+            max_jumps.insert(route, value as u64);
+        }
+        all_max_jumps.push(max_jumps);
+    }
+    Ok(all_max_jumps)
+}
+
+/// One combination of parameters in a sweep.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepPoint {
+    pub max_jumps: RouteCounter,
+    pub min_btn: f64,
+    pub min_route_btn: f64,
+}
+
+impl SweepPoint {
+    /// Return the max jump, like "3", or if it varies by route, each route's
+    /// max jump from minor to major, like "2-3-3-3-3".
+    pub fn max_jump_label(&self) -> String {
+        let jumps: Vec<u64> = [Minor, Feeder, Intermediate, Main, Major]
+            .iter()
+            .map(|route| self.max_jumps[route])
+            .collect();
+        if jumps.iter().all(|jump| *jump == jumps[0]) {
+            jumps[0].to_string()
+        } else {
+            let labels: Vec<String> = jumps.iter().map(|jump| jump.to_string()).collect();
+            labels.join("-")
+        }
+    }
+
+    /// Return the subdirectory for this point's output, like "3/8.5/10".
+    pub fn subdirectory(&self) -> PathBuf {
+        [
+            self.max_jump_label(),
+            self.min_btn.to_string(),
+            self.min_route_btn.to_string(),
+        ]
+        .iter()
+        .collect()
+    }
+}
+
+/// Every combination of these max jumps and BTN thresholds.
+#[derive(Clone, Debug)]
+pub struct Sweep {
+    pub max_jumps: Vec<RouteCounter>,
+    pub min_btns: Vec<f64>,
+    pub min_route_btns: Vec<f64>,
+}
+
+impl Sweep {
+    pub fn points(&self) -> Vec<SweepPoint> {
+        let mut points = Vec::new();
+        for max_jumps in self.max_jumps.iter() {
+            for min_btn in self.min_btns.iter() {
+                for min_route_btn in self.min_route_btns.iter() {
+                    points.push(SweepPoint {
+                        max_jumps: max_jumps.clone(),
+                        min_btn: *min_btn,
+                        min_route_btn: *min_route_btn,
+                    });
+                }
+            }
+        }
+        points
+    }
+
+    /// Compute trade routes over galaxy with model, changed to each point
+    /// in turn, and call visit with each result.  Neighbors and navigable
+    /// distances are only found again when the max jumps change.
+    ///
+    /// Return the number of routes of each kind at each point.
+    pub fn run<F>(
+        &self,
+        model: &TradeModel,
+        galaxy: Galaxy,
+        mut visit: F,
+    ) -> Result<Vec<(SweepPoint, RouteCounter)>>
+    where
+        F: FnMut(&SweepPoint, &TradeMap) -> Result<()>,
+    {
+        let mut results = Vec::new();
+        let mut galaxy_opt = Some(galaxy);
+        let mut trade_map_opt: Option<TradeMap> = None;
+        let points = self.points();
+        for (ii, point) in points.iter().enumerate() {
+            debug!(
+                "Sweep {}/{}: max jump {} min BTN {} min route BTN {}",
+                ii + 1,
+                points.len(),
+                point.max_jump_label(),
+                point.min_btn,
+                point.min_route_btn
+            );
+            let mut point_model = model
+                .clone()
+                .min_btn(point.min_btn)
+                .min_route_btn(point.min_route_btn);
+            for (route, max_jump) in point.max_jumps.iter() {
+                point_model = point_model.max_jump(*route, *max_jump);
+            }
+            let trade_map = match trade_map_opt.take() {
+                Some(previous) => point_model.recompute(previous)?,
+                None => point_model.compute(
+                    galaxy_opt
+                        .take()
+                        .ok_or_else(|| anyhow!("No galaxy to sweep"))?,
+                )?,
+            };
+            visit(point, &trade_map)?;
+            results.push((point.clone(), trade_map.route_counts()));
+            trade_map_opt = Some(trade_map);
+        }
+        Ok(results)
+    }
+}
+
+/// Write a CSV file with a line for each point in a sweep, and the number
+/// of routes of each kind there.
+pub fn write_summary_csv(path: &Path, results: &[(SweepPoint, RouteCounter)]) -> Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(
+        file,
+        "max_jump,min_btn,min_route_btn,major,main,intermediate,feeder,minor"
+    )?;
+    for (point, route_counts) in results {
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{}",
+            point.max_jump_label(),
+            point.min_btn,
+            point.min_route_btn,
+            route_counts[&Major],
+            route_counts[&Main],
+            route_counts[&Intermediate],
+            route_counts[&Feeder],
+            route_counts[&Minor]
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read_to_string;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    const TEST_DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test_data");
+
+    #[test]
+    fn test_parse_sweep_range() -> Result<()> {
+        assert_eq!(
+            "0..8:0.5".parse::<SweepRange>()?,
+            SweepRange {
+                start: 0.0,
+                end: 8.0,
+                step: 0.5
+            }
+        );
+        assert_eq!(
+            "1..6".parse::<SweepRange>()?.values(),
+            vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]
+        );
+        assert_eq!("3".parse::<SweepRange>()?.values(), vec![3.0]);
+        assert_eq!("6..7:0.1".parse::<SweepRange>()?.values().len(), 11);
+        assert_eq!("8..9:0.75".parse::<SweepRange>()?.values(), vec![8.0, 8.75]);
+        assert_eq!(
+            "0..1:0.1".parse::<SweepRange>()?.values(),
+            vec![0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0]
+        );
+        assert_eq!(
+            "0.05..0.3:0.1".parse::<SweepRange>()?.values(),
+            vec![0.05, 0.15, 0.25]
+        );
+        assert!("6..1".parse::<SweepRange>().is_err());
+        assert!("1..6:0".parse::<SweepRange>().is_err());
+        assert!("1..6:-1".parse::<SweepRange>().is_err());
+        assert!("a..b".parse::<SweepRange>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_jumps_for_range() -> Result<()> {
+        let all_max_jumps = max_jumps_for_range(&"2..3".parse()?)?;
+        assert_eq!(all_max_jumps.len(), 2);
+        assert_eq!(all_max_jumps[0][&Minor], 2);
+        assert_eq!(all_max_jumps[1][&Major], 3);
+        assert!(max_jumps_for_range(&"0..2".parse()?).is_err());
+        assert!(max_jumps_for_range(&"1..2:0.5".parse()?).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sweep_point_subdirectory() -> Result<()> {
+        let mut point = SweepPoint {
+            max_jumps: max_jumps_for_range(&"3".parse()?)?.remove(0),
+            min_btn: 8.5,
+            min_route_btn: 10.0,
+        };
+        assert_eq!(point.subdirectory(), PathBuf::from("3/8.5/10"));
+        // Steps like 0.1 give clean labels.
+        point.min_btn = "0..1:0.1".parse::<SweepRange>()?.values()[3];
+        assert_eq!(point.subdirectory(), PathBuf::from("3/0.3/10"));
+        point.max_jumps.insert(Minor, 2);
+        assert_eq!(point.max_jump_label(), "2-3-3-3-3");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sweep_run() -> Result<()> {
+        let galaxy = Galaxy::builder()
+            .data_directory(&PathBuf::from(TEST_DATA_DIR))
+            .offline(true)
+            .sectors(["Proving Ground", "Mockingbird"])
+            .build()?;
+        let sweep = Sweep {
+            max_jumps: max_jumps_for_range(&"2..3".parse()?)?,
+            min_btns: vec![8.0],
+            min_route_btns: "8..12:2".parse::<SweepRange>()?.values(),
+        };
+        let mut visited = Vec::new();
+        let results = sweep.run(&TradeModel::new(), galaxy.clone(), |point, trade_map| {
+            visited.push(point.subdirectory());
+            assert_eq!(trade_map.galaxy().worlds().count(), 31);
+            Ok(())
+        })?;
+        assert_eq!(
+            visited,
+            vec![
+                PathBuf::from("2/8/8"),
+                PathBuf::from("2/8/10"),
+                PathBuf::from("2/8/12"),
+                PathBuf::from("3/8/8"),
+                PathBuf::from("3/8/10"),
+                PathBuf::from("3/8/12"),
+            ]
+        );
+        assert_eq!(results.len(), 6);
+
+        // Each point matches computing it from scratch.
+        for (point, route_counts) in results.iter() {
+            let model = TradeModel::new()
+                .max_jump_all(point.max_jumps[&Minor])
+                .min_btn(point.min_btn)
+                .min_route_btn(point.min_route_btn);
+            let trade_map = model.compute(galaxy.clone())?;
+            assert_eq!(&trade_map.route_counts(), route_counts);
+        }
+        // Raising the minimum route BTN can only lose routes.
+        let total = |route_counts: &RouteCounter| route_counts.values().sum::<u64>();
+        assert!(total(&results[0].1) > 0);
+        assert!(total(&results[0].1) >= total(&results[1].1));
+        assert!(total(&results[1].1) >= total(&results[2].1));
+
+        let temp_dir = tempdir()?;
+        let csv_path = temp_dir.path().join("sweep.csv");
+        write_summary_csv(&csv_path, &results)?;
+        let csv = read_to_string(&csv_path)?;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(
+            lines[0],
+            "max_jump,min_btn,min_route_btn,major,main,intermediate,feeder,minor"
+        );
+        assert!(lines[1].starts_with("2,8,8,"));
+        assert!(lines[6].starts_with("3,8,12,"));
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+}
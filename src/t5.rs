@@ -0,0 +1,220 @@
+use anyhow::{anyhow, bail, Result};
+use std::str::FromStr;
+
+use crate::rules::{AllegianceGroups, Ruleset, TradeRules};
+use crate::scenario::Scenario;
+use crate::World;
+
+/// eHex digits, which skip I and O so they can't be mistaken for 1 and 0.
+const EHEX_DIGITS: &str = "0123456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+
+fn ehex_value(ch: char) -> Option<u32> {
+    EHEX_DIGITS
+        .find(ch.to_ascii_uppercase())
+        .map(|index| index as u32)
+}
+
+/// A Traveller5 economic extension, like "D7E+5": Resources, Labor, and
+/// Infrastructure as eHex digits, then Efficiency from -5 to +5.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EconomicExtension {
+    pub resources: u32,
+    pub labor: u32,
+    pub infrastructure: u32,
+    pub efficiency: i32,
+}
+
+impl FromStr for EconomicExtension {
+    type Err = anyhow::Error;
+
+    fn from_str(st: &str) -> Result<EconomicExtension> {
+        let st = st.trim();
+        if !st.is_ascii() || st.len() < 5 {
+            bail!("Bad economic extension '{}'", st);
+        }
+        let digit = |index: usize| {
+            st[index..]
+                .chars()
+                .next()
+                .and_then(ehex_value)
+                .ok_or_else(|| anyhow!("Bad economic extension '{}'", st))
+        };
+        let resources = digit(0)?;
+        let labor = digit(1)?;
+        let infrastructure = digit(2)?;
+        let efficiency: i32 = st[3..]
+            .parse()
+            .map_err(|_| anyhow!("Bad efficiency in economic extension '{}'", st))?;
+        if !(-5..=5).contains(&efficiency) {
+            bail!("Efficiency out of range in economic extension '{}'", st);
+        }
+        Ok(EconomicExtension {
+            resources,
+            labor,
+            infrastructure,
+            efficiency,
+        })
+    }
+}
+
+impl EconomicExtension {
+    /// T5 Resource Units: Resources times Labor times Infrastructure times
+    /// Efficiency, with zeros counted as 1.
+    ///
+    /// T5 makes RU negative when Efficiency is, which doesn't work as a
+    /// size, so negative Efficiency divides instead: -1 halves RU, -2
+    /// divides it by 3, and so on.
+    pub fn resource_units(&self) -> f64 {
+        let ru = (self.resources.max(1) * self.labor.max(1) * self.infrastructure.max(1)) as f64;
+        if self.efficiency < 0 {
+            ru / (1 - self.efficiency) as f64
+        } else {
+            ru * self.efficiency.max(1) as f64
+        }
+    }
+}
+
+/// Trade rules that get world trade numbers from the Traveller5 economic
+/// extension and Importance rather than from the UWP, and use a GURPS
+/// ruleset for everything else (pair and distance modifiers, BTN cap, and
+/// credits), so routes are comparable with that ruleset's.
+///
+/// WTN is a quarter point per doubling of Resource Units, plus a quarter
+/// point per point of Importance, plus 2, rounded down to a half point.
+/// That's close to the Far Trader WTN for most worlds.  Worlds without a
+/// usable economic extension get the base ruleset's WTN.
+#[derive(Clone, Debug, PartialEq)]
+pub struct T5Rules {
+    name: String,
+    pub base: Ruleset,
+}
+
+impl Default for T5Rules {
+    fn default() -> T5Rules {
+        T5Rules::new(Ruleset::far_trader())
+    }
+}
+
+impl T5Rules {
+    pub fn new(base: Ruleset) -> T5Rules {
+        T5Rules {
+            name: format!("t5 ({})", base.name),
+            base,
+        }
+    }
+
+    /// Return the WTN from world's economic extension and Importance, or
+    /// None if it doesn't have a usable economic extension.
+    pub fn economic_wtn(&self, world: &World) -> Option<f64> {
+        let economic: EconomicExtension = world.economic.parse().ok()?;
+        let wtn = (economic.resource_units().log2() + world.importance as f64) / 4.0 + 2.0;
+        Some((wtn * 2.0).floor() / 2.0)
+    }
+}
+
+impl TradeRules for T5Rules {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn wtn(&self, world: &World) -> f64 {
+        self.economic_wtn(world)
+            .unwrap_or_else(|| self.base.wtn(world))
+    }
+
+    fn pair_modifier(&self, world1: &World, world2: &World) -> f64 {
+        self.base.pair_modifier(world1, world2)
+    }
+
+    fn max_pair_bonus(&self) -> f64 {
+        self.base.max_pair_bonus()
+    }
+
+    fn max_pair_penalty(&self) -> f64 {
+        self.base.max_pair_penalty()
+    }
+
+    fn passenger_modifier(&self, world1: &World, world2: &World) -> f64 {
+        self.base.passenger_modifier(world1, world2)
+    }
+
+    fn distance_modifier(&self, distance: u32) -> f64 {
+        self.base.distance_modifier(distance)
+    }
+
+    fn btn_cap(&self, wtn1: f64, wtn2: f64) -> f64 {
+        self.base.btn_cap(wtn1, wtn2)
+    }
+
+    fn min_btn(&self) -> f64 {
+        self.base.min_btn()
+    }
+
+    fn dbtn_to_credits(&self) -> &[u64] {
+        self.base.dbtn_to_credits()
+    }
+
+    fn allegiance_groups(&self) -> &AllegianceGroups {
+        self.base.allegiance_groups()
+    }
+
+    fn scenario(&self) -> &Scenario {
+        self.base.scenario()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_economic_extension() -> Result<()> {
+        assert_eq!(
+            "D7E+5".parse::<EconomicExtension>()?,
+            EconomicExtension {
+                resources: 13,
+                labor: 7,
+                infrastructure: 14,
+                efficiency: 5,
+            }
+        );
+        assert_eq!(
+            "200-5".parse::<EconomicExtension>()?,
+            EconomicExtension {
+                resources: 2,
+                labor: 0,
+                infrastructure: 0,
+                efficiency: -5,
+            }
+        );
+        // eHex skips I
+        assert_eq!("J9H+0".parse::<EconomicExtension>()?.resources, 18);
+        assert_eq!("846+0".parse::<EconomicExtension>()?.efficiency, 0);
+        assert!("".parse::<EconomicExtension>().is_err());
+        assert!("D7E".parse::<EconomicExtension>().is_err());
+        assert!("D7E+9".parse::<EconomicExtension>().is_err());
+        assert!("D7I+1".parse::<EconomicExtension>().is_err());
+        assert!("???-?".parse::<EconomicExtension>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_units() -> Result<()> {
+        assert_eq!(
+            "D7E+5".parse::<EconomicExtension>()?.resource_units(),
+            6370.0
+        );
+        assert_eq!(
+            "846+0".parse::<EconomicExtension>()?.resource_units(),
+            192.0
+        );
+        assert_eq!(
+            "200-5".parse::<EconomicExtension>()?.resource_units(),
+            2.0 / 6.0
+        );
+        assert_eq!("843-1".parse::<EconomicExtension>()?.resource_units(), 48.0);
+
+        Ok(())
+    }
+}
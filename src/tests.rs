@@ -1,20 +1,30 @@
 use anyhow::Result;
-use ndarray::Array2;
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
-use std::fs::{create_dir_all, read_to_string};
+use std::fs::{read, read_to_string, write};
 use std::path::PathBuf;
+use std::time::Duration;
 use tempfile::tempdir;
 
-use crate::apsp::{Algorithm, INFINITY};
+use crate::apsp::{Algorithm, ShortestPaths, INFINITY, NO_PRED_NODE};
+use crate::custom::CustomSector;
+use crate::error::GalaxyError;
+use crate::error::ParseError;
+use crate::lint::lint_sectors;
 use crate::pdf::generate_pdfs;
+use crate::rules::{AllegianceGroups, Ruleset, TradeRules};
+use crate::scenario::{Border, Scenario, NO_SCENARIO};
+use crate::source::{stand_in, FsSource, HttpSource, MemorySource, SectorSource};
+use crate::t5::T5Rules;
+use crate::universe::Universe;
 use crate::{
-    distance_modifier_table_ft, distance_modifier_table_iw, download_sector_data,
-    find_max_allowed_jump, generate_text_btns, parse_file_of_sectors, parse_header_and_separator,
-    parse_max_jumps, populate_navigable_distances, populate_trade_routes, same_allegiance, Route,
-    MAX_DISTANCE_PENALTY, MIN_BTN, MIN_ROUTE_BTN,
+    check_num_worlds, default_data_dir, download_sector_data, download_universe,
+    find_custom_sectors, find_max_allowed_jump, find_missing_sector_data, generate_text_btns,
+    parse_file_of_sectors, parse_header_and_separator, populate_navigable_distances,
+    populate_trade_routes, resolve_sector_names, same_allegiance, select_sectors,
+    unknown_sector_names, DownloadOptions, Route, MAX_DISTANCE_PENALTY, MIN_BTN, MIN_ROUTE_BTN,
 };
-use crate::{Args, Coords, Sector, World};
+use crate::{Coords, Galaxy, Sector, TradeMap, TradeModel, World};
 use Route::{Feeder, Intermediate, Main, Major, Minor};
 
 #[cfg(test)]
@@ -25,14 +35,24 @@ mod tests {
     use std::fs::read_dir;
     use std::io;
 
-    // Reuse a test directory and downloaded files to avoid overloading travellermap.com
-    const TEST_DATA_DIR: &'static str = "/var/tmp/traderust_tests";
+    // Checked-in sector data, so the tests never need travellermap.com.
+    // "Spinward Marches", "Deneb", "Gvurrdon", "Core", "Reft", "Ley",
+    // "Empty Quarter", "Fornast", and "Yiklerzdanzh" are small excerpts of
+    // the real sectors.  "Proving Ground" and "Mockingbird" (trailing of it)
+    // are synthetic sectors built to exercise xboat routes, jump gaps,
+    // islands, and odd data, and "Homebrew" is a synthetic custom sector.
+    const TEST_DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test_data");
 
     const ALG: Algorithm = Algorithm::Dijkstra;
 
+    lazy_static! {
+        static ref FT_RULES: Ruleset = Ruleset::far_trader();
+        static ref IW_RULES: Ruleset = Ruleset::interstellar_wars();
+    }
+
     macro_rules! htw {
         ($sector:expr, $hex:expr, $ctw:expr) => {
-            $sector.hex_to_world(format!("{:04}", $hex), &$ctw).unwrap()
+            $sector.hex_to_world($hex.to_string(), &$ctw).unwrap()
         };
     }
 
@@ -51,28 +71,13 @@ mod tests {
     #[fixture]
     #[once]
     fn data_dir() -> PathBuf {
-        let data_dir = PathBuf::from(TEST_DATA_DIR);
-        create_dir_all(&data_dir).unwrap();
-        data_dir
+        PathBuf::from(TEST_DATA_DIR)
     }
 
     #[fixture]
     #[once]
-    fn download(data_dir: &PathBuf) -> Result<Vec<String>> {
-        let sector_names = vec![
-            "Deneb".to_string(),
-            "Gvurrdon".to_string(),
-            "Spinward Marches".to_string(),
-            "Core".to_string(),
-            "Reft".to_string(),
-            "Ley".to_string(),
-            "Empty Quarter".to_string(),
-            "Fornast".to_string(),
-            "Yiklerzdanzh".to_string(),
-        ];
-        download_sector_data(&data_dir, &sector_names)?;
-
-        Ok(sector_names)
+    fn source(data_dir: &PathBuf) -> FsSource {
+        FsSource::new(data_dir, None)
     }
 
     #[rstest]
@@ -133,41 +138,532 @@ mod tests {
         assert_eq!(coords2.partial_cmp(&coords1), Some(Ordering::Greater));
     }
 
-    #[rstest]
-    fn test_download_sector_data(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        let mut expected_filenames = Vec::new();
-        if let Ok(sector_names) = download {
-            for sector_name in sector_names {
-                expected_filenames.push(sector_name.to_owned() + ".sec");
-                expected_filenames.push(sector_name.to_owned() + ".xml");
-            }
-            expected_filenames.sort();
+    /// Options for downloads that retry without waiting around.
+    fn download_options(refresh: bool) -> DownloadOptions {
+        DownloadOptions {
+            refresh,
+            backoff: Duration::from_millis(1),
+            ..DownloadOptions::default()
         }
+    }
+
+    /// Metadata that passes the download checks, but isn't what the server
+    /// has, so we can tell whether it was downloaded again.
+    const STALE_METADATA: &str = "<Sector><Name>Stale</Name></Sector>";
+
+    #[rstest]
+    fn test_download_sector_data(source: &FsSource, data_dir: &PathBuf) -> Result<()> {
+        let base_url = stand_in::serve(source.clone());
+        let http_source = HttpSource::new(&base_url, None)?;
+        let temp_dir = tempdir()?;
+        let cache_dir: PathBuf = temp_dir.path().to_path_buf();
+        let sector_names = vec![
+            "Mockingbird".to_string(),
+            "Proving Ground".to_string(),
+            "Spinward Marches".to_string(),
+        ];
+        // Files that are already present must not be downloaded again.
+        write(cache_dir.join("Mockingbird.xml"), STALE_METADATA)?;
+
+        download_sector_data(
+            &http_source,
+            &cache_dir,
+            None,
+            &sector_names,
+            &HashMap::new(),
+            &download_options(false),
+        )?;
 
-        let found_filename_results: Vec<Result<OsString, io::Error>> = read_dir(&data_dir)?
+        let found_filename_results: Vec<Result<OsString, io::Error>> = read_dir(&cache_dir)?
             .map(|res| res.map(|e| e.file_name()))
             .collect();
-        let mut found_os_filenames: Vec<OsString> = Vec::new();
-        for res in found_filename_results {
-            if let Ok(filename) = res {
-                found_os_filenames.push(filename);
-            }
-        }
-
         let mut found_filenames: Vec<String> = Vec::new();
-        for osstr in found_os_filenames {
-            let opt = osstr.to_str();
-            if let Some(st) = opt {
+        for osstr in found_filename_results.into_iter().flatten() {
+            if let Some(st) = osstr.to_str() {
                 found_filenames.push(st.to_string());
             }
         }
         found_filenames.sort();
+        let mut expected_filenames = Vec::new();
+        for sector_name in &sector_names {
+            expected_filenames.push(sector_name.to_owned() + ".sec");
+            expected_filenames.push(sector_name.to_owned() + ".xml");
+        }
+        assert_eq!(found_filenames, expected_filenames);
+
+        assert_eq!(
+            read_to_string(cache_dir.join("Mockingbird.xml"))?,
+            STALE_METADATA
+        );
+        for filename in [
+            "Mockingbird.sec",
+            "Proving Ground.xml",
+            "Spinward Marches.sec",
+        ] {
+            assert_eq!(
+                read_to_string(cache_dir.join(filename))?,
+                read_to_string(data_dir.join(filename))?
+            );
+        }
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_download_sector_data_refresh(source: &FsSource, data_dir: &PathBuf) -> Result<()> {
+        let base_url = stand_in::serve(source.clone());
+        let http_source = HttpSource::new(&base_url, None)?;
+        let temp_dir = tempdir()?;
+        let cache_dir: PathBuf = temp_dir.path().to_path_buf();
+        let sector_names = vec!["Mockingbird".to_string()];
+        // An error page saved by an older version is always replaced.
+        write(
+            cache_dir.join("Mockingbird.sec"),
+            "<!DOCTYPE html>\n<html><body>Not Found</body></html>",
+        )?;
+        write(cache_dir.join("Mockingbird.xml"), STALE_METADATA)?;
+
+        download_sector_data(
+            &http_source,
+            &cache_dir,
+            None,
+            &sector_names,
+            &HashMap::new(),
+            &download_options(false),
+        )?;
+        assert_eq!(
+            read_to_string(cache_dir.join("Mockingbird.sec"))?,
+            read_to_string(data_dir.join("Mockingbird.sec"))?
+        );
+        assert_eq!(
+            read_to_string(cache_dir.join("Mockingbird.xml"))?,
+            STALE_METADATA
+        );
+
+        download_sector_data(
+            &http_source,
+            &cache_dir,
+            None,
+            &sector_names,
+            &HashMap::new(),
+            &download_options(true),
+        )?;
+        assert_eq!(
+            read_to_string(cache_dir.join("Mockingbird.xml"))?,
+            read_to_string(data_dir.join("Mockingbird.xml"))?
+        );
+        // Nothing but the data files is left behind.
+        assert_eq!(read_dir(&cache_dir)?.count(), 2);
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_download_sector_data_corrupt_cache(
+        source: &FsSource,
+        data_dir: &PathBuf,
+    ) -> Result<()> {
+        let base_url = stand_in::serve(source.clone());
+        let http_source = HttpSource::new(&base_url, None)?;
+        let temp_dir = tempdir()?;
+        let cache_dir: PathBuf = temp_dir.path().to_path_buf();
+        let sector_names = vec!["Mockingbird".to_string(), "Proving Ground".to_string()];
+        // Files that would fail the download checks, like an empty file or
+        // a plain text rate limit message, are replaced too.
+        write(cache_dir.join("Mockingbird.sec"), "")?;
+        write(cache_dir.join("Mockingbird.xml"), STALE_METADATA)?;
+        write(cache_dir.join("Proving Ground.sec"), "\n")?;
+        write(cache_dir.join("Proving Ground.xml"), "Too many requests")?;
+
+        download_sector_data(
+            &http_source,
+            &cache_dir,
+            None,
+            &sector_names,
+            &HashMap::new(),
+            &download_options(false),
+        )?;
+        for filename in [
+            "Mockingbird.sec",
+            "Proving Ground.sec",
+            "Proving Ground.xml",
+        ] {
+            assert_eq!(
+                read_to_string(cache_dir.join(filename))?,
+                read_to_string(data_dir.join(filename))?
+            );
+        }
+        assert_eq!(
+            read_to_string(cache_dir.join("Mockingbird.xml"))?,
+            STALE_METADATA
+        );
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_download_sector_data_error_page(source: &FsSource) -> Result<()> {
+        let mut memory_source = MemorySource::new();
+        memory_source.insert(
+            "Mockingbird",
+            source.sector_data("Mockingbird")?,
+            "<html><body>Rate limited</body></html>".to_string(),
+        );
+        let base_url = stand_in::serve(memory_source);
+        let http_source = HttpSource::new(&base_url, None)?;
+        let temp_dir = tempdir()?;
+        let cache_dir: PathBuf = temp_dir.path().to_path_buf();
+        let sector_names = vec!["Mockingbird".to_string()];
+
+        assert!(download_sector_data(
+            &http_source,
+            &cache_dir,
+            None,
+            &sector_names,
+            &HashMap::new(),
+            &download_options(false),
+        )
+        .is_err());
+        assert!(!cache_dir.join("Mockingbird.xml").exists());
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_download_sector_data_retries(source: &FsSource, data_dir: &PathBuf) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let cache_dir: PathBuf = temp_dir.path().to_path_buf();
+        let sector_names = vec!["Mockingbird".to_string(), "Proving Ground".to_string()];
+
+        // Three retries each, so however the failures land among the
+        // parallel downloads, no one file runs out.
+        let base_url = stand_in::serve_flaky(source.clone(), 3);
+        let http_source = HttpSource::new(&base_url, None)?;
+        download_sector_data(
+            &http_source,
+            &cache_dir,
+            None,
+            &sector_names,
+            &HashMap::new(),
+            &download_options(false),
+        )?;
+        for filename in [
+            "Mockingbird.sec",
+            "Mockingbird.xml",
+            "Proving Ground.sec",
+            "Proving Ground.xml",
+        ] {
+            assert_eq!(
+                read_to_string(cache_dir.join(filename))?,
+                read_to_string(data_dir.join(filename))?
+            );
+        }
+
+        // But not with no retries.
+        let base_url = stand_in::serve_flaky(source.clone(), 1);
+        let http_source = HttpSource::new(&base_url, None)?;
+        let options = DownloadOptions {
+            retries: 0,
+            jobs: 1,
+            ..download_options(true)
+        };
+        let err = download_sector_data(
+            &http_source,
+            &cache_dir,
+            None,
+            &sector_names,
+            &HashMap::new(),
+            &options,
+        )
+        .unwrap_err();
+        // The other sector still downloads, and the error says which failed.
+        assert!(err.to_string().contains("Mockingbird: "));
+        assert!(!err.to_string().contains("Proving Ground: "));
 
-        assert_eq!(expected_filenames, found_filenames);
+        temp_dir.close()?;
 
         Ok(())
     }
 
+    #[rstest]
+    fn test_download_sector_data_missing_sector(source: &FsSource) -> Result<()> {
+        let base_url = stand_in::serve(source.clone());
+        let http_source = HttpSource::new(&base_url, None)?;
+        let temp_dir = tempdir()?;
+        let cache_dir: PathBuf = temp_dir.path().to_path_buf();
+        let sector_names = vec!["Nowhere".to_string()];
+
+        assert!(download_sector_data(
+            &http_source,
+            &cache_dir,
+            None,
+            &sector_names,
+            &HashMap::new(),
+            &download_options(false)
+        )
+        .is_err());
+        assert!(!cache_dir.join("Nowhere.sec").exists());
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_find_missing_sector_data() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let data_dir: PathBuf = temp_dir.path().to_path_buf();
+        write(data_dir.join("Deneb.sec"), "")?;
+        write(data_dir.join("Deneb.xml"), "")?;
+        write(data_dir.join("Reft.xml"), "")?;
+        let sector_names = vec!["Core".to_string(), "Deneb".to_string(), "Reft".to_string()];
+
+        let missing = find_missing_sector_data(&data_dir, None, &sector_names, &HashMap::new());
+        assert_eq!(
+            missing,
+            vec![
+                data_dir.join("Core.sec"),
+                data_dir.join("Core.xml"),
+                data_dir.join("Reft.sec"),
+            ]
+        );
+
+        // Custom sectors don't need XML metadata.
+        let mut custom_sectors = HashMap::new();
+        custom_sectors.insert("Core".to_string(), "X: 0; Y: 0".parse()?);
+        let missing = find_missing_sector_data(&data_dir, None, &sector_names, &custom_sectors);
+        assert_eq!(
+            missing,
+            vec![data_dir.join("Core.sec"), data_dir.join("Reft.sec")]
+        );
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_download_sector_data_custom_sector(source: &FsSource) -> Result<()> {
+        let base_url = stand_in::serve(source.clone());
+        let http_source = HttpSource::new(&base_url, None)?;
+        let temp_dir = tempdir()?;
+        let cache_dir: PathBuf = temp_dir.path().to_path_buf();
+        let sector_names = vec!["Homebrew".to_string()];
+        let mut custom_sectors = HashMap::new();
+        custom_sectors.insert("Homebrew".to_string(), "X: -1; Y: 0".parse()?);
+
+        // Never downloaded, so its data has to be there already.
+        assert!(download_sector_data(
+            &http_source,
+            &cache_dir,
+            None,
+            &sector_names,
+            &custom_sectors,
+            &download_options(false)
+        )
+        .is_err());
+        write(cache_dir.join("Homebrew.sec"), "")?;
+        download_sector_data(
+            &http_source,
+            &cache_dir,
+            None,
+            &sector_names,
+            &custom_sectors,
+            &download_options(false),
+        )?;
+        assert!(!cache_dir.join("Homebrew.xml").exists());
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_download_sector_data_milieu(source: &FsSource) -> Result<()> {
+        // In M0, Threshold hasn't been named yet.
+        let sector_name = "Proving Ground";
+        let data = source.sector_data(sector_name)?;
+        let metadata = source.sector_metadata(sector_name)?;
+        let base_url = stand_in::serve_milieux(move |milieu_opt| {
+            let mut memory_source = MemorySource::new();
+            let mut data = data.to_string();
+            if milieu_opt == Some("M0") {
+                data = data.replace("Threshold", "Sill     ");
+            }
+            memory_source.insert(sector_name, data, metadata.to_string());
+            memory_source
+        });
+        let temp_dir = tempdir()?;
+        let cache_dir: PathBuf = temp_dir.path().to_path_buf();
+        let sector_names = vec![sector_name.to_string()];
+
+        for milieu_opt in [None, Some("M0")] {
+            let http_source = HttpSource::new(&base_url, milieu_opt)?;
+            assert_eq!(
+                find_missing_sector_data(&cache_dir, milieu_opt, &sector_names, &HashMap::new())
+                    .len(),
+                2
+            );
+            download_sector_data(
+                &http_source,
+                &cache_dir,
+                milieu_opt,
+                &sector_names,
+                &HashMap::new(),
+                &download_options(false),
+            )?;
+            assert!(find_missing_sector_data(
+                &cache_dir,
+                milieu_opt,
+                &sector_names,
+                &HashMap::new()
+            )
+            .is_empty());
+        }
+        for filename in [
+            "Proving Ground.sec",
+            "Proving Ground.xml",
+            "Proving Ground.M0.sec",
+            "Proving Ground.M0.xml",
+        ] {
+            assert!(cache_dir.join(filename).exists());
+        }
+
+        for (milieu_opt, name) in [(None, "Threshold"), (Some("M0"), "Sill")] {
+            let cache_source = FsSource::new(&cache_dir, milieu_opt);
+            let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+            let sector = Sector::new(
+                &cache_source,
+                sector_name.to_string(),
+                None,
+                false,
+                &mut coords_to_world,
+            )?;
+            assert_eq!(sector.location, (0, 0));
+            assert_eq!(htw!(sector, "0101", coords_to_world).name, name);
+        }
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_download_universe(source: &FsSource) -> Result<()> {
+        let base_url = stand_in::serve(source.clone());
+        let http_source = HttpSource::new(&base_url, None)?;
+        let temp_dir = tempdir()?;
+        let cache_dir: PathBuf = temp_dir.path().to_path_buf();
+
+        download_universe(&http_source, &cache_dir, None, &download_options(false))?;
+        let cache_source = FsSource::new(&cache_dir, None);
+        assert_eq!(cache_source.universe()?, source.universe()?);
+
+        // An existing file is not downloaded again.
+        write(cache_source.universe_path(), "{\"Sectors\":[]}")?;
+        download_universe(&http_source, &cache_dir, None, &download_options(false))?;
+        assert_eq!(cache_source.universe()?, "{\"Sectors\":[]}");
+
+        // Unless we ask for it.
+        download_universe(&http_source, &cache_dir, None, &download_options(true))?;
+        assert_eq!(cache_source.universe()?, source.universe()?);
+
+        // Or it isn't JSON.
+        write(cache_source.universe_path(), "Rate limited")?;
+        download_universe(&http_source, &cache_dir, None, &download_options(false))?;
+        assert_eq!(cache_source.universe()?, source.universe()?);
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_select_sectors(source: &FsSource) -> Result<()> {
+        let universe = Universe::parse(&source.universe()?)?;
+        let regions = vec!["-4..-4,-1..0".parse()?];
+        let arounds = vec!["Mockingbird:0".parse()?, "Proving Ground:0".parse()?];
+        assert_eq!(
+            select_sectors(&universe, &regions, &arounds)?,
+            vec![
+                "Spinward Marches",
+                "Trojan Reach",
+                "Mockingbird",
+                "Proving Ground"
+            ]
+        );
+        assert!(select_sectors(&universe, &[], &[])?.is_empty());
+        let arounds = vec!["Nowhere:1".parse()?];
+        assert!(select_sectors(&universe, &[], &arounds).is_err());
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_unknown_sector_names(data_dir: &PathBuf) {
+        let sector_names: Vec<String> = [
+            "Homebrew",
+            "Invented",
+            "Mockingbird",
+            "Spin",
+            "Spinward Marches",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        let invented: CustomSector = "Name: Invented; X: 9; Y: 9".parse().unwrap();
+        assert_eq!(
+            unknown_sector_names(data_dir, None, &sector_names, &[invented]),
+            vec!["Spin"]
+        );
+        assert_eq!(
+            unknown_sector_names(data_dir, Some("M0"), &sector_names, &[]),
+            sector_names
+        );
+    }
+
+    #[rstest]
+    fn test_resolve_sector_names(source: &FsSource) -> Result<()> {
+        let universe = Universe::parse(&source.universe()?)?;
+        let sector_names = vec![
+            "Spin".to_string(),
+            "Campo de Pruebas".to_string(),
+            "mockingbird".to_string(),
+        ];
+        assert_eq!(
+            resolve_sector_names(&universe, &sector_names)?,
+            vec!["Spinward Marches", "Proving Ground", "Mockingbird"]
+        );
+        let sector_names = vec![
+            "Spin".to_string(),
+            "Deneeb".to_string(),
+            "Nowhere".to_string(),
+        ];
+        assert_eq!(
+            resolve_sector_names(&universe, &sector_names)
+                .unwrap_err()
+                .to_string(),
+            "Unknown sector 'Deneeb'; did you mean 'Deneb'?\n\
+             Unknown sector 'Nowhere'"
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_default_data_dir() {
+        let data_dir = default_data_dir();
+        assert!(data_dir.ends_with("traderust"));
+    }
+
     #[rstest]
     fn test_parse_header_and_separator() -> Result<()> {
         let header = concat!(
@@ -205,11 +701,10 @@ mod tests {
     }
 
     #[rstest]
-    fn test_sector_spin(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_sector_spin(source: &FsSource) -> Result<()> {
         let sector_name = "Spinward Marches".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         assert_eq!(sector.name, "Spinward Marches");
         assert_eq!(sector.names, vec!["Spinward Marches", "Tloql"]);
@@ -229,29 +724,28 @@ mod tests {
             *sector.allegiance_code_to_name.get("CsIm").unwrap(),
             "Client state, Third Imperium".to_string()
         );
-        assert_eq!(sector.hex_to_coords.len(), 439);
-        let zeycude_coords = sector.hex_to_coords.get("0101").unwrap();
-        let zeycude = coords_to_world.get(zeycude_coords).unwrap();
-        assert_eq!(zeycude.name, "Zeycude");
-        let hazel_coords = sector.hex_to_coords.get("3236").unwrap();
-        let hazel = coords_to_world.get(hazel_coords).unwrap();
-        assert_eq!(hazel.name, "Hazel");
+        assert_eq!(sector.hex_to_coords.len(), 6);
+        let candory_coords = sector.hex_to_coords.get("0336").unwrap();
+        let candory = coords_to_world.get(candory_coords).unwrap();
+        assert_eq!(candory.name, "Candory");
+        let mora_coords = sector.hex_to_coords.get("3124").unwrap();
+        let mora = coords_to_world.get(mora_coords).unwrap();
+        assert_eq!(mora.name, "Mora");
 
         Ok(())
     }
 
     #[rstest]
-    fn test_sector_dene(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_sector_dene(source: &FsSource) -> Result<()> {
         let sector_name = "Deneb".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         assert_eq!(sector.name, "Deneb");
         assert_eq!(sector.names, vec!["Deneb", "Nieklsdia"]);
         assert_eq!(sector.abbreviation, "Dene");
         assert_eq!(sector.location, (-3, -1));
-        assert_eq!(sector.subsector_letter_to_name.len(), 16);
+        assert_eq!(sector.subsector_letter_to_name.len(), 2);
         assert_eq!(
             *sector.subsector_letter_to_name.get(&'A').unwrap(),
             "Pretoria".to_string()
@@ -260,12 +754,12 @@ mod tests {
             *sector.subsector_letter_to_name.get(&'P').unwrap(),
             "Vast Heavens".to_string()
         );
-        assert_eq!(sector.allegiance_code_to_name.len(), 6);
+        assert_eq!(sector.allegiance_code_to_name.len(), 2);
         assert_eq!(
             *sector.allegiance_code_to_name.get("CsIm").unwrap(),
             "Client state, Third Imperium".to_string()
         );
-        assert_eq!(sector.hex_to_coords.len(), 386);
+        assert_eq!(sector.hex_to_coords.len(), 2);
         let new_ramma_coords = sector.hex_to_coords.get("0108").unwrap();
         let new_ramma = coords_to_world.get(new_ramma_coords).unwrap();
         assert_eq!(new_ramma.name, "New Ramma");
@@ -277,17 +771,16 @@ mod tests {
     }
 
     #[rstest]
-    fn test_sector_gvur(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_sector_gvur(source: &FsSource) -> Result<()> {
         let sector_name = "Gvurrdon".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         assert_eq!(sector.name, "Gvurrdon");
         assert_eq!(sector.names, vec!["Gvurrdon", r"Briakqra'"]);
         assert_eq!(sector.abbreviation, "Gvur");
         assert_eq!(sector.location, (-4, -2));
-        assert_eq!(sector.subsector_letter_to_name.len(), 16);
+        assert_eq!(sector.subsector_letter_to_name.len(), 2);
         assert_eq!(
             *sector.subsector_letter_to_name.get(&'A').unwrap(),
             "Ongvos".to_string()
@@ -296,12 +789,12 @@ mod tests {
             *sector.subsector_letter_to_name.get(&'P').unwrap(),
             "Firgr".to_string()
         );
-        assert_eq!(sector.allegiance_code_to_name.len(), 16);
+        assert_eq!(sector.allegiance_code_to_name.len(), 2);
         assert_eq!(
             *sector.allegiance_code_to_name.get("CsIm").unwrap(),
             "Client state, Third Imperium".to_string()
         );
-        assert_eq!(sector.hex_to_coords.len(), 358);
+        assert_eq!(sector.hex_to_coords.len(), 2);
         let enjtodl_coords = sector.hex_to_coords.get("0104").unwrap();
         let enjtodl = coords_to_world.get(enjtodl_coords).unwrap();
         assert_eq!(enjtodl.name, "Enjtodl");
@@ -313,17 +806,16 @@ mod tests {
     }
 
     #[rstest]
-    fn test_sector_core(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_sector_core(source: &FsSource) -> Result<()> {
         let sector_name = "Core".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         assert_eq!(sector.name, "Core");
         assert_eq!(sector.names, vec!["Core", "Ukan"]);
         assert_eq!(sector.abbreviation, "Core");
         assert_eq!(sector.location, (0, 0));
-        assert_eq!(sector.subsector_letter_to_name.len(), 16);
+        assert_eq!(sector.subsector_letter_to_name.len(), 2);
         assert_eq!(
             *sector.subsector_letter_to_name.get(&'A').unwrap(),
             "Apge".to_string()
@@ -332,12 +824,12 @@ mod tests {
             *sector.subsector_letter_to_name.get(&'P').unwrap(),
             "Saregon".to_string()
         );
-        assert_eq!(sector.allegiance_code_to_name.len(), 2);
+        assert_eq!(sector.allegiance_code_to_name.len(), 1);
         assert_eq!(
             *sector.allegiance_code_to_name.get("ImSy").unwrap(),
             "Third Imperium, Sylean Worlds".to_string()
         );
-        assert_eq!(sector.hex_to_coords.len(), 546);
+        assert_eq!(sector.hex_to_coords.len(), 3);
         let shana_ma_coords = sector.hex_to_coords.get("0104").unwrap();
         let shana_ma = coords_to_world.get(shana_ma_coords).unwrap();
         assert_eq!(shana_ma.name, "Shana Ma");
@@ -349,20 +841,19 @@ mod tests {
     }
 
     #[rstest]
-    fn test_sector_reft(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_sector_reft(source: &FsSource) -> Result<()> {
         let sector_name = "Reft".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         assert_eq!(sector.name, "Reft");
         assert_eq!(
             sector.names,
-            vec!["Reft", r"Reft Sector", "Bransakral", "Sushinar"]
+            vec!["Reft", "Reft Sector", "Bransakral", "Sushinar"]
         );
         assert_eq!(sector.abbreviation, "Reft");
         assert_eq!(sector.location, (-3, 0));
-        assert_eq!(sector.subsector_letter_to_name.len(), 16);
+        assert_eq!(sector.subsector_letter_to_name.len(), 2);
         assert_eq!(
             *sector.subsector_letter_to_name.get(&'A').unwrap(),
             "Vestus".to_string()
@@ -371,12 +862,12 @@ mod tests {
             *sector.subsector_letter_to_name.get(&'P').unwrap(),
             "Moibin".to_string()
         );
-        assert_eq!(sector.allegiance_code_to_name.len(), 5);
+        assert_eq!(sector.allegiance_code_to_name.len(), 2);
         assert_eq!(
             *sector.allegiance_code_to_name.get("CsIm").unwrap(),
             "Client state, Third Imperium".to_string()
         );
-        assert_eq!(sector.hex_to_coords.len(), 130);
+        assert_eq!(sector.hex_to_coords.len(), 2);
         let grudovo_coords = sector.hex_to_coords.get("0111").unwrap();
         let grudovo = coords_to_world.get(grudovo_coords).unwrap();
         assert_eq!(grudovo.name, "Grudovo");
@@ -388,17 +879,16 @@ mod tests {
     }
 
     #[rstest]
-    fn test_sector_ley(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_sector_ley(source: &FsSource) -> Result<()> {
         let sector_name = "Ley".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         assert_eq!(sector.name, "Ley");
-        assert_eq!(sector.names, vec!["Ley", r"Ley Sector", "Makhuniim"]);
+        assert_eq!(sector.names, vec!["Ley", "Ley Sector", "Makhuniim"]);
         assert_eq!(sector.abbreviation, "Ley");
         assert_eq!(sector.location, (2, 0));
-        assert_eq!(sector.subsector_letter_to_name.len(), 16);
+        assert_eq!(sector.subsector_letter_to_name.len(), 2);
         assert_eq!(
             *sector.subsector_letter_to_name.get(&'A').unwrap(),
             "Ikhnaton".to_string()
@@ -407,12 +897,12 @@ mod tests {
             *sector.subsector_letter_to_name.get(&'P').unwrap(),
             "Outworld".to_string()
         );
-        assert_eq!(sector.allegiance_code_to_name.len(), 5);
+        assert_eq!(sector.allegiance_code_to_name.len(), 2);
         assert_eq!(
             *sector.allegiance_code_to_name.get("CsIm").unwrap(),
             "Client state, Third Imperium".to_string()
         );
-        assert_eq!(sector.hex_to_coords.len(), 387);
+        assert_eq!(sector.hex_to_coords.len(), 4);
         let amikell_coords = sector.hex_to_coords.get("0101").unwrap();
         let amikell = coords_to_world.get(amikell_coords).unwrap();
         assert_eq!(amikell.name, "Amikell");
@@ -423,6 +913,167 @@ mod tests {
         Ok(())
     }
 
+    #[rstest]
+    fn test_sector_prov(source: &FsSource) -> Result<()> {
+        let sector_name = "Proving Ground".to_string();
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
+
+        assert_eq!(sector.name, "Proving Ground");
+        assert_eq!(sector.names, vec!["Proving Ground", "Campo de Pruebas"]);
+        assert_eq!(sector.abbreviation, "Prov");
+        assert_eq!(sector.location, (0, 0));
+        assert_eq!(sector.subsector_letter_to_name.len(), 16);
+        assert_eq!(
+            *sector.subsector_letter_to_name.get(&'A').unwrap(),
+            "Ashfall".to_string()
+        );
+        assert_eq!(
+            *sector.subsector_letter_to_name.get(&'P').unwrap(),
+            "Plumb Line".to_string()
+        );
+        assert_eq!(sector.allegiance_code_to_name.len(), 3);
+        assert_eq!(
+            *sector.allegiance_code_to_name.get("ImDd").unwrap(),
+            "Third Imperium, Domain of Deneb".to_string()
+        );
+        assert_eq!(sector.hex_to_coords.len(), 20);
+        let threshold_coords = sector.hex_to_coords.get("0101").unwrap();
+        let threshold = coords_to_world.get(threshold_coords).unwrap();
+        assert_eq!(threshold.name, "Threshold");
+        let quill_coords = sector.hex_to_coords.get("3209").unwrap();
+        let quill = coords_to_world.get(quill_coords).unwrap();
+        assert_eq!(quill.name, "Quill");
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_sector_mock(source: &FsSource) -> Result<()> {
+        let sector_name = "Mockingbird".to_string();
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
+
+        assert_eq!(sector.name, "Mockingbird");
+        assert_eq!(sector.names, vec!["Mockingbird"]);
+        assert_eq!(sector.abbreviation, "Mock");
+        assert_eq!(sector.location, (1, 0));
+        assert_eq!(sector.subsector_letter_to_name.len(), 4);
+        assert_eq!(
+            *sector.subsector_letter_to_name.get(&'A').unwrap(),
+            "Quillon".to_string()
+        );
+        assert_eq!(
+            *sector.subsector_letter_to_name.get(&'P').unwrap(),
+            "Pommel".to_string()
+        );
+        assert_eq!(sector.subsector_letter_to_name.get(&'C'), None);
+        assert_eq!(sector.allegiance_code_to_name.len(), 3);
+        assert_eq!(
+            *sector.allegiance_code_to_name.get("ZhCo").unwrap(),
+            "Zhodani Consulate".to_string()
+        );
+        assert_eq!(sector.hex_to_coords.len(), 11);
+        let stillwater_coords = sector.hex_to_coords.get("0103").unwrap();
+        let stillwater = coords_to_world.get(stillwater_coords).unwrap();
+        assert_eq!(stillwater.name, "Stillwater");
+        let last_light_coords = sector.hex_to_coords.get("3240").unwrap();
+        let last_light = coords_to_world.get(last_light_coords).unwrap();
+        assert_eq!(last_light.name, "Last Light");
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_sector_custom(source: &FsSource, data_dir: &PathBuf) -> Result<()> {
+        let sector_names = vec!["Homebrew".to_string(), "Proving Ground".to_string()];
+        let custom_sectors = find_custom_sectors(data_dir, None, &sector_names, &[])?;
+        assert_eq!(custom_sectors.len(), 1);
+        let custom = custom_sectors.get("Homebrew").unwrap();
+
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
+        for sector_name in sector_names.iter() {
+            let custom_opt = custom_sectors.get(sector_name);
+            let sector = Sector::new(
+                source,
+                sector_name.to_string(),
+                custom_opt,
+                false,
+                &mut coords_to_world,
+            )?;
+            location_to_sector.insert(sector.location, sector);
+        }
+        let sector = location_to_sector.get(&custom.location).unwrap();
+        assert_eq!(sector.name, "Homebrew");
+        assert_eq!(sector.names, vec!["Homebrew", "Casa"]);
+        assert_eq!(sector.abbreviation, "Home");
+        assert_eq!(sector.location, (-1, 0));
+        assert_eq!(sector.subsector_letter_to_name.len(), 2);
+        assert_eq!(
+            *sector.subsector_letter_to_name.get(&'D').unwrap(),
+            "Pantry".to_string()
+        );
+        assert_eq!(
+            *sector.allegiance_code_to_name.get("HoFe").unwrap(),
+            "Homebrew Federation".to_string()
+        );
+        assert_eq!(sector.hex_to_coords.len(), 4);
+        // A multi-byte name doesn't shift the columns after it.
+        let solvberg = htw!(sector, "3110", coords_to_world);
+        assert_eq!(solvberg.name, "Sølvberg");
+        assert_eq!(solvberg.uwp, "E434410-7");
+        assert_eq!(solvberg.economic(), "731-2");
+        assert_eq!(solvberg.zone, 'A');
+        assert_eq!(solvberg.allegiance, "HoFe");
+        assert_eq!(solvberg.stars(), vec!["M2 V"]);
+
+        // Placed right next to Proving Ground.
+        let prov = location_to_sector.get(&(0, 0)).unwrap();
+        let hearth = htw!(sector, "3101", coords_to_world);
+        let kettle = htw!(sector, "3201", coords_to_world);
+        let threshold = htw!(prov, "0101", coords_to_world);
+        assert_eq!(hearth.sector_location, (-1, 0));
+        assert_eq!(kettle.straight_line_distance(threshold), 1);
+        assert_eq!(hearth.straight_line_distance(threshold), 2);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_find_custom_sectors(data_dir: &PathBuf) -> Result<()> {
+        let cli_custom: CustomSector = "Name: Proving Ground; X: 7; Y: 8".parse()?;
+        let sector_names = vec!["Homebrew".to_string(), "Proving Ground".to_string()];
+        let custom_sectors = find_custom_sectors(data_dir, None, &sector_names, &[cli_custom])?;
+        assert_eq!(custom_sectors.len(), 2);
+        assert_eq!(custom_sectors.get("Homebrew").unwrap().location, (-1, 0));
+        assert_eq!(
+            custom_sectors.get("Proving Ground").unwrap().location,
+            (7, 8)
+        );
+
+        // Sidecars without a Name are named after their file.
+        let temp_dir = tempdir()?;
+        let temp_data_dir: PathBuf = temp_dir.path().to_path_buf();
+        write(temp_data_dir.join("Nameless.meta"), "X: 1\nY: 2\n")?;
+        let sector_names = vec!["Nameless".to_string()];
+        let custom_sectors = find_custom_sectors(&temp_data_dir, None, &sector_names, &[])?;
+        assert_eq!(
+            custom_sectors.get("Nameless").unwrap().names,
+            vec!["Nameless"]
+        );
+
+        write(temp_data_dir.join("Nameless.meta"), "X: 1\n")?;
+        assert!(find_custom_sectors(&temp_data_dir, None, &sector_names, &[]).is_err());
+
+        let cli_custom: CustomSector = "X: 7; Y: 8".parse()?;
+        assert!(find_custom_sectors(&temp_data_dir, None, &[], &[cli_custom]).is_err());
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
     #[rstest]
     fn test_parse_file_of_sectors() -> Result<()> {
         let file_of_sectors = PathBuf::from("./sector_lists/CsTw.txt");
@@ -438,45 +1089,309 @@ mod tests {
     }
 
     #[rstest]
-    fn test_parse_max_jumps() -> Result<()> {
-        let args = Args {
-            algorithm: Algorithm::Dial,
-            min_btn: 0.0,
-            data_directory: Some(PathBuf::from("/tmp")),
-            file_of_sectors: vec![],
-            max_jump: None,
-            max_jump_minor: 1,
-            max_jump_feeder: 2,
-            max_jump_intermediate: 3,
-            max_jump_main: 4,
-            max_jump_major: 5,
-            output_directory: PathBuf::from("/tmp"),
-            quiet: true,
-            min_route_btn: 0.0,
-            sector: vec![],
-            verbose: 0,
-            ignore_xboat_routes: false,
-            passenger: false,
-            disallow_red_zones: false,
-            text_btns: false,
-            iw_rules: false,
-        };
-        let max_jumps = parse_max_jumps(&args);
-        assert_eq!(max_jumps.get(&Minor), Some(&1));
-        assert_eq!(max_jumps.get(&Feeder), Some(&2));
-        assert_eq!(max_jumps.get(&Intermediate), Some(&3));
-        assert_eq!(max_jumps.get(&Main), Some(&4));
-        assert_eq!(max_jumps.get(&Major), Some(&5));
+    fn test_sector_memory_source(source: &FsSource) -> Result<()> {
+        let mut memory_source = MemorySource::new();
+        for sector_name in ["Proving Ground", "Mockingbird"] {
+            memory_source.insert(
+                sector_name,
+                source.sector_data(sector_name)?,
+                source.sector_metadata(sector_name)?,
+            );
+        }
+
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
+        let mut coords_to_world2: HashMap<Coords, World> = HashMap::new();
+        let mut location_to_sector2: HashMap<(i64, i64), Sector> = HashMap::new();
+        for sector_name in ["Proving Ground", "Mockingbird"] {
+            let sector = Sector::new(
+                source,
+                sector_name.to_string(),
+                None,
+                false,
+                &mut coords_to_world,
+            )?;
+            location_to_sector.insert(sector.location, sector);
+            let sector2 = Sector::new(
+                &memory_source,
+                sector_name.to_string(),
+                None,
+                false,
+                &mut coords_to_world2,
+            )?;
+            location_to_sector2.insert(sector2.location, sector2);
+        }
+        for sector in location_to_sector.values() {
+            sector.parse_xml_routes(source, &location_to_sector, &mut coords_to_world)?;
+        }
+        for sector in location_to_sector2.values() {
+            sector.parse_xml_routes(&memory_source, &location_to_sector2, &mut coords_to_world2)?;
+        }
+
+        assert_eq!(location_to_sector, location_to_sector2);
+        assert_eq!(coords_to_world.len(), coords_to_world2.len());
+        for (coords, world) in coords_to_world.iter() {
+            let world2 = coords_to_world2.get(coords).unwrap();
+            assert_eq!(world.name, world2.name);
+            assert_eq!(world.uwp, world2.uwp);
+            assert_eq!(world.xboat_routes, world2.xboat_routes);
+        }
 
         Ok(())
     }
 
     #[rstest]
-    fn test_world_aramis(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_sector_tab_delimited(source: &FsSource, data_dir: &PathBuf) -> Result<()> {
+        // The same sector in the T5 tab-delimited format, with the same
+        // metadata.
+        let sector_name = "Proving Ground";
+        let mut memory_source = MemorySource::new();
+        memory_source.insert(
+            sector_name,
+            read_to_string(data_dir.join("Proving Ground.tab"))?,
+            source.sector_metadata(sector_name)?,
+        );
+
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let sector = Sector::new(
+            source,
+            sector_name.to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        let mut coords_to_world2: HashMap<Coords, World> = HashMap::new();
+        let sector2 = Sector::new(
+            &memory_source,
+            sector_name.to_string(),
+            None,
+            false,
+            &mut coords_to_world2,
+        )?;
+
+        assert_eq!(sector, sector2);
+        assert_eq!(coords_to_world.len(), 20);
+        assert_eq!(coords_to_world.len(), coords_to_world2.len());
+        for (coords, world) in coords_to_world.iter() {
+            let world2 = coords_to_world2.get(coords).unwrap();
+            assert_eq!(world.sector_location, world2.sector_location);
+            assert_eq!(world.sector_name, world2.sector_name);
+            assert_eq!(world.hex, world2.hex);
+            assert_eq!(world.name, world2.name);
+            assert_eq!(world.uwp, world2.uwp);
+            assert_eq!(world.trade_classifications, world2.trade_classifications);
+            assert_eq!(world.importance, world2.importance);
+            assert_eq!(world.economic, world2.economic);
+            assert_eq!(world.cultural, world2.cultural);
+            assert_eq!(world.nobles, world2.nobles);
+            assert_eq!(world.bases, world2.bases);
+            assert_eq!(world.zone, world2.zone);
+            assert_eq!(world.pbg, world2.pbg);
+            assert_eq!(world.worlds, world2.worlds);
+            assert_eq!(world.allegiance, world2.allegiance);
+            assert_eq!(world.stars, world2.stars);
+        }
+
+        let keystone = htw!(sector2, "2602", coords_to_world2);
+        assert_eq!(keystone.name, "Keystone");
+        assert_eq!(keystone.bases, set!("N".to_string(), "S".to_string()));
+        assert_eq!(keystone.nobles, "BcF");
+        assert_eq!(keystone.zone, 'G');
+        let verge = htw!(sector2, "3202", coords_to_world2);
+        assert_eq!(verge.zone, 'A');
+        assert_eq!(verge.allegiance, "CsIm");
+        let ember = htw!(sector2, "0521", coords_to_world2);
+        assert_eq!(ember.stars, vec!["F8 V", "M4 V"]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_sector_bad_data(source: &FsSource) -> Result<()> {
+        let sector_name = "Proving Ground";
+        let data = source
+            .sector_data(sector_name)?
+            .replace("C565566-8", "C565566  ")
+            .replace("3209", "32O9")
+            .replace(" 102 3  NaHu", " 1   3  NaHu");
+        let mut memory_source = MemorySource::new();
+        memory_source.insert(
+            sector_name,
+            data.to_string(),
+            source.sector_metadata(sector_name)?,
+        );
+
+        // Waypoint is on line 7, right after the header and separator.
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let err = Sector::new(
+            &memory_source,
+            sector_name.to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ParseError>(),
+            Some(&ParseError::BadField {
+                location: sector_name.to_string(),
+                line: 7,
+                hex: "0420".to_string(),
+                field: "UWP".to_string(),
+                value: "C565566  ".to_string(),
+            })
+        );
+
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let sector = Sector::new(
+            &memory_source,
+            sector_name.to_string(),
+            None,
+            true,
+            &mut coords_to_world,
+        )?;
+        assert_eq!(sector.hex_to_coords.len(), 17);
+        assert_eq!(coords_to_world.len(), 17);
+        let skipped: Vec<String> = sector
+            .skipped_worlds
+            .iter()
+            .map(|err| err.to_string())
+            .collect();
+        assert_eq!(
+            skipped,
+            vec![
+                "Proving Ground line 7 hex 0420: bad UWP 'C565566  '",
+                "Proving Ground line 13 hex 1221: bad PBG '1'",
+                "Proving Ground line 25 hex 32O9: bad Hex '32O9'",
+            ]
+        );
+
+        // World data with no header can't be parsed at all.
+        let headless: String = data
+            .lines()
+            .filter(|line| !line.starts_with("---"))
+            .map(|line| line.to_string() + "\n")
+            .collect();
+        memory_source.insert(sector_name, headless, source.sector_metadata(sector_name)?);
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let sector = Sector::new(
+            &memory_source,
+            sector_name.to_string(),
+            None,
+            true,
+            &mut coords_to_world,
+        )?;
+        assert!(coords_to_world.is_empty());
+        assert_eq!(sector.skipped_worlds.len(), 20);
+        assert_eq!(
+            sector.skipped_worlds[0],
+            ParseError::NoHeader {
+                location: sector_name.to_string(),
+                line: 5,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_lint_sectors(source: &FsSource) -> Result<()> {
+        let sector_name = "Proving Ground";
+        let mut data: String = source
+            .sector_data(sector_name)?
+            .lines()
+            .filter(|line| !line.starts_with("2602"))
+            .map(|line| line.to_string() + "\n")
+            .collect();
+        data = data
+            .replace("C566776-8", "CG66776-8")
+            .replace("Ag Ga Ni  ", "Ag Ga Hi  ")
+            .replace(" 200 4  ImDd", " 2A0 4  ImDd")
+            .replace(" 210 5  CsIm", " 210 5  CsZz");
+        data += "3209 Quill Again          D332488-7 Ni Po                                    \
+                 { -1 } (731-2) [4532] -     -  - 210 5  CsZz K7 V\n";
+        data += "3341 Beyond               D332488-7 Ni Po                                    \
+                 { -1 } (731-2) [4532] -     -  - 210 5  CsIm K7 V\n";
+        let mut memory_source = MemorySource::new();
+        memory_source.insert(sector_name, data, source.sector_metadata(sector_name)?);
+
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
+        let sector = Sector::new(
+            &memory_source,
+            sector_name.to_string(),
+            None,
+            true,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(sector.location, sector);
+
+        let issues: Vec<String> =
+            lint_sectors(&memory_source, &location_to_sector, &coords_to_world, &[])?
+                .iter()
+                .map(|issue| issue.to_string())
+                .collect();
+        assert_eq!(
+            issues,
+            vec![
+                "Proving Ground: Proving Ground line 25 hex 3209: duplicate hex",
+                "Proving Ground 0101: UWP CG66776-8 has size 'G' out of range 0-F",
+                "Proving Ground 0622: PBG '2A0' is not three digits",
+                "Proving Ground 2602: xboat route Proving Ground 2602 to Proving Ground 3003 \
+                 ends at empty hex Proving Ground 2602",
+                "Proving Ground 2703: trade code Hi contradicts UWP C655675-7",
+                "Proving Ground 3209: unknown allegiance code CsZz",
+                "Proving Ground 3341: hex 3341 is outside 0101-3240",
+            ]
+        );
+
+        // A duplicate hex doesn't stop a normal run: the later world
+        // replaces the earlier one.
+        let mut data = source.sector_data(sector_name)?;
+        data += "3209 Quill Again          D332488-7 Ni Po                                    \
+                 { -1 } (731-2) [4532] -     -  - 210 5  CsIm K7 V\n";
+        memory_source.insert(sector_name, data, source.sector_metadata(sector_name)?);
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let sector = Sector::new(
+            &memory_source,
+            sector_name.to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        assert!(sector.skipped_worlds.is_empty());
+        assert_eq!(
+            sector
+                .hex_to_world("3209".to_string(), &coords_to_world)
+                .unwrap()
+                .name,
+            "Quill Again"
+        );
+        assert_eq!(coords_to_world.len(), 20);
+
+        // The real data is clean.
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
+        for sector_name in ["Mockingbird", "Proving Ground", "Spinward Marches"] {
+            let sector = Sector::new(
+                source,
+                sector_name.to_string(),
+                None,
+                false,
+                &mut coords_to_world,
+            )?;
+            location_to_sector.insert(sector.location, sector);
+        }
+        assert!(lint_sectors(source, &location_to_sector, &coords_to_world, &[])?.is_empty());
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_world_aramis(source: &FsSource) -> Result<()> {
         let sector_name = "Spinward Marches".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         let aramis_coords = sector.hex_to_coords.get("3110").unwrap();
         let aramis = coords_to_world.get(aramis_coords).unwrap();
@@ -509,10 +1424,10 @@ mod tests {
         assert_eq!(aramis.government(), '5');
         assert_eq!(aramis.law_level(), '6');
         assert_eq!(aramis.tech_level(), 'B');
-        assert_eq!(aramis.g_tech_level(), 9);
-        assert_eq!(aramis.uwtn(), 3.5);
-        assert_eq!(aramis.wtn_port_modifier(), 0.5);
-        assert_eq!(aramis.wtn(), 4.0);
+        assert_eq!(aramis.g_tech_level(&FT_RULES), 9);
+        assert_eq!(aramis.uwtn(&FT_RULES), 3.5);
+        assert_eq!(aramis.wtn_port_modifier(&FT_RULES), 0.5);
+        assert_eq!(aramis.wtn(&*FT_RULES), 4.0);
         assert_eq!(aramis.gas_giants(), '0');
         assert!(aramis.can_refuel(false));
         assert_eq!(aramis.desc(), "Aramis (Spinward Marches 3110)");
@@ -521,11 +1436,10 @@ mod tests {
     }
 
     #[rstest]
-    fn test_world_regina(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_world_regina(source: &FsSource) -> Result<()> {
         let sector_name = "Spinward Marches".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         let regina_coords = sector.hex_to_coords.get("1910").unwrap();
         let regina = coords_to_world.get(regina_coords).unwrap();
@@ -568,10 +1482,10 @@ mod tests {
         assert_eq!(regina.government(), '9');
         assert_eq!(regina.law_level(), '9');
         assert_eq!(regina.tech_level(), 'C');
-        assert_eq!(regina.g_tech_level(), 10);
-        assert_eq!(regina.uwtn(), 5.0);
-        assert_eq!(regina.wtn_port_modifier(), 0.0);
-        assert_eq!(regina.wtn(), 5.0);
+        assert_eq!(regina.g_tech_level(&FT_RULES), 10);
+        assert_eq!(regina.uwtn(&FT_RULES), 5.0);
+        assert_eq!(regina.wtn_port_modifier(&FT_RULES), 0.0);
+        assert_eq!(regina.wtn(&*FT_RULES), 5.0);
         assert_eq!(regina.gas_giants(), '3');
         assert!(regina.can_refuel(false));
         assert_eq!(regina.desc(), "Regina (Spinward Marches 1910)");
@@ -580,11 +1494,10 @@ mod tests {
     }
 
     #[rstest]
-    fn test_world_bronze(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_world_bronze(source: &FsSource) -> Result<()> {
         let sector_name = "Spinward Marches".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         let bronze_coords = sector.hex_to_coords.get("1627").unwrap();
         let bronze = coords_to_world.get(bronze_coords).unwrap();
@@ -622,10 +1535,10 @@ mod tests {
         assert_eq!(bronze.government(), '0');
         assert_eq!(bronze.law_level(), '0');
         assert_eq!(bronze.tech_level(), '0');
-        assert_eq!(bronze.g_tech_level(), 2);
-        assert_eq!(bronze.uwtn(), -0.5);
-        assert_eq!(bronze.wtn_port_modifier(), 0.5);
-        assert_eq!(bronze.wtn(), 0.0);
+        assert_eq!(bronze.g_tech_level(&FT_RULES), 2);
+        assert_eq!(bronze.uwtn(&FT_RULES), -0.5);
+        assert_eq!(bronze.wtn_port_modifier(&FT_RULES), 0.5);
+        assert_eq!(bronze.wtn(&*FT_RULES), 0.0);
         assert_eq!(bronze.gas_giants(), '0');
         assert!(bronze.can_refuel(false));
         assert_eq!(bronze.desc(), "Bronze (Spinward Marches 1627)");
@@ -634,11 +1547,10 @@ mod tests {
     }
 
     #[rstest]
-    fn test_world_callia(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_world_callia(source: &FsSource) -> Result<()> {
         let sector_name = "Spinward Marches".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         let callia_coords = sector.hex_to_coords.get("1836").unwrap();
         let callia = coords_to_world.get(callia_coords).unwrap();
@@ -671,10 +1583,10 @@ mod tests {
         assert_eq!(callia.government(), '5');
         assert_eq!(callia.law_level(), '2');
         assert_eq!(callia.tech_level(), '6');
-        assert_eq!(callia.g_tech_level(), 6);
-        assert_eq!(callia.uwtn(), 4.5);
-        assert_eq!(callia.wtn_port_modifier(), -1.0);
-        assert_eq!(callia.wtn(), 3.5);
+        assert_eq!(callia.g_tech_level(&FT_RULES), 6);
+        assert_eq!(callia.uwtn(&FT_RULES), 4.5);
+        assert_eq!(callia.wtn_port_modifier(&FT_RULES), -1.0);
+        assert_eq!(callia.wtn(&*FT_RULES), 3.5);
         assert_eq!(callia.gas_giants(), '0');
         assert!(!callia.can_refuel(false));
         assert_eq!(callia.desc(), "Callia (Spinward Marches 1836)");
@@ -683,11 +1595,10 @@ mod tests {
     }
 
     #[rstest]
-    fn test_world_candory(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_world_candory(source: &FsSource) -> Result<()> {
         let sector_name = "Spinward Marches".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         let candory_coords = sector.hex_to_coords.get("0336").unwrap();
         let candory = coords_to_world.get(candory_coords).unwrap();
@@ -725,10 +1636,10 @@ mod tests {
         assert_eq!(candory.government(), '3');
         assert_eq!(candory.law_level(), '4');
         assert_eq!(candory.tech_level(), '8');
-        assert_eq!(candory.g_tech_level(), 8);
-        assert_eq!(candory.uwtn(), 3.5);
-        assert_eq!(candory.wtn_port_modifier(), 0.0);
-        assert_eq!(candory.wtn(), 3.5);
+        assert_eq!(candory.g_tech_level(&FT_RULES), 8);
+        assert_eq!(candory.uwtn(&FT_RULES), 3.5);
+        assert_eq!(candory.wtn_port_modifier(&FT_RULES), 0.0);
+        assert_eq!(candory.wtn(&*FT_RULES), 3.5);
         assert_eq!(candory.gas_giants(), '0');
         assert!(!candory.can_refuel(false));
         assert!(!candory.can_refuel(true));
@@ -738,11 +1649,10 @@ mod tests {
     }
 
     #[rstest]
-    fn test_world_mora(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_world_mora(source: &FsSource) -> Result<()> {
         let sector_name = "Spinward Marches".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         let mora_coords = sector.hex_to_coords.get("3124").unwrap();
         let mora = coords_to_world.get(mora_coords).unwrap();
@@ -775,10 +1685,10 @@ mod tests {
         assert_eq!(mora.government(), 'C');
         assert_eq!(mora.law_level(), '7');
         assert_eq!(mora.tech_level(), 'F');
-        assert_eq!(mora.g_tech_level(), 12);
-        assert_eq!(mora.uwtn(), 6.5);
-        assert_eq!(mora.wtn_port_modifier(), 0.0);
-        assert_eq!(mora.wtn(), 6.5);
+        assert_eq!(mora.g_tech_level(&FT_RULES), 12);
+        assert_eq!(mora.uwtn(&FT_RULES), 6.5);
+        assert_eq!(mora.wtn_port_modifier(&FT_RULES), 0.0);
+        assert_eq!(mora.wtn(&*FT_RULES), 6.5);
         assert_eq!(mora.gas_giants(), '2');
         assert!(mora.can_refuel(false));
         assert!(mora.can_refuel(true));
@@ -788,11 +1698,10 @@ mod tests {
     }
 
     #[rstest]
-    fn test_world_khiinra_ash(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_world_khiinra_ash(source: &FsSource) -> Result<()> {
         let sector_name = "Core".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         let khiinra_ash_coords = sector.hex_to_coords.get("2916").unwrap();
         let khiinra_ash = coords_to_world.get(khiinra_ash_coords).unwrap();
@@ -801,7 +1710,10 @@ mod tests {
         assert_eq!(khiinra_ash.sector_name, "Core");
         assert_eq!(khiinra_ash.hex, "2916");
         assert_eq!(khiinra_ash.uwp, "BAE6362-8");
-        // No test for trade classifications to avoid UTF-8 in the code
+        // The remarks have a multi-byte UTF-8 character, so the columns
+        // after them must be sliced by character rather than by byte.
+        let tc = set!("Lo".to_string(), "(Ashñu)".to_string());
+        assert_eq!(khiinra_ash.trade_classifications, tc);
         assert_eq!(khiinra_ash.importance(), -1);
         assert_eq!(khiinra_ash.economic(), "920-5");
         assert_eq!(khiinra_ash.cultural(), "1214");
@@ -822,10 +1734,10 @@ mod tests {
         assert_eq!(khiinra_ash.government(), '6');
         assert_eq!(khiinra_ash.law_level(), '2');
         assert_eq!(khiinra_ash.tech_level(), '8');
-        assert_eq!(khiinra_ash.g_tech_level(), 8);
-        assert_eq!(khiinra_ash.uwtn(), 2.0);
-        assert_eq!(khiinra_ash.wtn_port_modifier(), 0.5);
-        assert_eq!(khiinra_ash.wtn(), 2.5);
+        assert_eq!(khiinra_ash.g_tech_level(&FT_RULES), 8);
+        assert_eq!(khiinra_ash.uwtn(&FT_RULES), 2.0);
+        assert_eq!(khiinra_ash.wtn_port_modifier(&FT_RULES), 0.5);
+        assert_eq!(khiinra_ash.wtn(&*FT_RULES), 2.5);
         assert_eq!(khiinra_ash.gas_giants(), '4');
         assert!(khiinra_ash.can_refuel(false));
         assert_eq!(khiinra_ash.desc(), "Khiinra Ash (Core 2916)");
@@ -834,11 +1746,10 @@ mod tests {
     }
 
     #[rstest]
-    fn test_world_enz(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_world_enz(source: &FsSource) -> Result<()> {
         let sector_name = "Yiklerzdanzh".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let sector = Sector::new(&data_dir, sector_name, &mut coords_to_world);
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
 
         let enz_coords = sector.hex_to_coords.get("0119").unwrap();
         let enz = coords_to_world.get(enz_coords).unwrap();
@@ -869,974 +1780,1137 @@ mod tests {
         assert_eq!(enz.government(), '6');
         assert_eq!(enz.law_level(), 'E');
         assert_eq!(enz.tech_level(), '8');
-        assert_eq!(enz.g_tech_level(), 8);
-        assert_eq!(enz.uwtn(), 3.0);
-        assert_eq!(enz.wtn_port_modifier(), 0.0);
-        assert_eq!(enz.wtn(), 3.0);
+        assert_eq!(enz.g_tech_level(&FT_RULES), 8);
+        assert_eq!(enz.uwtn(&FT_RULES), 3.0);
+        assert_eq!(enz.wtn_port_modifier(&FT_RULES), 0.0);
+        assert_eq!(enz.wtn(&*FT_RULES), 3.0);
         assert_eq!(enz.gas_giants(), '1');
         assert!(enz.can_refuel(false));
         assert_eq!(enz.desc(), "Enz (Yiklerzdanzh 0119)");
+        // No economic extension to get a T5 WTN from.
+        assert_eq!(T5Rules::default().economic_wtn(enz), None);
 
         Ok(())
     }
 
     #[rstest]
-    fn test_get_coords(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_world_hollow_oak(source: &FsSource) -> Result<()> {
+        let sector_name = "Mockingbird".to_string();
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
-            &mut coords_to_world,
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
+
+        let hollow_oak_coords = sector.hex_to_coords.get("0205").unwrap();
+        let hollow_oak = coords_to_world.get(hollow_oak_coords).unwrap();
+        assert_eq!(hollow_oak.name, "Hollow Oak");
+        assert_eq!(hollow_oak.sector_location, (1, 0));
+        assert_eq!(hollow_oak.sector_name, "Mockingbird");
+        assert_eq!(hollow_oak.hex, "0205");
+        assert_eq!(hollow_oak.uwp, "C587654-9");
+
+        // The remarks contain a multi-byte character, which must not shift
+        // the columns that follow.
+        let tc = set!(
+            "Ag".to_string(),
+            "Ni".to_string(),
+            "Ri".to_string(),
+            "(\u{c4}llyn)3".to_string()
         );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let ldd = htw!(spin, 3010, coords_to_world);
-        let natoko = htw!(spin, 3209, coords_to_world);
-        let reacher = htw!(spin, 3210, coords_to_world);
-        let vinorian = htw!(spin, 3111, coords_to_world);
-        let nutema = htw!(spin, 3112, coords_to_world);
-        let margesi = htw!(spin, 3212, coords_to_world);
-        let saarinen = htw!(dene, 0113, coords_to_world);
-        let regina = htw!(spin, 1910, coords_to_world);
-
-        let no_world = spin.hex_to_world("9999".to_string(), &coords_to_world);
-
-        assert_eq!(<(f64, f64)>::from(aramis.get_coords()), (-97.0, -30.0));
-        assert_eq!(<(f64, f64)>::from(ldd.get_coords()), (-98.0, -29.5));
-        assert_eq!(<(f64, f64)>::from(natoko.get_coords()), (-96.0, -30.5));
-        assert_eq!(<(f64, f64)>::from(reacher.get_coords()), (-96.0, -29.5));
-        assert_eq!(<(f64, f64)>::from(vinorian.get_coords()), (-97.0, -29.0));
-        assert_eq!(<(f64, f64)>::from(nutema.get_coords()), (-97.0, -28.0));
-        assert_eq!(<(f64, f64)>::from(margesi.get_coords()), (-96.0, -27.5));
-        assert_eq!(<(f64, f64)>::from(saarinen.get_coords()), (-95.0, -27.0));
-        assert_eq!(<(f64, f64)>::from(regina.get_coords()), (-109.0, -30.0));
+        assert_eq!(hollow_oak.trade_classifications, tc);
+
+        assert_eq!(hollow_oak.importance(), 1);
+        assert_eq!(hollow_oak.economic(), "A54+1");
+        assert_eq!(hollow_oak.cultural(), "6658");
+        assert_eq!(hollow_oak.nobles(), "B");
+        let bases = HashSet::new();
+        assert_eq!(hollow_oak.bases(), bases);
+        assert_eq!(hollow_oak.zone, 'G');
+        assert_eq!(hollow_oak.pbg, "221");
+        assert_eq!(hollow_oak.worlds(), 8);
+        assert_eq!(hollow_oak.allegiance, "ImDd");
+        assert_eq!(hollow_oak.stars(), vec!["G8 V", "K0 V"]);
+        assert_eq!(hollow_oak.starport(), 'C');
+        assert_eq!(hollow_oak.g_starport(), "III");
+        assert_eq!(hollow_oak.size(), '5');
+        assert_eq!(hollow_oak.atmosphere(), '8');
+        assert_eq!(hollow_oak.hydrosphere(), '7');
+        assert_eq!(hollow_oak.population(), '6');
+        assert_eq!(hollow_oak.government(), '5');
+        assert_eq!(hollow_oak.law_level(), '4');
+        assert_eq!(hollow_oak.tech_level(), '9');
+        assert_eq!(hollow_oak.g_tech_level(&FT_RULES), 9);
+        assert_eq!(hollow_oak.uwtn(&FT_RULES), 4.0);
+        assert_eq!(hollow_oak.wtn_port_modifier(&FT_RULES), 0.0);
+        assert_eq!(hollow_oak.wtn(&*FT_RULES), 4.0);
+        assert_eq!(hollow_oak.gas_giants(), '1');
+        assert!(hollow_oak.can_refuel(false));
+        assert_eq!(hollow_oak.desc(), "Hollow Oak (Mockingbird 0205)");
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_world_tinder(source: &FsSource) -> Result<()> {
+        let sector_name = "Mockingbird".to_string();
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let sector = Sector::new(source, sector_name, None, false, &mut coords_to_world)?;
+
+        let tinder_coords = sector.hex_to_coords.get("0303").unwrap();
+        let tinder = coords_to_world.get(tinder_coords).unwrap();
+        assert_eq!(tinder.name, "Tinder");
+        assert_eq!(tinder.sector_location, (1, 0));
+        assert_eq!(tinder.sector_name, "Mockingbird");
+        assert_eq!(tinder.hex, "0303");
+        assert_eq!(tinder.uwp, "X200000-0");
+        let tc = set!("Ba".to_string(), "Va".to_string());
+        assert_eq!(tinder.trade_classifications, tc);
+        // Blank Ix, Ex, and Cx columns
+        assert_eq!(tinder.importance(), 0);
+        assert_eq!(tinder.economic(), "       ");
+        assert_eq!(tinder.cultural(), "      ");
+        assert_eq!(tinder.nobles(), "");
+        let bases = HashSet::new();
+        assert_eq!(tinder.bases(), bases);
+        assert_eq!(tinder.zone, 'R');
+        assert_eq!(tinder.pbg, "002");
+        assert_eq!(tinder.worlds(), 2);
+        assert_eq!(tinder.allegiance, "NaHu");
+        assert_eq!(tinder.stars(), vec!["M7 V"]);
+        assert_eq!(tinder.starport(), 'X');
+        assert_eq!(tinder.g_starport(), "0");
+        assert_eq!(tinder.size(), '2');
+        assert_eq!(tinder.atmosphere(), '0');
+        assert_eq!(tinder.hydrosphere(), '0');
+        assert_eq!(tinder.population(), '0');
+        assert_eq!(tinder.government(), '0');
+        assert_eq!(tinder.law_level(), '0');
+        assert_eq!(tinder.tech_level(), '0');
+        assert_eq!(tinder.g_tech_level(&FT_RULES), 2);
+        assert_eq!(tinder.uwtn(&FT_RULES), -0.5);
+        assert_eq!(tinder.wtn_port_modifier(&FT_RULES), 0.0);
+        assert_eq!(tinder.wtn(&*FT_RULES), -0.5);
+        assert_eq!(tinder.gas_giants(), '2');
+        assert!(tinder.can_refuel(false));
+        assert!(!tinder.can_refuel(true));
+        assert_eq!(tinder.desc(), "Tinder (Mockingbird 0303)");
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_get_coords(source: &FsSource) -> Result<()> {
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+
+        let threshold = htw!(prov, "0101", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let verge = htw!(prov, "3202", coords_to_world);
+        let quill = htw!(prov, "3209", coords_to_world);
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let ford = htw!(mock, "0109", coords_to_world);
+        let hollow_oak = htw!(mock, "0205", coords_to_world);
+        let tinder = htw!(mock, "0303", coords_to_world);
+        let last_light = htw!(mock, "3240", coords_to_world);
+
+        let no_world = prov.hex_to_world("9999".to_string(), &coords_to_world);
+        let empty_hex = prov.hex_to_world("0102".to_string(), &coords_to_world);
+
+        assert_eq!(<(f64, f64)>::from(threshold.get_coords()), (1.0, 1.0));
+        assert_eq!(<(f64, f64)>::from(keystone.get_coords()), (26.0, 2.5));
+        assert_eq!(<(f64, f64)>::from(brimstone.get_coords()), (30.0, 3.5));
+        assert_eq!(<(f64, f64)>::from(verge.get_coords()), (32.0, 2.5));
+        assert_eq!(<(f64, f64)>::from(quill.get_coords()), (32.0, 9.5));
+        assert_eq!(<(f64, f64)>::from(stillwater.get_coords()), (33.0, 3.0));
+        assert_eq!(<(f64, f64)>::from(ford.get_coords()), (33.0, 9.0));
+        assert_eq!(<(f64, f64)>::from(hollow_oak.get_coords()), (34.0, 5.5));
+        assert_eq!(<(f64, f64)>::from(tinder.get_coords()), (35.0, 3.0));
+        assert_eq!(<(f64, f64)>::from(last_light.get_coords()), (64.0, 40.5));
         assert_eq!(no_world, None);
+        assert_eq!(empty_hex, None);
 
         Ok(())
     }
 
     #[rstest]
-    fn test_straight_line_distance(
-        data_dir: &PathBuf,
-        download: &Result<Vec<String>>,
-    ) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_straight_line_distance(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let ldd = htw!(spin, 3010, coords_to_world);
-        let natoko = htw!(spin, 3209, coords_to_world);
-        let reacher = htw!(spin, 3210, coords_to_world);
-        let vinorian = htw!(spin, 3111, coords_to_world);
-        let nutema = htw!(spin, 3112, coords_to_world);
-        let margesi = htw!(spin, 3212, coords_to_world);
-        let patinir = htw!(spin, 3207, coords_to_world);
-        let saarinen = htw!(dene, 0113, coords_to_world);
-        let regina = htw!(spin, 1910, coords_to_world);
-        let corfu = htw!(spin, 2602, coords_to_world);
-        let lablon = htw!(spin, 2701, coords_to_world);
-        let junidy = htw!(spin, 3202, coords_to_world);
-        let marz = htw!(dene, 0201, coords_to_world);
-
-        assert_eq!(aramis.straight_line_distance(aramis), 0);
-        assert_eq!(aramis.straight_line_distance(ldd), 1);
-        assert_eq!(ldd.straight_line_distance(aramis), 1);
-        assert_eq!(aramis.straight_line_distance(natoko), 1);
-        assert_eq!(aramis.straight_line_distance(reacher), 1);
-        assert_eq!(natoko.straight_line_distance(reacher), 1);
-        assert_eq!(aramis.straight_line_distance(vinorian), 1);
-        assert_eq!(vinorian.straight_line_distance(nutema), 1);
-        assert_eq!(nutema.straight_line_distance(margesi), 1);
-        assert_eq!(margesi.straight_line_distance(saarinen), 1);
-        assert_eq!(ldd.straight_line_distance(natoko), 2);
-        assert_eq!(ldd.straight_line_distance(reacher), 2);
-        assert_eq!(ldd.straight_line_distance(nutema), 2);
-        assert_eq!(ldd.straight_line_distance(margesi), 3);
-        assert_eq!(aramis.straight_line_distance(patinir), 3);
-        assert_eq!(ldd.straight_line_distance(saarinen), 4);
-        assert_eq!(aramis.straight_line_distance(corfu), 10);
-        assert_eq!(aramis.straight_line_distance(lablon), 11);
-        assert_eq!(aramis.straight_line_distance(junidy), 8);
-        assert_eq!(aramis.straight_line_distance(marz), 10);
-        assert_eq!(aramis.straight_line_distance(regina), 12);
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+
+        let threshold = htw!(prov, "0101", coords_to_world);
+        let waypoint = htw!(prov, "0420", coords_to_world);
+        let refuge = htw!(prov, "1021", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let cobble = htw!(prov, "2703", coords_to_world);
+        let tallow = htw!(prov, "2904", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let verge = htw!(prov, "3202", coords_to_world);
+        let quill = htw!(prov, "3209", coords_to_world);
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let ford = htw!(mock, "0109", coords_to_world);
+        let drift = htw!(mock, "0204", coords_to_world);
+        let hollow_oak = htw!(mock, "0205", coords_to_world);
+        let tinder = htw!(mock, "0303", coords_to_world);
+        let sable = htw!(mock, "0407", coords_to_world);
+        let last_light = htw!(mock, "3240", coords_to_world);
+
+        assert_eq!(keystone.straight_line_distance(keystone), 0);
+        assert_eq!(keystone.straight_line_distance(cobble), 1);
+        assert_eq!(cobble.straight_line_distance(keystone), 1);
+        assert_eq!(tallow.straight_line_distance(brimstone), 1);
+        assert_eq!(verge.straight_line_distance(stillwater), 1);
+        assert_eq!(quill.straight_line_distance(ford), 1);
+        assert_eq!(drift.straight_line_distance(hollow_oak), 1);
+        assert_eq!(brimstone.straight_line_distance(verge), 2);
+        assert_eq!(stillwater.straight_line_distance(tinder), 2);
+        assert_eq!(keystone.straight_line_distance(tallow), 3);
+        assert_eq!(brimstone.straight_line_distance(stillwater), 3);
+        assert_eq!(keystone.straight_line_distance(brimstone), 4);
+        assert_eq!(waypoint.straight_line_distance(refuge), 6);
+        assert_eq!(keystone.straight_line_distance(verge), 6);
+        assert_eq!(keystone.straight_line_distance(stillwater), 7);
+        assert_eq!(brimstone.straight_line_distance(sable), 7);
+        assert_eq!(keystone.straight_line_distance(sable), 10);
+        assert_eq!(threshold.straight_line_distance(keystone), 25);
+        assert_eq!(keystone.straight_line_distance(last_light), 57);
 
         Ok(())
     }
 
     #[rstest]
     fn test_distance_modifier_table_ft() {
-        assert_eq!(distance_modifier_table_ft(0), 0.0);
-        assert_eq!(distance_modifier_table_ft(1), 0.0);
-        assert_eq!(distance_modifier_table_ft(2), 0.5);
-        assert_eq!(distance_modifier_table_ft(3), 1.0);
-        assert_eq!(distance_modifier_table_ft(5), 1.0);
-        assert_eq!(distance_modifier_table_ft(6), 1.5);
-        assert_eq!(distance_modifier_table_ft(9), 1.5);
-        assert_eq!(distance_modifier_table_ft(10), 2.0);
-        assert_eq!(distance_modifier_table_ft(19), 2.0);
-        assert_eq!(distance_modifier_table_ft(20), 2.5);
-        assert_eq!(distance_modifier_table_ft(29), 2.5);
-        assert_eq!(distance_modifier_table_ft(30), 3.0);
-        assert_eq!(distance_modifier_table_ft(59), 3.0);
-        assert_eq!(distance_modifier_table_ft(60), 3.5);
-        assert_eq!(distance_modifier_table_ft(99), 3.5);
-        assert_eq!(distance_modifier_table_ft(100), 4.0);
-        assert_eq!(distance_modifier_table_ft(199), 4.0);
-        assert_eq!(distance_modifier_table_ft(200), 4.5);
-        assert_eq!(distance_modifier_table_ft(299), 4.5);
-        assert_eq!(distance_modifier_table_ft(300), 5.0);
-        assert_eq!(distance_modifier_table_ft(599), 5.0);
-        assert_eq!(distance_modifier_table_ft(600), 5.5);
-        assert_eq!(distance_modifier_table_ft(999), 5.5);
-        assert_eq!(distance_modifier_table_ft(1000), 6.0);
-        assert_eq!(distance_modifier_table_ft(9999), 6.0);
-        assert_eq!(distance_modifier_table_ft(INFINITY), MAX_DISTANCE_PENALTY);
+        assert_eq!(FT_RULES.distance_modifier(0), 0.0);
+        assert_eq!(FT_RULES.distance_modifier(1), 0.0);
+        assert_eq!(FT_RULES.distance_modifier(2), 0.5);
+        assert_eq!(FT_RULES.distance_modifier(3), 1.0);
+        assert_eq!(FT_RULES.distance_modifier(5), 1.0);
+        assert_eq!(FT_RULES.distance_modifier(6), 1.5);
+        assert_eq!(FT_RULES.distance_modifier(9), 1.5);
+        assert_eq!(FT_RULES.distance_modifier(10), 2.0);
+        assert_eq!(FT_RULES.distance_modifier(19), 2.0);
+        assert_eq!(FT_RULES.distance_modifier(20), 2.5);
+        assert_eq!(FT_RULES.distance_modifier(29), 2.5);
+        assert_eq!(FT_RULES.distance_modifier(30), 3.0);
+        assert_eq!(FT_RULES.distance_modifier(59), 3.0);
+        assert_eq!(FT_RULES.distance_modifier(60), 3.5);
+        assert_eq!(FT_RULES.distance_modifier(99), 3.5);
+        assert_eq!(FT_RULES.distance_modifier(100), 4.0);
+        assert_eq!(FT_RULES.distance_modifier(199), 4.0);
+        assert_eq!(FT_RULES.distance_modifier(200), 4.5);
+        assert_eq!(FT_RULES.distance_modifier(299), 4.5);
+        assert_eq!(FT_RULES.distance_modifier(300), 5.0);
+        assert_eq!(FT_RULES.distance_modifier(599), 5.0);
+        assert_eq!(FT_RULES.distance_modifier(600), 5.5);
+        assert_eq!(FT_RULES.distance_modifier(999), 5.5);
+        assert_eq!(FT_RULES.distance_modifier(1000), 6.0);
+        assert_eq!(FT_RULES.distance_modifier(9999), 6.0);
+        assert_eq!(FT_RULES.distance_modifier(INFINITY), MAX_DISTANCE_PENALTY);
     }
 
     #[rstest]
     fn test_distance_modifier_table_iw() {
-        assert_eq!(distance_modifier_table_iw(0), 0.0);
-        assert_eq!(distance_modifier_table_iw(1), 0.5);
-        assert_eq!(distance_modifier_table_iw(2), 0.5);
-        assert_eq!(distance_modifier_table_iw(3), 1.0);
-        assert_eq!(distance_modifier_table_iw(5), 1.0);
-        assert_eq!(distance_modifier_table_iw(6), 1.5);
-        assert_eq!(distance_modifier_table_iw(9), 1.5);
-        assert_eq!(distance_modifier_table_iw(10), 2.0);
-        assert_eq!(distance_modifier_table_iw(19), 2.0);
-        assert_eq!(distance_modifier_table_iw(20), MAX_DISTANCE_PENALTY);
-        assert_eq!(distance_modifier_table_iw(INFINITY), MAX_DISTANCE_PENALTY);
+        assert_eq!(IW_RULES.distance_modifier(0), 0.0);
+        assert_eq!(IW_RULES.distance_modifier(1), 0.5);
+        assert_eq!(IW_RULES.distance_modifier(2), 0.5);
+        assert_eq!(IW_RULES.distance_modifier(3), 1.0);
+        assert_eq!(IW_RULES.distance_modifier(5), 1.0);
+        assert_eq!(IW_RULES.distance_modifier(6), 1.5);
+        assert_eq!(IW_RULES.distance_modifier(9), 1.5);
+        assert_eq!(IW_RULES.distance_modifier(10), 2.0);
+        assert_eq!(IW_RULES.distance_modifier(19), 2.0);
+        assert_eq!(IW_RULES.distance_modifier(20), MAX_DISTANCE_PENALTY);
+        assert_eq!(IW_RULES.distance_modifier(INFINITY), MAX_DISTANCE_PENALTY);
     }
 
     #[rstest]
     fn test_same_allegiance() {
-        assert!(!(same_allegiance("CsIm", "CsIm")));
-        assert!(!(same_allegiance("CsZh", "CsZh")));
-        assert!(!(same_allegiance("CsIm", "CsZh")));
-        assert!(!(same_allegiance("NaHu", "NaHu")));
-        assert!(!(same_allegiance("NaXX", "NaXX")));
-        assert!(!(same_allegiance("NaHu", "NaXX")));
-        assert!(!(same_allegiance("DaCf", "ImDd")));
-        assert!(!(same_allegiance("ImDd", "ZhIN")));
-        assert!((same_allegiance("DaCf", "DaCf")));
-        assert!((same_allegiance("ImDd", "ImDd")));
-        assert!((same_allegiance("SwCf", "SwCf")));
-        assert!((same_allegiance("ZhIN", "ZhIN")));
-    }
-
-    #[rstest]
-    #[should_panic(expected = "Too many worlds for a u16!  We will overflow!")]
-    fn test_populate_navigable_distances_overflow(
-        data_dir: &PathBuf,
-        download: &Result<Vec<String>>,
-    ) {
-        if let Ok(_sector_names) = download {};
+        // Without groups, only identical codes match, and not even those for
+        // non-aligned worlds and client states.
+        let none = AllegianceGroups::default();
+        assert!(!(same_allegiance("CsIm", "CsIm", &none)));
+        assert!(!(same_allegiance("CsZh", "CsZh", &none)));
+        assert!(!(same_allegiance("CsIm", "CsZh", &none)));
+        assert!(!(same_allegiance("NaHu", "NaHu", &none)));
+        assert!(!(same_allegiance("NaXX", "NaXX", &none)));
+        assert!(!(same_allegiance("NaHu", "NaXX", &none)));
+        assert!(!(same_allegiance("DaCf", "ImDd", &none)));
+        assert!(!(same_allegiance("ImDd", "ZhIN", &none)));
+        assert!(!(same_allegiance("ImDd", "ImDv", &none)));
+        assert!((same_allegiance("DaCf", "DaCf", &none)));
+        assert!((same_allegiance("ImDd", "ImDd", &none)));
+        assert!((same_allegiance("SwCf", "SwCf", &none)));
+        assert!((same_allegiance("ZhIN", "ZhIN", &none)));
+
+        let groups = AllegianceGroups::standard();
+        assert!((same_allegiance("CsIm", "CsIm", &groups)));
+        assert!((same_allegiance("CsIm", "ImDd", &groups)));
+        assert!((same_allegiance("ImDd", "ImDv", &groups)));
+        assert!((same_allegiance("CsZh", "ZhIN", &groups)));
+        assert!((same_allegiance("AsT3", "AsMw", &groups)));
+        assert!(!(same_allegiance("CsIm", "CsZh", &groups)));
+        assert!(!(same_allegiance("ImDd", "ZhIN", &groups)));
+        assert!(!(same_allegiance("ImDd", "SwCf", &groups)));
+        assert!(!(same_allegiance("SwCf", "ImDd", &groups)));
+        assert!(!(same_allegiance("NaHu", "NaHu", &groups)));
+        assert!((same_allegiance("DaCf", "DaCf", &groups)));
+        assert!((same_allegiance("SwCf", "SwCf", &groups)));
+    }
+
+    #[rstest]
+    fn test_check_num_worlds() {
+        assert!(check_num_worlds(0).is_ok());
+        assert!(check_num_worlds(NO_PRED_NODE as usize - 1).is_ok());
+        // Any more and a world's index could be a sentinel.
+        assert_eq!(
+            check_num_worlds(NO_PRED_NODE as usize),
+            Err(GalaxyError::TooManyWorlds {
+                num_worlds: NO_PRED_NODE as usize
+            })
+        );
+        assert!(check_num_worlds(u32::MAX as usize).is_err());
+    }
+
+    #[rstest]
+    fn test_populate_navigable_distances_more_than_u16(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
-        let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
-        for sector in location_to_sector.values() {
-            sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
-                .unwrap();
-        }
-        // Make a temporary clone to avoid having mutable and immutable refs.
-        let coords_to_world2 = coords_to_world.clone();
-        for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
-        }
-        let mut sorted_coords: Vec<Coords> = Vec::new();
-        for ii in 0..66666 {
-            sorted_coords.push(Coords::new(ii as f64, ii as f64));
+        )?;
+        let template = htw!(prov, "0420", coords_to_world).clone();
+
+        // A chain of worlds a jump apart, too long for u16 indexes.
+        const NUM_WORLDS: usize = 70000;
+        let sorted_coords: Vec<Coords> = (0..NUM_WORLDS)
+            .map(|ii| Coords {
+                x: ii as i64,
+                y2: 0,
+            })
+            .collect();
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        for (ii, coords) in sorted_coords.iter().enumerate() {
+            let mut world = template.clone();
+            world.index = Some(ii);
+            world.xboat_routes.clear();
+            world.neighbors = vec![HashSet::new(); 2];
+            if ii > 0 {
+                world.neighbors[1].insert(sorted_coords[ii - 1]);
+            }
+            if ii + 1 < NUM_WORLDS {
+                world.neighbors[1].insert(sorted_coords[ii + 1]);
+            }
+            coords_to_world.insert(*coords, world);
         }
-        let (_, _) = populate_navigable_distances(&sorted_coords, &coords_to_world, 2, false, ALG);
+
+        let dist = populate_navigable_distances(
+            &sorted_coords,
+            &coords_to_world,
+            1,
+            false,
+            &NO_SCENARIO,
+            ALG,
+        )?;
+        let last = NUM_WORLDS as u32 - 1;
+        assert_eq!(dist.distance(0, last), last);
+        assert_eq!(dist.distance(last, 65536), last - 65536);
+        let path = dist.path(0, last).unwrap();
+        assert_eq!(path.len(), NUM_WORLDS);
+        assert_eq!(path[65536], 65536);
+
+        Ok(())
     }
 
     #[rstest]
-    fn test_distance_modifier(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_distance_modifier(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
         }
         let mut sorted_coords: Vec<Coords>;
         sorted_coords = coords_to_world.keys().cloned().collect();
         sorted_coords.sort();
-        assert_eq!(sorted_coords.len(), 825);
+        assert_eq!(sorted_coords.len(), 31);
         for (ii, coords) in sorted_coords.iter_mut().enumerate() {
             let world = coords_to_world.get_mut(coords).unwrap();
             world.index = Some(ii);
         }
-        let (dist2, _) =
-            populate_navigable_distances(&sorted_coords, &coords_to_world, 2, false, ALG);
-
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let ldd = htw!(spin, 3010, coords_to_world);
-        let natoko = htw!(spin, 3209, coords_to_world);
-        let vinorian = htw!(spin, 3111, coords_to_world);
-        let margesi = htw!(spin, 3212, coords_to_world);
-        let corfu = htw!(spin, 2602, coords_to_world);
-        let andor = htw!(spin, 0236, coords_to_world);
-        let candory = htw!(spin, 0336, coords_to_world);
-        let reno = htw!(spin, 0102, coords_to_world);
-        let regina = htw!(spin, 1910, coords_to_world);
-        let mongo = htw!(spin, 1204, coords_to_world);
-        let collace = htw!(spin, 1237, coords_to_world);
-        let pavanne = htw!(spin, 2905, coords_to_world);
-        let raweh = htw!(spin, 0139, coords_to_world);
-        let javan = htw!(dene, 2131, coords_to_world);
-        let salaam = htw!(dene, 3213, coords_to_world);
-
-        assert_eq!(aramis.distance_modifier(aramis, &dist2, false), 0.0);
-        assert_eq!(aramis.distance_modifier(ldd, &dist2, false), 0.0);
-        assert_eq!(aramis.distance_modifier(vinorian, &dist2, false), 0.0);
-        assert_eq!(aramis.distance_modifier(corfu, &dist2, false), 2.0);
-        assert_eq!(
-            aramis.distance_modifier(andor, &dist2, false),
+        let dist2 = populate_navigable_distances(
+            &sorted_coords,
+            &coords_to_world,
+            2,
+            false,
+            &NO_SCENARIO,
+            ALG,
+        )?;
+
+        let waypoint = htw!(prov, "0420", coords_to_world);
+        let refuge = htw!(prov, "1021", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let cobble = htw!(prov, "2703", coords_to_world);
+        let tallow = htw!(prov, "2904", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let cinder = htw!(prov, "3106", coords_to_world);
+        let verge = htw!(prov, "3202", coords_to_world);
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let sable = htw!(mock, "0407", coords_to_world);
+        let lee_shore = htw!(mock, "0912", coords_to_world);
+
+        assert_eq!(
+            keystone.distance_modifier(keystone, &dist2, &*FT_RULES),
+            0.0
+        );
+        assert_eq!(keystone.distance_modifier(cobble, &dist2, &*FT_RULES), 0.0);
+        assert_eq!(brimstone.distance_modifier(verge, &dist2, &*FT_RULES), 0.5);
+        assert_eq!(keystone.distance_modifier(tallow, &dist2, &*FT_RULES), 1.0);
+        assert_eq!(
+            keystone.distance_modifier(stillwater, &dist2, &*FT_RULES),
+            1.5
+        );
+        assert_eq!(keystone.distance_modifier(sable, &dist2, &*FT_RULES), 2.0);
+        assert_eq!(sable.distance_modifier(keystone, &dist2, &*FT_RULES), 2.0);
+        assert_eq!(
+            keystone.distance_modifier(lee_shore, &dist2, &*FT_RULES),
+            2.5
+        );
+        assert_eq!(
+            keystone.distance_modifier(cinder, &dist2, &*FT_RULES),
             MAX_DISTANCE_PENALTY
         );
-        assert_eq!(aramis.distance_modifier(margesi, &dist2, false), 1.0);
-        assert_eq!(aramis.distance_modifier(pavanne, &dist2, false), 1.5);
-        assert_eq!(aramis.distance_modifier(regina, &dist2, false), 2.0);
-        assert_eq!(aramis.distance_modifier(mongo, &dist2, false), 2.5);
-        assert_eq!(aramis.distance_modifier(collace, &dist2, false), 3.0);
-        assert_eq!(reno.distance_modifier(javan, &dist2, false), 3.5);
         assert_eq!(
-            andor.distance_modifier(candory, &dist2, false),
+            waypoint.distance_modifier(refuge, &dist2, &*FT_RULES),
             MAX_DISTANCE_PENALTY
         );
         assert_eq!(
-            candory.distance_modifier(andor, &dist2, false),
+            refuge.distance_modifier(waypoint, &dist2, &*FT_RULES),
             MAX_DISTANCE_PENALTY
         );
-        assert_eq!(ldd.distance_modifier(natoko, &dist2, false), 0.5);
-        assert_eq!(collace.distance_modifier(salaam, &dist2, false), 3.0);
-        assert_eq!(raweh.distance_modifier(salaam, &dist2, false), 3.5);
 
         Ok(())
     }
 
     #[rstest]
-    fn test_xboat_routes(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_xboat_routes(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let ldd = htw!(spin, 3010, coords_to_world);
-        let natoko = htw!(spin, 3209, coords_to_world);
-        let reacher = htw!(spin, 3210, coords_to_world);
-        let vinorian = htw!(spin, 3111, coords_to_world);
-        let nutema = htw!(spin, 3112, coords_to_world);
-        let saarinen = htw!(dene, 0113, coords_to_world);
-        let regina = htw!(spin, 1910, coords_to_world);
-        let corfu = htw!(spin, 2602, coords_to_world);
-        let lablon = htw!(spin, 2701, coords_to_world);
-        let junidy = htw!(spin, 3202, coords_to_world);
-        let marz = htw!(dene, 0201, coords_to_world);
-        let celepina = htw!(spin, 2913, coords_to_world);
-        let teh = htw!(dene, 0208, coords_to_world);
-        let ash = htw!(dene, 0504, coords_to_world);
-        let roup = htw!(spin, 2007, coords_to_world);
-        let jenghe = htw!(spin, 1810, coords_to_world);
-        let dinomn = htw!(spin, 1912, coords_to_world);
-        let towers = htw!(spin, 3103, coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let cobble = htw!(prov, "2703", coords_to_world);
+        let tallow = htw!(prov, "2904", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let verge = htw!(prov, "3202", coords_to_world);
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let ford = htw!(mock, "0109", coords_to_world);
+        let sable = htw!(mock, "0407", coords_to_world);
+        let farpoint = htw!(mock, "0811", coords_to_world);
+        let last_light = htw!(mock, "3240", coords_to_world);
 
         let set = HashSet::new();
-        assert_eq!(reacher.xboat_routes, set);
-        assert_eq!(vinorian.xboat_routes, set);
-        assert_eq!(nutema.xboat_routes, set);
-        assert_eq!(saarinen.xboat_routes, set);
-        assert_eq!(corfu.xboat_routes, set);
-        assert_eq!(lablon.xboat_routes, set);
-
-        let set = set!(ldd.get_coords(), natoko.get_coords());
-        assert_eq!(aramis.xboat_routes, set);
+        assert_eq!(cobble.xboat_routes, set);
+        assert_eq!(tallow.xboat_routes, set);
+        assert_eq!(ford.xboat_routes, set);
+        // Its route leads into a sector that was not loaded.
+        assert_eq!(last_light.xboat_routes, set);
 
-        let set = set!(aramis.get_coords(), celepina.get_coords());
-        assert_eq!(ldd.xboat_routes, set);
+        let set = set!(brimstone.get_coords());
+        assert_eq!(keystone.xboat_routes, set);
 
-        let set = set!(aramis.get_coords(), teh.get_coords());
-        assert_eq!(natoko.xboat_routes, set);
+        let set = set!(keystone.get_coords(), stillwater.get_coords());
+        assert_eq!(brimstone.xboat_routes, set);
 
-        let set = HashSet::new();
-        assert_eq!(reacher.xboat_routes, set);
-        assert_eq!(vinorian.xboat_routes, set);
-        assert_eq!(nutema.xboat_routes, set);
-        assert_eq!(saarinen.xboat_routes, set);
-        assert_eq!(corfu.xboat_routes, set);
-        assert_eq!(lablon.xboat_routes, set);
+        let set = set!(
+            brimstone.get_coords(),
+            verge.get_coords(),
+            sable.get_coords()
+        );
+        assert_eq!(stillwater.xboat_routes, set);
 
-        let set = set!(marz.get_coords(), towers.get_coords());
-        assert_eq!(junidy.xboat_routes, set);
+        let set = set!(stillwater.get_coords());
+        assert_eq!(verge.xboat_routes, set);
 
-        let set = set!(junidy.get_coords(), ash.get_coords());
-        assert_eq!(marz.xboat_routes, set);
+        let set = set!(stillwater.get_coords(), farpoint.get_coords());
+        assert_eq!(sable.xboat_routes, set);
 
-        let set = set!(roup.get_coords(), jenghe.get_coords(), dinomn.get_coords());
-        assert_eq!(regina.xboat_routes, set);
+        let set = set!(sable.get_coords());
+        assert_eq!(farpoint.xboat_routes, set);
 
         Ok(())
     }
 
     #[rstest]
-    fn test_xboat_routes_ley_empty_fornast(
-        data_dir: &PathBuf,
-        download: &Result<Vec<String>>,
-    ) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_xboat_routes_ley_empty_fornast(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let ley = Sector::new(&data_dir, "Ley".to_string(), &mut coords_to_world);
-        let empt = Sector::new(&data_dir, "Empty Quarter".to_string(), &mut coords_to_world);
-        let forn = Sector::new(&data_dir, "Fornast".to_string(), &mut coords_to_world);
-        location_to_sector.insert(ley.location, ley.clone());
-        location_to_sector.insert(empt.location, empt.clone());
-        location_to_sector.insert(forn.location, forn.clone());
+        for sector_name in ["Ley", "Empty Quarter", "Fornast"] {
+            let sector = Sector::new(
+                source,
+                sector_name.to_string(),
+                None,
+                false,
+                &mut coords_to_world,
+            )?;
+            location_to_sector.insert(sector.location, sector);
+        }
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
-
-        let econdora = htw!(ley, 0201, coords_to_world);
-        let maissia = htw!(ley, 0504, coords_to_world);
-        let nulinad = htw!(empt, 0338, coords_to_world);
-        let allyn = htw!(forn, 3103, coords_to_world);
+        let ley = location_to_sector.get(&(2, 0)).unwrap();
+        let empt = location_to_sector.get(&(2, -1)).unwrap();
+        let forn = location_to_sector.get(&(1, 0)).unwrap();
+
+        let econdora = htw!(ley, "0201", coords_to_world);
+        let maissia = htw!(ley, "0504", coords_to_world);
+        let nulinad = htw!(empt, "0338", coords_to_world);
+        let allyn = htw!(forn, "3103", coords_to_world);
         let set = set!(
             nulinad.get_coords(),
             allyn.get_coords(),
             maissia.get_coords()
         );
         assert_eq!(econdora.xboat_routes, set);
+        // Maissia's route on to 0807 ends at an empty hex, so is dropped.
+        let set = set!(econdora.get_coords());
+        assert_eq!(maissia.xboat_routes, set);
+        assert_eq!(nulinad.xboat_routes, set!(econdora.get_coords()));
+        assert_eq!(allyn.xboat_routes, set!(econdora.get_coords()));
 
         Ok(())
     }
 
     #[rstest]
-    fn test_neighbors(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_xboat_routes_unloaded_sector(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
+        )?;
+        location_to_sector.insert(mock.location, mock.clone());
+        for sector in location_to_sector.values() {
+            sector
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
+                .unwrap();
+        }
+
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let sable = htw!(mock, "0407", coords_to_world);
+        let set = set!(sable.get_coords());
+        assert_eq!(stillwater.xboat_routes, set);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_neighbors(source: &FsSource) -> Result<()> {
+        let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
+        let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
 
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
-        }
-
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let ldd = htw!(spin, 3010, coords_to_world);
-        let natoko = htw!(spin, 3209, coords_to_world);
-        let reacher = htw!(spin, 3210, coords_to_world);
-        let vinorian = htw!(spin, 3111, coords_to_world);
-        let nutema = htw!(spin, 3112, coords_to_world);
-        let teh = htw!(dene, 0208, coords_to_world);
-        let pysadi = htw!(spin, 3008, coords_to_world);
-        let margesi = htw!(spin, 3212, coords_to_world);
-        let zila = htw!(spin, 2908, coords_to_world);
-        let lewis = htw!(spin, 3107, coords_to_world);
-        let patinir = htw!(spin, 3207, coords_to_world);
-        let henoz = htw!(spin, 2912, coords_to_world);
-        let suvfoto = htw!(dene, 0211, coords_to_world);
-        let kretikaa = htw!(dene, 0209, coords_to_world);
-        let new_ramma = htw!(dene, 0108, coords_to_world);
-        let valhalla = htw!(spin, 2811, coords_to_world);
-        let saarinen = htw!(dene, 0113, coords_to_world);
-        let celepina = htw!(spin, 2913, coords_to_world);
-        let zivije = htw!(spin, 2812, coords_to_world);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
+        }
+
+        let threshold = htw!(prov, "0101", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let cobble = htw!(prov, "2703", coords_to_world);
+        let oxbow = htw!(prov, "2805", coords_to_world);
+        let tallow = htw!(prov, "2904", coords_to_world);
+        let gristle = htw!(prov, "2905", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let flint = htw!(prov, "3006", coords_to_world);
+        let cinder = htw!(prov, "3106", coords_to_world);
+        let verge = htw!(prov, "3202", coords_to_world);
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let drift = htw!(mock, "0204", coords_to_world);
+        let hollow_oak = htw!(mock, "0205", coords_to_world);
+        let tinder = htw!(mock, "0303", coords_to_world);
+
+        let set = set!(gristle.get_coords(), brimstone.get_coords());
+        assert_eq!(tallow.neighbors[1], set);
+
+        let set = set!(oxbow.get_coords(), cobble.get_coords());
+        assert_eq!(tallow.neighbors[2], set);
 
         let set = set!(
-            ldd.get_coords(),
-            natoko.get_coords(),
-            reacher.get_coords(),
-            vinorian.get_coords()
+            keystone.get_coords(),
+            verge.get_coords(),
+            flint.get_coords()
         );
-        assert_eq!(aramis.neighbors[1], set);
+        assert_eq!(tallow.neighbors[3], set);
 
-        let set = set!(nutema.get_coords(), pysadi.get_coords());
-        assert_eq!(aramis.neighbors[2], set);
+        let set = set!(verge.get_coords());
+        assert_eq!(stillwater.neighbors[1], set);
 
-        let set = set!(
-            margesi.get_coords(),
-            teh.get_coords(),
-            zila.get_coords(),
-            lewis.get_coords(),
-            patinir.get_coords(),
-            henoz.get_coords(),
-            suvfoto.get_coords(),
-            kretikaa.get_coords(),
-            new_ramma.get_coords(),
-            valhalla.get_coords()
-        );
-        assert_eq!(aramis.neighbors[3], set);
-
-        let set = set!(
-            aramis.get_coords(),
-            ldd.get_coords(),
-            reacher.get_coords(),
-            nutema.get_coords()
-        );
-        assert_eq!(vinorian.neighbors[1], set);
+        let set = set!(tinder.get_coords(), drift.get_coords());
+        assert_eq!(stillwater.neighbors[2], set);
 
-        let set = set!(
-            natoko.get_coords(),
-            margesi.get_coords(),
-            henoz.get_coords()
-        );
-        assert_eq!(vinorian.neighbors[2], set);
+        let set = set!(hollow_oak.get_coords(), brimstone.get_coords());
+        assert_eq!(stillwater.neighbors[3], set);
 
-        let set = set!(
-            kretikaa.get_coords(),
-            suvfoto.get_coords(),
-            saarinen.get_coords(),
-            celepina.get_coords(),
-            zivije.get_coords(),
-            valhalla.get_coords(),
-            pysadi.get_coords()
-        );
-        assert_eq!(vinorian.neighbors[3], set);
+        // Cinder cannot refuel, so it is nobody's neighbor and has none.
+        let set = HashSet::new();
+        for jump in 1..=3 {
+            assert_eq!(cinder.neighbors[jump], set);
+            assert_eq!(threshold.neighbors[jump], set);
+        }
+        for world in coords_to_world.values() {
+            for jump in 1..=3 {
+                assert!(!world.neighbors[jump].contains(&cinder.get_coords()));
+            }
+        }
 
         Ok(())
     }
 
     #[rstest]
-    fn test_navigable_distance(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_navigable_distance(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
         }
         let mut sorted_coords: Vec<Coords>;
         sorted_coords = coords_to_world.keys().cloned().collect();
         sorted_coords.sort();
-        assert_eq!(sorted_coords.len(), 825);
+        assert_eq!(sorted_coords.len(), 31);
         for (ii, coords) in sorted_coords.iter_mut().enumerate() {
             let world = coords_to_world.get_mut(coords).unwrap();
             world.index = Some(ii);
         }
-        let (dist2, _) =
-            populate_navigable_distances(&sorted_coords, &coords_to_world, 2, false, ALG);
-        let (dist3, _) =
-            populate_navigable_distances(&sorted_coords, &coords_to_world, 3, false, ALG);
-
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let ldd = htw!(spin, 3010, coords_to_world);
-        let natoko = htw!(spin, 3209, coords_to_world);
-        let vinorian = htw!(spin, 3111, coords_to_world);
-        let margesi = htw!(spin, 3212, coords_to_world);
-        let corfu = htw!(spin, 2602, coords_to_world);
-        let andor = htw!(spin, 0236, coords_to_world);
-        let candory = htw!(spin, 0336, coords_to_world);
-        let reno = htw!(spin, 0102, coords_to_world);
-        let regina = htw!(spin, 1910, coords_to_world);
-        let mongo = htw!(spin, 1204, coords_to_world);
-        let collace = htw!(spin, 1237, coords_to_world);
-        let pavanne = htw!(spin, 2905, coords_to_world);
-        let raweh = htw!(spin, 0139, coords_to_world);
-        let javan = htw!(dene, 2131, coords_to_world);
-        let salaam = htw!(dene, 3213, coords_to_world);
-
-        assert_eq!(aramis.navigable_distance(aramis, &dist2), 0);
-        assert_eq!(aramis.navigable_distance(aramis, &dist3), 0);
-        assert_eq!(aramis.navigable_distance(ldd, &dist2), 1);
-        assert_eq!(aramis.navigable_distance(ldd, &dist3), 1);
-        assert_eq!(aramis.navigable_distance(vinorian, &dist2), 1);
-        assert_eq!(aramis.navigable_distance(vinorian, &dist3), 1);
-        assert_eq!(aramis.navigable_distance(corfu, &dist2), 16);
-        assert_eq!(aramis.navigable_distance(corfu, &dist3), 13);
-        assert_eq!(aramis.navigable_distance(andor, &dist2), INFINITY);
-        assert_eq!(aramis.navigable_distance(andor, &dist3), 45);
-        assert_eq!(aramis.navigable_distance(margesi, &dist2), 3);
-        assert_eq!(aramis.navigable_distance(pavanne, &dist2), 6);
-        assert_eq!(aramis.navigable_distance(regina, &dist2), 12);
-        assert_eq!(aramis.navigable_distance(mongo, &dist2), 22);
-        assert_eq!(aramis.navigable_distance(collace, &dist2), 37);
-        assert_eq!(reno.navigable_distance(javan, &dist2), 61);
-        assert_eq!(andor.navigable_distance(candory, &dist2), INFINITY);
-        assert_eq!(candory.navigable_distance(andor, &dist2), INFINITY);
-        assert_eq!(ldd.navigable_distance(natoko, &dist2), 2);
-        assert_eq!(collace.navigable_distance(salaam, &dist2), 59);
-        assert_eq!(raweh.navigable_distance(salaam, &dist2), 70);
-
-        Ok(())
-    }
-
-    #[rstest]
-    fn test_navigable_path(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+        let dist2 = populate_navigable_distances(
+            &sorted_coords,
+            &coords_to_world,
+            2,
+            false,
+            &NO_SCENARIO,
+            ALG,
+        )?;
+        let dist3 = populate_navigable_distances(
+            &sorted_coords,
+            &coords_to_world,
+            3,
+            false,
+            &NO_SCENARIO,
+            ALG,
+        )?;
+
+        let threshold = htw!(prov, "0101", coords_to_world);
+        let waypoint = htw!(prov, "0420", coords_to_world);
+        let kindle = htw!(prov, "0622", coords_to_world);
+        let refuge = htw!(prov, "1021", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let cobble = htw!(prov, "2703", coords_to_world);
+        let oxbow = htw!(prov, "2805", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let marrow = htw!(prov, "3008", coords_to_world);
+        let flint = htw!(prov, "3006", coords_to_world);
+        let cinder = htw!(prov, "3106", coords_to_world);
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let hollow_oak = htw!(mock, "0205", coords_to_world);
+        let sable = htw!(mock, "0407", coords_to_world);
+        let lee_shore = htw!(mock, "0912", coords_to_world);
+
+        assert_eq!(keystone.navigable_distance(keystone, &dist2), 0);
+        assert_eq!(keystone.navigable_distance(keystone, &dist3), 0);
+        assert_eq!(keystone.navigable_distance(cobble, &dist2), 1);
+        assert_eq!(keystone.navigable_distance(cobble, &dist3), 1);
+        assert_eq!(keystone.navigable_distance(oxbow, &dist2), 5);
+        assert_eq!(keystone.navigable_distance(oxbow, &dist3), 4);
+        // Along the xboat route, even though it is longer than jump-3
+        assert_eq!(keystone.navigable_distance(brimstone, &dist2), 4);
+        assert_eq!(keystone.navigable_distance(brimstone, &dist3), 4);
+        assert_eq!(keystone.navigable_distance(stillwater, &dist2), 7);
+        assert_eq!(keystone.navigable_distance(sable, &dist2), 13);
+        assert_eq!(sable.navigable_distance(keystone, &dist2), 13);
+        assert_eq!(keystone.navigable_distance(lee_shore, &dist2), 20);
+        assert_eq!(brimstone.navigable_distance(marrow, &dist2), 6);
+        assert_eq!(brimstone.navigable_distance(marrow, &dist3), 5);
+        assert_eq!(flint.navigable_distance(hollow_oak, &dist2), 10);
+        assert_eq!(flint.navigable_distance(hollow_oak, &dist3), 9);
+        assert_eq!(keystone.navigable_distance(cinder, &dist2), INFINITY);
+        assert_eq!(keystone.navigable_distance(cinder, &dist3), INFINITY);
+        assert_eq!(waypoint.navigable_distance(refuge, &dist2), INFINITY);
+        assert_eq!(waypoint.navigable_distance(refuge, &dist3), 6);
+        assert_eq!(kindle.navigable_distance(refuge, &dist2), INFINITY);
+        assert_eq!(kindle.navigable_distance(refuge, &dist3), 5);
+        assert_eq!(threshold.navigable_distance(keystone, &dist3), INFINITY);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_navigable_path(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
         }
         let mut sorted_coords: Vec<Coords>;
         sorted_coords = coords_to_world.keys().cloned().collect();
-        assert_eq!(sorted_coords.len(), 825);
+        assert_eq!(sorted_coords.len(), 31);
         sorted_coords.sort();
         for (ii, coords) in sorted_coords.iter_mut().enumerate() {
             let world = coords_to_world.get_mut(coords).unwrap();
             world.index = Some(ii);
         }
-        let (dist2, pred2) =
-            populate_navigable_distances(&sorted_coords, &coords_to_world, 2, false, ALG);
-        let (dist3, pred3) =
-            populate_navigable_distances(&sorted_coords, &coords_to_world, 3, false, ALG);
-
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let ldd = htw!(spin, 3010, coords_to_world);
-        let vinorian = htw!(spin, 3111, coords_to_world);
-        let corfu = htw!(spin, 2602, coords_to_world);
-        let andor = htw!(spin, 0236, coords_to_world);
-        let candory = htw!(spin, 0336, coords_to_world);
-        let reno = htw!(spin, 0102, coords_to_world);
-        let mongo = htw!(spin, 1204, coords_to_world);
-        let collace = htw!(spin, 1237, coords_to_world);
-        let javan = htw!(dene, 2131, coords_to_world);
-        let pysadi = htw!(spin, 3008, coords_to_world);
-        let lewis = htw!(spin, 3107, coords_to_world);
-        let yebab = htw!(spin, 3002, coords_to_world);
-        let lablon = htw!(spin, 2701, coords_to_world);
-        let violante = htw!(spin, 2708, coords_to_world);
-        let focaline = htw!(spin, 2607, coords_to_world);
-        let moughas = htw!(spin, 2406, coords_to_world);
-        let enope = htw!(spin, 2205, coords_to_world);
-        let becks_world = htw!(spin, 2204, coords_to_world);
-        let yorbund = htw!(spin, 2303, coords_to_world);
-        let heya = htw!(spin, 2402, coords_to_world);
-        let zila = htw!(spin, 2908, coords_to_world);
-        let zykoca = htw!(spin, 3004, coords_to_world);
-        let feri = htw!(spin, 2005, coords_to_world);
-        let uakye = htw!(spin, 1805, coords_to_world);
-        let efate = htw!(spin, 1705, coords_to_world);
-        let lysen = htw!(spin, 1307, coords_to_world);
-        let nakege = htw!(spin, 1305, coords_to_world);
-
-        let nutema = htw!(spin, 3112, coords_to_world);
-        let celepina = htw!(spin, 2913, coords_to_world);
-        let jae_tellona = htw!(spin, 2814, coords_to_world);
-        let rhylanor = htw!(spin, 2716, coords_to_world);
-        let equus = htw!(spin, 2417, coords_to_world);
-        let ivendo = htw!(spin, 2319, coords_to_world);
-        let quiru = htw!(spin, 2321, coords_to_world);
-        let resten = htw!(spin, 2323, coords_to_world);
-        let lunion = htw!(spin, 2124, coords_to_world);
-        let derchon = htw!(spin, 2024, coords_to_world);
-        let zaibon = htw!(spin, 1825, coords_to_world);
-        let iron = htw!(spin, 1626, coords_to_world);
-        let mithril = htw!(spin, 1628, coords_to_world);
-        let steel = htw!(spin, 1529, coords_to_world);
-        let dawnworld = htw!(spin, 1531, coords_to_world);
-        let forine = htw!(spin, 1533, coords_to_world);
-        let tarkine = htw!(spin, 1434, coords_to_world);
-        let talos = htw!(spin, 1436, coords_to_world);
-
-        let path = aramis
-            .navigable_path(aramis, &sorted_coords, &coords_to_world, &dist2, &pred2)
-            .unwrap();
-        assert_eq!(path.len(), 1);
-        assert_eq!(path[0], aramis.get_coords());
+        let dist2 = populate_navigable_distances(
+            &sorted_coords,
+            &coords_to_world,
+            2,
+            false,
+            &NO_SCENARIO,
+            ALG,
+        )?;
+        let dist3 = populate_navigable_distances(
+            &sorted_coords,
+            &coords_to_world,
+            3,
+            false,
+            &NO_SCENARIO,
+            ALG,
+        )?;
 
-        let path = aramis
-            .navigable_path(aramis, &sorted_coords, &coords_to_world, &dist3, &pred3)
+        let threshold = htw!(prov, "0101", coords_to_world);
+        let waypoint = htw!(prov, "0420", coords_to_world);
+        let tinderbox = htw!(prov, "0721", coords_to_world);
+        let refuge = htw!(prov, "1021", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let cobble = htw!(prov, "2703", coords_to_world);
+        let tallow = htw!(prov, "2904", coords_to_world);
+        let gristle = htw!(prov, "2905", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let flint = htw!(prov, "3006", coords_to_world);
+        let marrow = htw!(prov, "3008", coords_to_world);
+        let cinder = htw!(prov, "3106", coords_to_world);
+        let quill = htw!(prov, "3209", coords_to_world);
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let ford = htw!(mock, "0109", coords_to_world);
+        let lantern = htw!(mock, "0309", coords_to_world);
+        let sable = htw!(mock, "0407", coords_to_world);
+        let farpoint = htw!(mock, "0811", coords_to_world);
+        let lee_shore = htw!(mock, "0912", coords_to_world);
+
+        let path = keystone
+            .navigable_path(keystone, &sorted_coords, &dist2)
             .unwrap();
         assert_eq!(path.len(), 1);
-        assert_eq!(path[0], aramis.get_coords());
+        assert_eq!(path[0], keystone.get_coords());
 
-        let path = aramis
-            .navigable_path(ldd, &sorted_coords, &coords_to_world, &dist2, &pred2)
+        let path = keystone
+            .navigable_path(keystone, &sorted_coords, &dist3)
             .unwrap();
-        assert_eq!(path.len(), 2);
-        assert_eq!(path[0], aramis.get_coords());
-        assert_eq!(path[1], ldd.get_coords());
-
-        let path = aramis
-            .navigable_path(ldd, &sorted_coords, &coords_to_world, &dist3, &pred3)
-            .unwrap();
-        assert_eq!(path.len(), 2);
-        assert_eq!(path[0], aramis.get_coords());
-        assert_eq!(path[1], ldd.get_coords());
+        assert_eq!(path.len(), 1);
+        assert_eq!(path[0], keystone.get_coords());
 
-        let path = aramis
-            .navigable_path(vinorian, &sorted_coords, &coords_to_world, &dist2, &pred2)
+        let path = keystone
+            .navigable_path(cobble, &sorted_coords, &dist2)
             .unwrap();
-        assert_eq!(path.len(), 2);
-        assert_eq!(path[0], aramis.get_coords());
-        assert_eq!(path[1], vinorian.get_coords());
+        assert_eq!(path, vec![keystone.get_coords(), cobble.get_coords()]);
 
-        let path = aramis
-            .navigable_path(vinorian, &sorted_coords, &coords_to_world, &dist3, &pred3)
+        let path = keystone
+            .navigable_path(cobble, &sorted_coords, &dist3)
             .unwrap();
-        assert_eq!(path.len(), 2);
-        assert_eq!(path[0], aramis.get_coords());
-        assert_eq!(path[1], vinorian.get_coords());
+        assert_eq!(path, vec![keystone.get_coords(), cobble.get_coords()]);
 
-        let path = aramis
-            .navigable_path(corfu, &sorted_coords, &coords_to_world, &dist2, &pred2)
+        let path = keystone
+            .navigable_path(sable, &sorted_coords, &dist2)
             .unwrap();
-        assert_eq!(path.len(), 11);
-        for coords in &path {
-            println!("{}", coords_to_world.get(&coords).unwrap().name);
-        }
         assert_eq!(
             path,
             vec![
-                aramis.get_coords(),
-                pysadi.get_coords(),
-                zila.get_coords(),
-                violante.get_coords(),
-                focaline.get_coords(),
-                moughas.get_coords(),
-                enope.get_coords(),
-                becks_world.get_coords(),
-                yorbund.get_coords(),
-                heya.get_coords(),
-                corfu.get_coords(),
+                keystone.get_coords(),
+                brimstone.get_coords(),
+                stillwater.get_coords(),
+                sable.get_coords(),
             ]
         );
 
-        let path = aramis
-            .navigable_path(corfu, &sorted_coords, &coords_to_world, &dist3, &pred3)
+        let path = keystone
+            .navigable_path(lee_shore, &sorted_coords, &dist3)
             .unwrap();
-        for coords in &path {
-            println!("{}", coords_to_world.get(&coords).unwrap().name);
-        }
-        assert_eq!(path.len(), 6);
         assert_eq!(
             path,
             vec![
-                aramis.get_coords(),
-                lewis.get_coords(),
-                zykoca.get_coords(),
-                yebab.get_coords(),
-                lablon.get_coords(),
-                corfu.get_coords(),
+                keystone.get_coords(),
+                brimstone.get_coords(),
+                stillwater.get_coords(),
+                sable.get_coords(),
+                farpoint.get_coords(),
+                lee_shore.get_coords(),
             ]
         );
 
-        let path = aramis
-            .navigable_path(mongo, &sorted_coords, &coords_to_world, &dist2, &pred2)
+        let path = keystone
+            .navigable_path(lantern, &sorted_coords, &dist2)
             .unwrap();
-        for coords in &path {
-            println!("{}", coords_to_world.get(&coords).unwrap().name);
-        }
-        assert_eq!(path.len(), 13);
         assert_eq!(
             path,
             vec![
-                aramis.get_coords(),
-                pysadi.get_coords(),
-                zila.get_coords(),
-                violante.get_coords(),
-                focaline.get_coords(),
-                moughas.get_coords(),
-                enope.get_coords(),
-                feri.get_coords(),
-                uakye.get_coords(),
-                efate.get_coords(),
-                lysen.get_coords(),
-                nakege.get_coords(),
-                mongo.get_coords(),
+                keystone.get_coords(),
+                cobble.get_coords(),
+                tallow.get_coords(),
+                gristle.get_coords(),
+                flint.get_coords(),
+                marrow.get_coords(),
+                quill.get_coords(),
+                ford.get_coords(),
+                lantern.get_coords(),
             ]
         );
 
-        let path = aramis
-            .navigable_path(collace, &sorted_coords, &coords_to_world, &dist2, &pred2)
+        let path = keystone
+            .navigable_path(lantern, &sorted_coords, &dist3)
             .unwrap();
-        for coords in &path {
-            println!("{}", coords_to_world.get(&coords).unwrap().name);
-        }
-        assert_eq!(path.len(), 20);
         assert_eq!(
             path,
             vec![
-                aramis.get_coords(),
-                nutema.get_coords(),
-                celepina.get_coords(),
-                jae_tellona.get_coords(),
-                rhylanor.get_coords(),
-                equus.get_coords(),
-                ivendo.get_coords(),
-                quiru.get_coords(),
-                resten.get_coords(),
-                lunion.get_coords(),
-                derchon.get_coords(),
-                zaibon.get_coords(),
-                iron.get_coords(),
-                mithril.get_coords(),
-                steel.get_coords(),
-                dawnworld.get_coords(),
-                forine.get_coords(),
-                tarkine.get_coords(),
-                talos.get_coords(),
-                collace.get_coords(),
+                keystone.get_coords(),
+                tallow.get_coords(),
+                flint.get_coords(),
+                marrow.get_coords(),
+                ford.get_coords(),
+                lantern.get_coords(),
             ]
         );
 
-        let path = reno
-            .navigable_path(javan, &sorted_coords, &coords_to_world, &dist2, &pred2)
-            .unwrap();
-        for coords in &path {
-            println!("{}", coords_to_world.get(&coords).unwrap().name);
-        }
-        assert_eq!(path.len(), 33);
-
-        let path_opt =
-            andor.navigable_path(candory, &sorted_coords, &coords_to_world, &dist2, &pred2);
+        let path_opt = keystone.navigable_path(cinder, &sorted_coords, &dist3);
         assert_eq!(path_opt, None);
 
-        let path_opt =
-            candory.navigable_path(andor, &sorted_coords, &coords_to_world, &dist2, &pred2);
+        let path_opt = threshold.navigable_path(keystone, &sorted_coords, &dist3);
         assert_eq!(path_opt, None);
 
-        let path_opt =
-            aramis.navigable_path(andor, &sorted_coords, &coords_to_world, &dist2, &pred2);
+        let path_opt = waypoint.navigable_path(refuge, &sorted_coords, &dist2);
         assert_eq!(path_opt, None);
 
-        let path = aramis
-            .navigable_path(andor, &sorted_coords, &coords_to_world, &dist3, &pred3)
+        let path = waypoint
+            .navigable_path(refuge, &sorted_coords, &dist3)
             .unwrap();
-        for coords in &path {
-            println!("{}", coords_to_world.get(&coords).unwrap().name);
-        }
-        assert_eq!(path.len(), 17);
+        assert_eq!(
+            path,
+            vec![
+                waypoint.get_coords(),
+                tinderbox.get_coords(),
+                refuge.get_coords(),
+            ]
+        );
 
         Ok(())
     }
 
     #[rstest]
-    fn test_btn(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_btn(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
         }
         let mut sorted_coords: Vec<Coords>;
         sorted_coords = coords_to_world.keys().cloned().collect();
         sorted_coords.sort();
-        assert_eq!(sorted_coords.len(), 825);
+        assert_eq!(sorted_coords.len(), 31);
         for (ii, coords) in sorted_coords.iter_mut().enumerate() {
             let world = coords_to_world.get_mut(coords).unwrap();
             world.index = Some(ii);
         }
-        let (dist2, _) =
-            populate_navigable_distances(&sorted_coords, &coords_to_world, 2, false, ALG);
-
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let ldd = htw!(spin, 3010, coords_to_world);
-        let natoko = htw!(spin, 3209, coords_to_world);
-        let vinorian = htw!(spin, 3111, coords_to_world);
-        let corfu = htw!(spin, 2602, coords_to_world);
-        let andor = htw!(spin, 0236, coords_to_world);
-        let candory = htw!(spin, 0336, coords_to_world);
-        let regina = htw!(spin, 1910, coords_to_world);
-        let reacher = htw!(spin, 3210, coords_to_world);
-        let nutema = htw!(spin, 3112, coords_to_world);
-        let margesi = htw!(spin, 3212, coords_to_world);
-        let saarinen = htw!(dene, 0113, coords_to_world);
-        let lablon = htw!(spin, 2701, coords_to_world);
-        let junidy = htw!(spin, 3202, coords_to_world);
-        let marz = htw!(dene, 0201, coords_to_world);
-
-        assert_eq!(aramis.btn(ldd, &dist2, false, false), 8.0);
-        assert_eq!(aramis.btn(natoko, &dist2, false, false), 6.5);
-        assert_eq!(aramis.btn(reacher, &dist2, false, false), 7.0);
-        assert_eq!(aramis.btn(vinorian, &dist2, false, false), 8.0);
-        assert_eq!(aramis.btn(corfu, &dist2, false, false), 5.5);
-        assert_eq!(aramis.btn(lablon, &dist2, false, false), 6.0);
-        assert_eq!(aramis.btn(junidy, &dist2, false, false), 7.5);
-        assert_eq!(aramis.btn(marz, &dist2, false, false), 7.5);
-        assert_eq!(aramis.btn(regina, &dist2, false, false), 7.0);
-        assert_eq!(ldd.btn(aramis, &dist2, false, false), 8.0);
-        assert_eq!(ldd.btn(natoko, &dist2, false, false), 6.0);
-        assert_eq!(ldd.btn(reacher, &dist2, false, false), 6.5);
-        assert_eq!(ldd.btn(nutema, &dist2, false, false), 6.0);
-        assert_eq!(ldd.btn(margesi, &dist2, false, false), 6.0);
-        assert_eq!(ldd.btn(saarinen, &dist2, false, false), 5.5);
-        assert_eq!(natoko.btn(reacher, &dist2, false, false), 5.5);
-        assert_eq!(vinorian.btn(nutema, &dist2, false, false), 6.5);
-        assert_eq!(nutema.btn(margesi, &dist2, false, false), 5.5);
-        assert_eq!(margesi.btn(saarinen, &dist2, false, false), 5.5);
-        assert_eq!(aramis.btn(andor, &dist2, false, false), 0.0);
-        assert_eq!(andor.btn(candory, &dist2, false, false), 0.0);
-        Ok(())
-    }
-
-    #[rstest]
-    fn test_passenger_btn(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+        let dist2 = populate_navigable_distances(
+            &sorted_coords,
+            &coords_to_world,
+            2,
+            false,
+            &NO_SCENARIO,
+            ALG,
+        )?;
+        let dist3 = populate_navigable_distances(
+            &sorted_coords,
+            &coords_to_world,
+            3,
+            false,
+            &NO_SCENARIO,
+            ALG,
+        )?;
+
+        let waypoint = htw!(prov, "0420", coords_to_world);
+        let refuge = htw!(prov, "1021", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let cobble = htw!(prov, "2703", coords_to_world);
+        let tallow = htw!(prov, "2904", coords_to_world);
+        let gristle = htw!(prov, "2905", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let cinder = htw!(prov, "3106", coords_to_world);
+        let verge = htw!(prov, "3202", coords_to_world);
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let ford = htw!(mock, "0109", coords_to_world);
+        let tinder = htw!(mock, "0303", coords_to_world);
+        let lantern = htw!(mock, "0309", coords_to_world);
+        let sable = htw!(mock, "0407", coords_to_world);
+        let lee_shore = htw!(mock, "0912", coords_to_world);
+
+        assert_eq!(keystone.btn(cobble, &dist2, false, &*FT_RULES), 8.5);
+        assert_eq!(cobble.btn(keystone, &dist2, false, &*FT_RULES), 8.5);
+        assert_eq!(keystone.btn(tallow, &dist2, false, &*FT_RULES), 8.0);
+        assert_eq!(keystone.btn(brimstone, &dist2, false, &*FT_RULES), 10.5);
+        assert_eq!(keystone.btn(stillwater, &dist2, false, &*FT_RULES), 10.5);
+        assert_eq!(keystone.btn(sable, &dist2, false, &*FT_RULES), 11.0);
+        // ImDd and CsIm are both in the Imperium group, so no allegiance penalty
+        assert_eq!(keystone.btn(verge, &dist2, false, &*FT_RULES), 10.5);
+        assert_eq!(keystone.btn(tinder, &dist2, false, &*FT_RULES), 4.0);
+        assert_eq!(keystone.btn(lee_shore, &dist2, false, &*FT_RULES), 6.5);
+        assert_eq!(brimstone.btn(verge, &dist2, false, &*FT_RULES), 10.5);
+        assert_eq!(stillwater.btn(sable, &dist2, false, &*FT_RULES), 10.5);
+        assert_eq!(tallow.btn(ford, &dist2, false, &*FT_RULES), 7.5);
+        assert_eq!(ford.btn(lantern, &dist2, false, &*FT_RULES), 9.0);
+        assert_eq!(ford.btn(sable, &dist2, false, &*FT_RULES), 10.0);
+        assert_eq!(gristle.btn(tinder, &dist2, false, &*FT_RULES), 0.0);
+        assert_eq!(keystone.btn(cinder, &dist2, false, &*FT_RULES), 0.0);
+        assert_eq!(waypoint.btn(refuge, &dist2, false, &*FT_RULES), 0.0);
+        assert_eq!(waypoint.btn(refuge, &dist3, false, &*FT_RULES), 6.5);
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_passenger_btn(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
         }
         let mut sorted_coords: Vec<Coords>;
         sorted_coords = coords_to_world.keys().cloned().collect();
         sorted_coords.sort();
-        assert_eq!(sorted_coords.len(), 825);
+        assert_eq!(sorted_coords.len(), 31);
         for (ii, coords) in sorted_coords.iter_mut().enumerate() {
             let world = coords_to_world.get_mut(coords).unwrap();
             world.index = Some(ii);
         }
-        let (dist2, _) =
-            populate_navigable_distances(&sorted_coords, &coords_to_world, 2, false, ALG);
-
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let ldd = htw!(spin, 3010, coords_to_world);
-        let natoko = htw!(spin, 3209, coords_to_world);
-        let vinorian = htw!(spin, 3111, coords_to_world);
-        let corfu = htw!(spin, 2602, coords_to_world);
-        let andor = htw!(spin, 0236, coords_to_world);
-        let candory = htw!(spin, 0336, coords_to_world);
-        let regina = htw!(spin, 1910, coords_to_world);
-        let reacher = htw!(spin, 3210, coords_to_world);
-        let nutema = htw!(spin, 3112, coords_to_world);
-        let margesi = htw!(spin, 3212, coords_to_world);
-        let saarinen = htw!(dene, 0113, coords_to_world);
-        let lablon = htw!(spin, 2701, coords_to_world);
-        let junidy = htw!(spin, 3202, coords_to_world);
-        let marz = htw!(dene, 0201, coords_to_world);
-        let mora = htw!(spin, 3124, coords_to_world);
-
-        assert_eq!(aramis.btn(ldd, &dist2, true, false), 8.5);
-        assert_eq!(aramis.btn(natoko, &dist2, true, false), 7.0);
-        assert_eq!(aramis.btn(reacher, &dist2, true, false), 7.5);
-        assert_eq!(aramis.btn(vinorian, &dist2, true, false), 8.5);
-        assert_eq!(aramis.btn(corfu, &dist2, true, false), 6.0);
-        assert_eq!(aramis.btn(lablon, &dist2, true, false), 6.5);
-        assert_eq!(aramis.btn(junidy, &dist2, true, false), 8.0);
-        assert_eq!(aramis.btn(marz, &dist2, true, false), 8.0);
-        assert_eq!(aramis.btn(regina, &dist2, true, false), 8.5);
-        assert_eq!(aramis.btn(mora, &dist2, true, false), 9.0);
-        assert_eq!(ldd.btn(aramis, &dist2, true, false), 8.5);
-        assert_eq!(ldd.btn(natoko, &dist2, true, false), 6.0);
-        assert_eq!(ldd.btn(reacher, &dist2, true, false), 6.5);
-        assert_eq!(ldd.btn(nutema, &dist2, true, false), 6.0);
-        assert_eq!(ldd.btn(margesi, &dist2, true, false), 6.0);
-        assert_eq!(ldd.btn(saarinen, &dist2, true, false), 5.5);
-        assert_eq!(natoko.btn(reacher, &dist2, true, false), 5.5);
-        assert_eq!(vinorian.btn(nutema, &dist2, true, false), 6.5);
-        assert_eq!(nutema.btn(margesi, &dist2, true, false), 5.5);
-        assert_eq!(margesi.btn(saarinen, &dist2, true, false), 5.5);
-        assert_eq!(aramis.btn(andor, &dist2, true, false), 0.0);
-        assert_eq!(andor.btn(candory, &dist2, true, false), 0.0);
+        let dist2 = populate_navigable_distances(
+            &sorted_coords,
+            &coords_to_world,
+            2,
+            false,
+            &NO_SCENARIO,
+            ALG,
+        )?;
+
+        let waypoint = htw!(prov, "0420", coords_to_world);
+        let ember = htw!(prov, "0521", coords_to_world);
+        let tinderbox = htw!(prov, "0721", coords_to_world);
+        let refuge = htw!(prov, "1021", coords_to_world);
+        let outpost = htw!(prov, "1221", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let cobble = htw!(prov, "2703", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let cinder = htw!(prov, "3106", coords_to_world);
+        let verge = htw!(prov, "3202", coords_to_world);
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let drift = htw!(mock, "0204", coords_to_world);
+        let hollow_oak = htw!(mock, "0205", coords_to_world);
+        let tinder = htw!(mock, "0303", coords_to_world);
+        let sable = htw!(mock, "0407", coords_to_world);
+        let farpoint = htw!(mock, "0811", coords_to_world);
+
+        assert_eq!(keystone.btn(cobble, &dist2, true, &*FT_RULES), 8.5);
+        assert_eq!(keystone.btn(verge, &dist2, true, &*FT_RULES), 10.5);
+        assert_eq!(keystone.btn(sable, &dist2, true, &*FT_RULES), 11.5);
+        assert_eq!(keystone.btn(hollow_oak, &dist2, true, &*FT_RULES), 9.0);
+        assert_eq!(keystone.btn(farpoint, &dist2, true, &*FT_RULES), 9.5);
+        assert_eq!(brimstone.btn(stillwater, &dist2, true, &*FT_RULES), 10.0);
+        assert_eq!(waypoint.btn(ember, &dist2, true, &*FT_RULES), 8.0);
+        assert_eq!(ember.btn(tinderbox, &dist2, true, &*FT_RULES), 7.0);
+        assert_eq!(refuge.btn(outpost, &dist2, true, &*FT_RULES), 7.0);
+        assert_eq!(drift.btn(hollow_oak, &dist2, true, &*FT_RULES), 7.5);
+        assert_eq!(stillwater.btn(tinder, &dist2, true, &*FT_RULES), 4.0);
+        assert_eq!(keystone.btn(cinder, &dist2, true, &*FT_RULES), 0.0);
+        assert_eq!(waypoint.btn(refuge, &dist2, true, &*FT_RULES), 0.0);
         Ok(())
     }
 
@@ -1879,32 +2953,34 @@ mod tests {
     }
 
     #[rstest]
-    fn test_populate_trade_routes(
-        data_dir: &PathBuf,
-        download: &Result<Vec<String>>,
-    ) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_populate_trade_routes(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        let gvur = Sector::new(&data_dir, "Gvurrdon".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
-        location_to_sector.insert(gvur.location, gvur.clone());
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
         }
         let mut sorted_coords: Vec<Coords>;
         sorted_coords = coords_to_world.keys().cloned().collect();
@@ -1921,13 +2997,17 @@ mod tests {
         max_jumps.insert(Main, 3);
         max_jumps.insert(Major, 3);
         let all_jumps: HashSet<u64> = max_jumps.values().cloned().collect();
-        let mut dists: HashMap<u64, Array2<u16>> = HashMap::new();
-        let mut preds: HashMap<u64, Array2<u16>> = HashMap::new();
+        let mut dists: HashMap<u64, ShortestPaths> = HashMap::new();
         for jump in all_jumps.iter() {
-            let (dist, pred) =
-                populate_navigable_distances(&sorted_coords, &coords_to_world, *jump, false, ALG);
+            let dist = populate_navigable_distances(
+                &sorted_coords,
+                &coords_to_world,
+                *jump,
+                false,
+                &NO_SCENARIO,
+                ALG,
+            )?;
             dists.insert(*jump, dist);
-            preds.insert(*jump, pred);
         }
 
         populate_trade_routes(
@@ -1937,155 +3017,607 @@ mod tests {
             false,
             &max_jumps,
             &dists,
-            &preds,
-            false,
+            &*FT_RULES,
+        );
+
+        let threshold = htw!(prov, "0101", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let cobble = htw!(prov, "2703", coords_to_world);
+        let oxbow = htw!(prov, "2805", coords_to_world);
+        let tallow = htw!(prov, "2904", coords_to_world);
+        let gristle = htw!(prov, "2905", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let flint = htw!(prov, "3006", coords_to_world);
+        let verge = htw!(prov, "3202", coords_to_world);
+        let stillwater = htw!(mock, "0103", coords_to_world);
+        let ford = htw!(mock, "0109", coords_to_world);
+        let drift = htw!(mock, "0204", coords_to_world);
+        let hollow_oak = htw!(mock, "0205", coords_to_world);
+        let bramble = htw!(mock, "0306", coords_to_world);
+        let lantern = htw!(mock, "0309", coords_to_world);
+        let sable = htw!(mock, "0407", coords_to_world);
+        let farpoint = htw!(mock, "0811", coords_to_world);
+
+        let empty = HashSet::new();
+
+        assert_eq!(keystone.major_routes, set!(brimstone.get_coords()));
+        assert_eq!(keystone.main_routes, empty);
+        assert_eq!(
+            keystone.intermediate_routes,
+            set!(cobble.get_coords(), tallow.get_coords())
         );
+        assert_eq!(keystone.feeder_routes, empty);
+        assert_eq!(keystone.minor_routes, empty);
 
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let mora = htw!(spin, 3124, coords_to_world);
-        let jesedipere = htw!(spin, 3001, coords_to_world);
-        let rruthaekuksu = htw!(gvur, 2840, coords_to_world);
-
-        fn set_to_worlds(
-            set: &HashSet<Coords>,
-            coords_to_world: &HashMap<Coords, World>,
-        ) -> Vec<String> {
-            set.iter()
-                .map(|&c| coords_to_world.get(&c).unwrap().name.clone())
-                .collect()
-        }
-
-        println!(
-            "aramis major {:?}",
-            set_to_worlds(&aramis.major_routes, &coords_to_world)
-        );
-        println!(
-            "aramis main {:?}",
-            set_to_worlds(&aramis.main_routes, &coords_to_world)
-        );
-        println!(
-            "aramis intermediate {:?}",
-            set_to_worlds(&aramis.intermediate_routes, &coords_to_world)
+        assert_eq!(
+            brimstone.major_routes,
+            set!(keystone.get_coords(), stillwater.get_coords())
         );
-        println!(
-            "aramis feeder {:?}",
-            set_to_worlds(&aramis.feeder_routes, &coords_to_world)
+        assert_eq!(
+            brimstone.main_routes,
+            set!(flint.get_coords(), verge.get_coords())
         );
-        println!(
-            "aramis minor {:?}",
-            set_to_worlds(&aramis.minor_routes, &coords_to_world)
+        assert_eq!(brimstone.intermediate_routes, set!(oxbow.get_coords()));
+        assert_eq!(
+            brimstone.feeder_routes,
+            set!(gristle.get_coords(), tallow.get_coords())
         );
-        assert_eq!(aramis.major_routes.len(), 0);
-        assert_eq!(aramis.main_routes.len(), 0);
-        assert_eq!(aramis.intermediate_routes.len(), 5);
-        assert_eq!(aramis.feeder_routes.len(), 7);
-        assert_eq!(aramis.minor_routes.len(), 1);
+        assert_eq!(brimstone.minor_routes, empty);
 
-        println!(
-            "mora major {:?}",
-            set_to_worlds(&mora.major_routes, &coords_to_world)
-        );
-        println!(
-            "mora main {:?}",
-            set_to_worlds(&mora.main_routes, &coords_to_world)
-        );
-        println!(
-            "mora intermediate {:?}",
-            set_to_worlds(&mora.intermediate_routes, &coords_to_world)
+        assert_eq!(
+            stillwater.major_routes,
+            set!(brimstone.get_coords(), sable.get_coords())
         );
-        println!(
-            "mora feeder {:?}",
-            set_to_worlds(&mora.feeder_routes, &coords_to_world)
+        assert_eq!(stillwater.main_routes, set!(verge.get_coords()));
+        assert_eq!(stillwater.intermediate_routes, empty);
+        assert_eq!(stillwater.feeder_routes, set!(drift.get_coords()));
+        assert_eq!(stillwater.minor_routes, empty);
+
+        assert_eq!(sable.major_routes, set!(stillwater.get_coords()));
+        assert_eq!(sable.main_routes, set!(ford.get_coords()));
+        assert_eq!(
+            sable.intermediate_routes,
+            set!(farpoint.get_coords(), lantern.get_coords())
         );
-        println!(
-            "mora minor {:?}",
-            set_to_worlds(&mora.minor_routes, &coords_to_world)
+        assert_eq!(
+            sable.feeder_routes,
+            set!(bramble.get_coords(), hollow_oak.get_coords())
         );
-        assert_eq!(mora.major_routes.len(), 1);
-        assert_eq!(mora.main_routes.len(), 8);
-        assert_eq!(mora.intermediate_routes.len(), 4);
-        assert_eq!(mora.feeder_routes.len(), 1);
-        assert_eq!(mora.minor_routes.len(), 0);
+        assert_eq!(sable.minor_routes, empty);
 
-        println!(
-            "jesedipere major {:?}",
-            set_to_worlds(&jesedipere.major_routes, &coords_to_world)
+        assert_eq!(drift.major_routes, empty);
+        assert_eq!(drift.main_routes, empty);
+        assert_eq!(drift.intermediate_routes, empty);
+        assert_eq!(
+            drift.feeder_routes,
+            set!(hollow_oak.get_coords(), stillwater.get_coords())
         );
-        println!(
-            "jesedipere main {:?}",
-            set_to_worlds(&jesedipere.main_routes, &coords_to_world)
+        assert_eq!(drift.minor_routes, set!(bramble.get_coords()));
+
+        assert_eq!(threshold.major_routes, empty);
+        assert_eq!(threshold.main_routes, empty);
+        assert_eq!(threshold.intermediate_routes, empty);
+        assert_eq!(threshold.feeder_routes, empty);
+        assert_eq!(threshold.minor_routes, empty);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_galaxy_builder(data_dir: &PathBuf) -> Result<()> {
+        let galaxy = Galaxy::builder()
+            .data_directory(data_dir)
+            .offline(true)
+            .sectors(["Prov", "Mockingbird"])
+            .build()?;
+        let sector_names: Vec<String> = galaxy
+            .sectors()
+            .iter()
+            .map(|sector| sector.name())
+            .collect();
+        assert_eq!(sector_names, vec!["Mockingbird", "Proving Ground"]);
+        let prov = galaxy.sector("Proving Ground").unwrap();
+        assert_eq!(prov.location(), (0, 0));
+        assert_eq!(prov.abbreviation(), "Prov");
+        assert_eq!(galaxy.worlds().count(), 31);
+        let keystone = galaxy.world("Proving Ground", "2602").unwrap();
+        assert_eq!(keystone.name(), "Keystone");
+        assert_eq!(keystone.sector_name(), "Proving Ground");
+        assert_eq!(keystone.hex(), "2602");
+        assert!(galaxy.world("Proving Ground", "0000").is_none());
+        assert!(galaxy.world("Nowhere", "0101").is_none());
+        assert!(galaxy.skipped_worlds().is_empty());
+        assert!(galaxy.lint()?.is_empty());
+
+        let err = Galaxy::builder()
+            .data_directory(data_dir)
+            .offline(true)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&GalaxyError::NoSectors));
+
+        let err = Galaxy::builder()
+            .data_directory(data_dir)
+            .offline(true)
+            .milieu("M0")
+            .sector("Mockingbird")
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&GalaxyError::MissingSectorData {
+                paths: vec![
+                    data_dir.join("Mockingbird.M0.sec"),
+                    data_dir.join("Mockingbird.M0.xml")
+                ]
+            })
         );
-        println!(
-            "jesedipere intermediate {:?}",
-            set_to_worlds(&jesedipere.intermediate_routes, &coords_to_world)
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_galaxy_builder_download(source: &FsSource) -> Result<()> {
+        let base_url = stand_in::serve(source.clone());
+        let temp_dir = tempdir()?;
+        let galaxy = Galaxy::builder()
+            .data_directory(temp_dir.path())
+            .base_url(&base_url)
+            .sector("Spin")
+            .build()?;
+        assert_eq!(
+            galaxy.world("Spinward Marches", "1910").unwrap().name(),
+            "Regina"
         );
-        println!(
-            "jesedipere feeder {:?}",
-            set_to_worlds(&jesedipere.feeder_routes, &coords_to_world)
+        assert!(temp_dir.path().join("Spinward Marches.sec").exists());
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_galaxy_builder_source(source: &FsSource) -> Result<()> {
+        let mut memory_source = MemorySource::new();
+        for sector_name in ["Proving Ground", "Mockingbird"] {
+            memory_source.insert(
+                sector_name,
+                source.sector_data(sector_name)?,
+                source.sector_metadata(sector_name)?,
+            );
+        }
+
+        // Without a universe, names have to be exact.
+        let galaxy = Galaxy::builder()
+            .source(memory_source.clone())
+            .sectors(["Proving Ground", "Mockingbird"])
+            .build()?;
+        assert_eq!(galaxy.worlds().count(), 31);
+        assert_eq!(
+            galaxy.world("Proving Ground", "2602").unwrap().name(),
+            "Keystone"
+        );
+        assert!(galaxy.lint()?.is_empty());
+        assert!(Galaxy::builder()
+            .source(memory_source.clone())
+            .sector("Prov")
+            .build()
+            .is_err());
+
+        // With one, they're resolved, and the data directory is never used.
+        memory_source.set_universe(source.universe()?);
+        let temp_dir = tempdir()?;
+        let data_dir = temp_dir.path().join("unused");
+        let galaxy = Galaxy::builder()
+            .data_directory(&data_dir)
+            .source(memory_source)
+            .sectors(["Prov", "mockingbird"])
+            .build()?;
+        let sector_names: Vec<&str> = galaxy
+            .sectors()
+            .iter()
+            .map(|sector| sector.name.as_str())
+            .collect();
+        assert_eq!(sector_names, vec!["Mockingbird", "Proving Ground"]);
+        assert!(!data_dir.exists());
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_trade_model(data_dir: &PathBuf) -> Result<()> {
+        let galaxy = Galaxy::builder()
+            .data_directory(data_dir)
+            .offline(true)
+            .sectors(["Proving Ground", "Mockingbird"])
+            .build()?;
+        let trade_map = TradeModel::new().algorithm(ALG).compute(galaxy)?;
+        let galaxy = trade_map.galaxy();
+        let keystone = galaxy.world("Proving Ground", "2602").unwrap();
+        let cobble = galaxy.world("Proving Ground", "2703").unwrap();
+        let tallow = galaxy.world("Proving Ground", "2904").unwrap();
+        let brimstone = galaxy.world("Proving Ground", "3003").unwrap();
+        let threshold = galaxy.world("Proving Ground", "0101").unwrap();
+
+        // Same as test_populate_trade_routes
+        assert_eq!(
+            trade_map.routes(keystone),
+            vec![
+                (Major, brimstone),
+                (Intermediate, cobble),
+                (Intermediate, tallow)
+            ]
         );
-        println!(
-            "jesedipere minor {:?}",
-            set_to_worlds(&jesedipere.minor_routes, &coords_to_world)
+        assert!(trade_map.routes(threshold).is_empty());
+        assert!(keystone.endpoint_trade_credits() > 0);
+
+        assert_eq!(trade_map.distance(keystone, keystone), Some(0));
+        assert_eq!(trade_map.distance(keystone, cobble), Some(1));
+        let path = trade_map.path(keystone, cobble).unwrap();
+        assert_eq!(path, vec![keystone, cobble]);
+        assert!(trade_map.btn(keystone, brimstone) >= trade_map.btn(keystone, cobble));
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_trade_model_rules(data_dir: &PathBuf) -> Result<()> {
+        let galaxy = Galaxy::builder()
+            .data_directory(data_dir)
+            .offline(true)
+            .sectors(["Proving Ground", "Mockingbird"])
+            .build()?;
+        let ft_map = TradeModel::new().algorithm(ALG).compute(galaxy.clone())?;
+        let iw_map = TradeModel::new()
+            .algorithm(ALG)
+            .rules(Ruleset::interstellar_wars())
+            .compute(galaxy.clone())?;
+        let mut house_rules = Ruleset::far_trader();
+        for credits in house_rules.dbtn_to_credits.iter_mut() {
+            *credits *= 2;
+        }
+        let house_map = TradeModel::new()
+            .algorithm(ALG)
+            .rules(house_rules)
+            .compute(galaxy)?;
+
+        let keystone = ft_map.galaxy().world("Proving Ground", "2602").unwrap();
+        let brimstone = ft_map.galaxy().world("Proving Ground", "3003").unwrap();
+        let wtn1 = keystone.wtn(&*FT_RULES);
+        let wtn2 = brimstone.wtn(&*FT_RULES);
+        assert!(ft_map.btn(keystone, brimstone) <= f64::min(wtn1, wtn2) + 5.0);
+        assert!(iw_map.btn(keystone, brimstone) <= 2.0 * f64::min(wtn1, wtn2) + 1.0);
+        assert_eq!(
+            house_map.btn(keystone, brimstone),
+            ft_map.btn(keystone, brimstone)
         );
-        assert_eq!(jesedipere.major_routes.len(), 0);
-        assert_eq!(jesedipere.main_routes.len(), 0);
-        assert_eq!(jesedipere.intermediate_routes.len(), 2);
-        assert_eq!(jesedipere.feeder_routes.len(), 5);
-        assert_eq!(jesedipere.minor_routes.len(), 1);
 
-        println!(
-            "rruthaekuksu major {:?}",
-            set_to_worlds(&rruthaekuksu.major_routes, &coords_to_world)
+        // Same routes, twice the credits
+        let house_keystone = house_map.galaxy().world("Proving Ground", "2602").unwrap();
+        assert_eq!(house_map.routes(house_keystone).len(), 3);
+        assert_eq!(
+            house_keystone.endpoint_trade_credits(),
+            2 * keystone.endpoint_trade_credits()
         );
-        println!(
-            "rruthaekuksu main {:?}",
-            set_to_worlds(&rruthaekuksu.main_routes, &coords_to_world)
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_trade_model_custom_trade_rules(data_dir: &PathBuf) -> Result<()> {
+        /// Far Trader, but every pair of worlds trades a bit more.
+        #[derive(Debug)]
+        struct Friendly(Ruleset);
+
+        impl TradeRules for Friendly {
+            fn name(&self) -> &str {
+                "friendly"
+            }
+            fn wtn(&self, world: &World) -> f64 {
+                self.0.wtn(world)
+            }
+            fn pair_modifier(&self, world1: &World, world2: &World) -> f64 {
+                self.0.pair_modifier(world1, world2) + 1.0
+            }
+            fn max_pair_bonus(&self) -> f64 {
+                self.0.max_pair_bonus() + 1.0
+            }
+            fn max_pair_penalty(&self) -> f64 {
+                self.0.max_pair_penalty() - 1.0
+            }
+            fn passenger_modifier(&self, world1: &World, world2: &World) -> f64 {
+                self.0.passenger_modifier(world1, world2)
+            }
+            fn distance_modifier(&self, distance: u32) -> f64 {
+                self.0.distance_modifier(distance)
+            }
+            fn btn_cap(&self, wtn1: f64, wtn2: f64) -> f64 {
+                self.0.btn_cap(wtn1, wtn2)
+            }
+            fn min_btn(&self) -> f64 {
+                self.0.min_btn()
+            }
+            fn dbtn_to_credits(&self) -> &[u64] {
+                self.0.dbtn_to_credits()
+            }
+        }
+
+        let galaxy = Galaxy::builder()
+            .data_directory(data_dir)
+            .offline(true)
+            .sectors(["Proving Ground", "Mockingbird"])
+            .build()?;
+        let ft_map = TradeModel::new().algorithm(ALG).compute(galaxy.clone())?;
+        let friendly_map = TradeModel::new()
+            .algorithm(ALG)
+            .rules(Friendly(Ruleset::far_trader()))
+            .compute(galaxy)?;
+
+        let mut more_trade = false;
+        for world1 in ft_map.galaxy().worlds() {
+            for world2 in ft_map.galaxy().worlds() {
+                if world1.desc() >= world2.desc() {
+                    continue;
+                }
+                let ft_btn = ft_map.btn(world1, world2);
+                let friendly_btn = friendly_map.btn(world1, world2);
+                let cap = FT_RULES.btn_cap(world1.wtn(&*FT_RULES), world2.wtn(&*FT_RULES));
+                assert!(friendly_btn >= ft_btn);
+                assert!(friendly_btn <= f64::max(ft_btn + 1.0, FT_RULES.min_btn()));
+                assert!(friendly_btn <= f64::max(cap, FT_RULES.min_btn()));
+                if friendly_btn > ft_btn {
+                    more_trade = true;
+                }
+            }
+        }
+        assert!(more_trade);
+        let route_count = |trade_map: &TradeMap| trade_map.route_counts().values().sum::<u64>();
+        assert!(route_count(&friendly_map) > route_count(&ft_map));
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_trade_model_t5_rules(data_dir: &PathBuf) -> Result<()> {
+        let galaxy = Galaxy::builder()
+            .data_directory(data_dir)
+            .offline(true)
+            .sectors(["Spinward Marches", "Proving Ground", "Mockingbird"])
+            .build()?;
+        let t5_rules = T5Rules::default();
+
+        // D7E+5 {+4}
+        let regina = galaxy.world("Spinward Marches", "1910").unwrap();
+        assert_eq!(t5_rules.wtn(regina), 6.0);
+        assert_eq!(regina.wtn(&*FT_RULES), 5.0);
+        // 200-5 {-3}
+        let bronze = galaxy.world("Spinward Marches", "1627").unwrap();
+        assert_eq!(t5_rules.wtn(bronze), 0.5);
+        assert_eq!(bronze.wtn(&*FT_RULES), 0.0);
+
+        // F9H+5 {+5}
+        let mora = galaxy.world("Spinward Marches", "3124").unwrap();
+        assert_eq!(t5_rules.economic_wtn(mora), Some(6.5));
+        assert_eq!(mora.wtn(&*FT_RULES), 6.5);
+        // 846+1 {+2}
+        let aramis = galaxy.world("Spinward Marches", "3110").unwrap();
+        assert_eq!(t5_rules.economic_wtn(aramis), Some(4.0));
+        assert_eq!(aramis.wtn(&*FT_RULES), 4.0);
+
+        // Economic extensions give a WTN within half a point of Far
+        // Trader's for most worlds, and never far off.
+        let mut num_economic = 0;
+        let mut num_close = 0;
+        for world in galaxy.worlds() {
+            if let Some(wtn) = t5_rules.economic_wtn(world) {
+                let difference = f64::abs(wtn - world.wtn(&*FT_RULES));
+                assert!(difference <= 1.5, "{}", world.desc());
+                num_economic += 1;
+                if difference <= 0.5 {
+                    num_close += 1;
+                }
+            }
+        }
+        assert!(num_economic >= 20);
+        assert!(num_close * 4 >= num_economic * 3);
+
+        // Comparable to Far Trader WTNs, though not the same.
+        let mut total_difference = 0.0;
+        let mut same = 0;
+        for world in galaxy.worlds() {
+            let difference = t5_rules.wtn(world) - world.wtn(&*FT_RULES);
+            total_difference += f64::abs(difference);
+            if difference == 0.0 {
+                same += 1;
+            }
+        }
+        let num_worlds = galaxy.worlds().count();
+        assert!(total_difference / (num_worlds as f64) < 0.5);
+        assert!(same >= num_worlds / 3);
+
+        let ft_map = TradeModel::new().algorithm(ALG).compute(galaxy.clone())?;
+        let t5_map = TradeModel::new()
+            .algorithm(ALG)
+            .rules(t5_rules)
+            .compute(galaxy)?;
+        let route_count = |trade_map: &TradeMap| trade_map.route_counts().values().sum::<u64>();
+        let ft_routes = route_count(&ft_map) as f64;
+        let t5_routes = route_count(&t5_map) as f64;
+        assert!(ft_routes > 0.0);
+        assert!(f64::abs(t5_routes - ft_routes) <= 0.25 * ft_routes);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_trade_model_scenario(data_dir: &PathBuf) -> Result<()> {
+        let galaxy = Galaxy::builder()
+            .data_directory(data_dir)
+            .offline(true)
+            .sectors(["Proving Ground", "Mockingbird"])
+            .build()?;
+        let border = |between: &str, and: &str, closed: bool, penalty: f64| Border {
+            between: vec![between.to_string()],
+            and: vec![and.to_string()],
+            closed,
+            penalty,
+        };
+        let with_scenario = |scenario: &Scenario| {
+            let mut rules = Ruleset::far_trader();
+            rules.scenario = scenario.clone();
+            TradeModel::new()
+                .algorithm(ALG)
+                .rules(rules)
+                .compute(galaxy.clone())
+        };
+        let peace_map = TradeModel::new().algorithm(ALG).compute(galaxy.clone())?;
+        let war = Scenario {
+            name: "war".to_string(),
+            borders: vec![border("Im*", "Zh*", true, 0.0)],
+        };
+        let war_map = with_scenario(&war)?;
+        let embargo = Scenario {
+            name: "embargo".to_string(),
+            borders: vec![border("ImDd", "CsIm", false, 1.0)],
+        };
+        let embargo_map = with_scenario(&embargo)?;
+
+        let world = |trade_map: &TradeMap, sector: &str, hex: &str| -> World {
+            trade_map.galaxy().world(sector, hex).unwrap().clone()
+        };
+        let keystone = world(&peace_map, "Proving Ground", "2602");
+        let verge = world(&peace_map, "Proving Ground", "3202");
+        let bramble = world(&peace_map, "Mockingbird", "0306");
+        let ford = world(&peace_map, "Mockingbird", "0109");
+        let lantern = world(&peace_map, "Mockingbird", "0309");
+
+        // Closed borders stop trade, and jumps across them.
+        assert_eq!(peace_map.btn(&ford, &lantern), 9.0);
+        assert_eq!(war_map.btn(&ford, &lantern), 0.0);
+        assert_eq!(war_map.btn(&keystone, &verge), 10.5);
+        for world1 in war_map.galaxy().worlds() {
+            for neighbor in world1.neighbors.iter().flatten() {
+                let world2 = war_map.galaxy().coords_to_world.get(neighbor).unwrap();
+                assert!(!war.closed(&world1.allegiance, &world2.allegiance));
+            }
+        }
+
+        // Trade between worlds on the same side goes around the front line.
+        let names = |path: Vec<&World>| -> Vec<String> {
+            path.iter().map(|world| world.name.clone()).collect()
+        };
+        assert_eq!(
+            names(peace_map.path(&bramble, &ford).unwrap()),
+            vec!["Bramble", "Lantern", "Ford"]
         );
-        println!(
-            "rruthaekuksu intermediate {:?}",
-            set_to_worlds(&rruthaekuksu.intermediate_routes, &coords_to_world)
+        assert_eq!(
+            names(war_map.path(&bramble, &ford).unwrap()),
+            vec!["Bramble", "Sable", "Ford"]
         );
-        println!(
-            "rruthaekuksu feeder {:?}",
-            set_to_worlds(&rruthaekuksu.feeder_routes, &coords_to_world)
+        assert_eq!(
+            war_map.distance(&bramble, &ford),
+            peace_map.distance(&bramble, &ford)
         );
-        println!(
-            "rruthaekuksu minor {:?}",
-            set_to_worlds(&rruthaekuksu.minor_routes, &coords_to_world)
+
+        // Embargoes only cost BTN.
+        assert_eq!(embargo_map.btn(&keystone, &verge), 9.5);
+        assert_eq!(
+            embargo_map.distance(&keystone, &verge),
+            peace_map.distance(&keystone, &verge)
         );
-        assert_eq!(rruthaekuksu.major_routes.len(), 0);
-        assert_eq!(rruthaekuksu.main_routes.len(), 0);
-        assert_eq!(rruthaekuksu.intermediate_routes.len(), 0);
-        assert_eq!(rruthaekuksu.feeder_routes.len(), 4);
-        assert_eq!(rruthaekuksu.minor_routes.len(), 0);
+        assert_eq!(embargo_map.btn(&ford, &lantern), 9.0);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_trade_model_distance_cache(data_dir: &PathBuf) -> Result<()> {
+        let galaxy = Galaxy::builder()
+            .data_directory(data_dir)
+            .offline(true)
+            .sectors(["Proving Ground", "Mockingbird"])
+            .build()?;
+        let temp_dir = tempdir()?;
+        let cache_dir = temp_dir.path().join("distances");
+        let model = TradeModel::new()
+            .algorithm(ALG)
+            .distance_cache_dir(&cache_dir);
+        let trade_map = model.compute(galaxy.clone())?;
+        // One file each for jump-2 and jump-3
+        let mut paths: Vec<PathBuf> = read_dir(&cache_dir)?
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        assert_eq!(paths.len(), 2);
+        let modified: Vec<_> = paths
+            .iter()
+            .map(|path| path.metadata().unwrap().modified().unwrap())
+            .collect();
+
+        // Same jumps, so nothing new to find or save.
+        let trade_map2 = model.clone().compute(galaxy.clone())?;
+        let modified2: Vec<_> = paths
+            .iter()
+            .map(|path| path.metadata().unwrap().modified().unwrap())
+            .collect();
+        assert_eq!(modified2, modified);
+        let keystone = trade_map.galaxy().world("Proving Ground", "2602").unwrap();
+        let keystone2 = trade_map2.galaxy().world("Proving Ground", "2602").unwrap();
+        let routes: Vec<(Route, String)> = trade_map
+            .routes(keystone)
+            .iter()
+            .map(|(route, world)| (*route, world.name()))
+            .collect();
+        let routes2: Vec<(Route, String)> = trade_map2
+            .routes(keystone2)
+            .iter()
+            .map(|(route, world)| (*route, world.name()))
+            .collect();
+        assert_eq!(routes2, routes);
+
+        // A corrupt cache is ignored and replaced.
+        write(&paths[0], "garbage")?;
+        let trade_map3 = model.min_btn(9.0).compute(galaxy)?;
+        let keystone3 = trade_map3.galaxy().world("Proving Ground", "2602").unwrap();
+        assert!(!trade_map3.routes(keystone3).is_empty());
+        assert_ne!(read(&paths[0])?, b"garbage");
+
+        temp_dir.close()?;
 
         Ok(())
     }
 
     #[rstest]
-    fn test_port_size(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_port_size(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        let gvur = Sector::new(&data_dir, "Gvurrdon".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
-        location_to_sector.insert(gvur.location, gvur.clone());
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
         }
         let mut sorted_coords: Vec<Coords>;
         sorted_coords = coords_to_world.keys().cloned().collect();
@@ -2102,13 +3634,17 @@ mod tests {
         max_jumps.insert(Main, 3);
         max_jumps.insert(Major, 3);
         let all_jumps: HashSet<u64> = max_jumps.values().cloned().collect();
-        let mut dists: HashMap<u64, Array2<u16>> = HashMap::new();
-        let mut preds: HashMap<u64, Array2<u16>> = HashMap::new();
+        let mut dists: HashMap<u64, ShortestPaths> = HashMap::new();
         for jump in all_jumps.iter() {
-            let (dist, pred) =
-                populate_navigable_distances(&sorted_coords, &coords_to_world, *jump, false, ALG);
+            let dist = populate_navigable_distances(
+                &sorted_coords,
+                &coords_to_world,
+                *jump,
+                false,
+                &NO_SCENARIO,
+                ALG,
+            )?;
             dists.insert(*jump, dist);
-            preds.insert(*jump, pred);
         }
         populate_trade_routes(
             &mut coords_to_world,
@@ -2117,39 +3653,60 @@ mod tests {
             false,
             &max_jumps,
             &dists,
-            &preds,
-            false,
+            &*FT_RULES,
         );
 
-        let aramis = htw!(spin, 3110, coords_to_world);
-        let mora = htw!(spin, 3124, coords_to_world);
-        let jesedipere = htw!(spin, 3001, coords_to_world);
-        let rruthaekuksu = htw!(gvur, 2840, coords_to_world);
+        let threshold = htw!(prov, "0101", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+        let cobble = htw!(prov, "2703", coords_to_world);
+        let brimstone = htw!(prov, "3003", coords_to_world);
+        let cinder = htw!(prov, "3106", coords_to_world);
+        let tinder = htw!(mock, "0303", coords_to_world);
+        let sable = htw!(mock, "0407", coords_to_world);
 
-        assert_eq!(aramis.port_size(), 6);
-        assert_eq!(mora.port_size(), 8);
-        assert_eq!(jesedipere.port_size(), 5);
-        assert_eq!(rruthaekuksu.port_size(), 5);
+        assert_eq!(keystone.port_size(&*FT_RULES), 8);
+        assert_eq!(sable.port_size(&*FT_RULES), 8);
+        assert_eq!(brimstone.port_size(&*FT_RULES), 7);
+        assert_eq!(cobble.port_size(&*FT_RULES), 5);
+        assert_eq!(threshold.port_size(&*FT_RULES), 4);
+        assert_eq!(cinder.port_size(&*FT_RULES), 1);
+        assert_eq!(tinder.port_size(&*FT_RULES), 0);
 
         Ok(())
     }
 
     #[rstest]
-    fn test_islands(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_islands(source: &FsSource) -> Result<()> {
+        // Waypoint, Ember, Kindle, and Tinderbox are only connected to
+        // Refuge, Harbor, and Outpost by a single jump-3 gap, and neither
+        // group can reach the rest of the map.
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let reft = Sector::new(&data_dir, "Reft".to_string(), &mut coords_to_world);
-        location_to_sector.insert(reft.location, reft.clone());
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
         }
         let mut sorted_coords: Vec<Coords>;
         sorted_coords = coords_to_world.keys().cloned().collect();
@@ -2166,13 +3723,17 @@ mod tests {
         max_jumps.insert(Main, 3);
         max_jumps.insert(Major, 3);
         let all_jumps: HashSet<u64> = max_jumps.values().cloned().collect();
-        let mut dists: HashMap<u64, Array2<u16>> = HashMap::new();
-        let mut preds: HashMap<u64, Array2<u16>> = HashMap::new();
+        let mut dists: HashMap<u64, ShortestPaths> = HashMap::new();
         for jump in all_jumps.iter() {
-            let (dist, pred) =
-                populate_navigable_distances(&sorted_coords, &coords_to_world, *jump, false, ALG);
+            let dist = populate_navigable_distances(
+                &sorted_coords,
+                &coords_to_world,
+                *jump,
+                false,
+                &NO_SCENARIO,
+                ALG,
+            )?;
             dists.insert(*jump, dist);
-            preds.insert(*jump, pred);
         }
         populate_trade_routes(
             &mut coords_to_world,
@@ -2181,313 +3742,110 @@ mod tests {
             false,
             &max_jumps,
             &dists,
-            &preds,
-            false,
+            &*FT_RULES,
         );
 
         let dist2 = dists.get(&2).unwrap();
         let dist3 = dists.get(&3).unwrap();
 
-        let zuflucht = htw!(reft, 0921, coords_to_world);
-        let wellington = htw!(reft, 0925, coords_to_world);
-        let esperanza = htw!(reft, 0926, coords_to_world);
-        let st_hilaire = htw!(reft, 0930, coords_to_world);
-        let nebelwelt = htw!(reft, 1030, coords_to_world);
-        let gloire = htw!(reft, 1123, coords_to_world);
-        let serendip_belt = htw!(reft, 1323, coords_to_world);
-        let new_colchis = htw!(reft, 1327, coords_to_world);
-        let herzenslust = htw!(reft, 1426, coords_to_world);
-        let orphee = htw!(reft, 1429, coords_to_world);
-        let topas = htw!(reft, 1522, coords_to_world);
-        let elysee = htw!(reft, 1525, coords_to_world);
-        let besancon = htw!(reft, 1526, coords_to_world);
-        let berlichingen = htw!(reft, 1621, coords_to_world);
-        let joyeuse = htw!(reft, 1628, coords_to_world);
-        let sturgeons_law = htw!(reft, 1724, coords_to_world);
-        let quichotte = htw!(reft, 1729, coords_to_world);
-        let neubayern = htw!(reft, 1822, coords_to_world);
-        let schlesien_belt = htw!(reft, 1923, coords_to_world);
-        let new_home = htw!(reft, 1925, coords_to_world);
-        let colchis = htw!(reft, 2026, coords_to_world);
-        let st_genevieve = htw!(reft, 2123, coords_to_world);
-        let acadie = htw!(reft, 2225, coords_to_world);
-        let sansterre = htw!(reft, 2322, coords_to_world);
-        let achille = htw!(reft, 2324, coords_to_world);
-        let amondiage = htw!(reft, 2325, coords_to_world);
-        let st_denis = htw!(reft, 2423, coords_to_world);
-
-        assert_eq!(zuflucht.uwtn(), 4.0);
-        assert_eq!(zuflucht.wtn_port_modifier(), 0.0);
-        assert_eq!(zuflucht.wtn(), 4.0);
-        assert_eq!(wellington.uwtn(), 2.0);
-        assert_eq!(wellington.wtn_port_modifier(), 0.5);
-        assert_eq!(wellington.wtn(), 2.5);
-        assert_eq!(esperanza.uwtn(), 6.0);
-        assert_eq!(esperanza.wtn_port_modifier(), 0.0);
-        assert_eq!(esperanza.wtn(), 6.0);
-        assert_eq!(st_hilaire.uwtn(), 4.5);
-        assert_eq!(st_hilaire.wtn_port_modifier(), 0.0);
-        assert_eq!(st_hilaire.wtn(), 4.5);
-        assert_eq!(nebelwelt.uwtn(), 2.5);
-        assert_eq!(nebelwelt.wtn_port_modifier(), 0.5);
-        assert_eq!(nebelwelt.wtn(), 3.0);
-        assert_eq!(gloire.uwtn(), 3.5);
-        assert_eq!(gloire.wtn_port_modifier(), 0.0);
-        assert_eq!(gloire.wtn(), 3.5);
-        assert_eq!(serendip_belt.uwtn(), 5.5);
-        assert_eq!(serendip_belt.wtn_port_modifier(), 0.0);
-        assert_eq!(serendip_belt.wtn(), 5.5);
-        assert_eq!(new_colchis.uwtn(), 5.5);
-        assert_eq!(new_colchis.wtn_port_modifier(), 0.0);
-        assert_eq!(new_colchis.wtn(), 5.5);
-        assert_eq!(herzenslust.uwtn(), 4.0);
-        assert_eq!(herzenslust.wtn_port_modifier(), -1.0);
-        assert_eq!(herzenslust.wtn(), 3.0);
-        assert_eq!(orphee.uwtn(), 2.5);
-        assert_eq!(orphee.wtn_port_modifier(), -2.5);
-        assert_eq!(orphee.wtn(), 0.0);
-        assert_eq!(topas.uwtn(), 4.5);
-        assert_eq!(topas.wtn_port_modifier(), -0.5);
-        assert_eq!(topas.wtn(), 4.0);
-        assert_eq!(elysee.uwtn(), 3.5);
-        assert_eq!(elysee.wtn_port_modifier(), 0.5);
-        assert_eq!(elysee.wtn(), 4.0);
-        assert_eq!(besancon.uwtn(), 2.5);
-        assert_eq!(besancon.wtn_port_modifier(), 0.5);
-        assert_eq!(besancon.wtn(), 3.0);
-        assert_eq!(berlichingen.uwtn(), 2.5);
-        assert_eq!(berlichingen.wtn_port_modifier(), 0.0);
-        assert_eq!(berlichingen.wtn(), 2.5);
-        assert_eq!(joyeuse.uwtn(), 5.5);
-        assert_eq!(joyeuse.wtn_port_modifier(), 0.0);
-        assert_eq!(joyeuse.wtn(), 5.5);
-        assert_eq!(sturgeons_law.uwtn(), 3.0);
-        assert_eq!(sturgeons_law.wtn_port_modifier(), 0.0);
-        assert_eq!(sturgeons_law.wtn(), 3.0);
-        assert_eq!(quichotte.uwtn(), 3.5);
-        assert_eq!(quichotte.wtn_port_modifier(), -0.5);
-        assert_eq!(quichotte.wtn(), 3.0);
-        assert_eq!(neubayern.uwtn(), 5.5);
-        assert_eq!(neubayern.wtn_port_modifier(), 0.0);
-        assert_eq!(neubayern.wtn(), 5.5);
-        assert_eq!(schlesien_belt.uwtn(), 2.5);
-        assert_eq!(schlesien_belt.wtn_port_modifier(), 0.5);
-        assert_eq!(schlesien_belt.wtn(), 3.0);
-        assert_eq!(new_home.uwtn(), 5.0);
-        assert_eq!(new_home.wtn_port_modifier(), 0.0);
-        assert_eq!(new_home.wtn(), 5.0);
-        assert_eq!(colchis.uwtn(), 5.0);
-        assert_eq!(colchis.wtn_port_modifier(), 0.0);
-        assert_eq!(colchis.wtn(), 5.0);
-        assert_eq!(st_genevieve.uwtn(), 1.5);
-        assert_eq!(st_genevieve.wtn_port_modifier(), 0.5);
-        assert_eq!(st_genevieve.wtn(), 2.0);
-        assert_eq!(acadie.uwtn(), 3.5);
-        assert_eq!(acadie.wtn_port_modifier(), 0.0);
-        assert_eq!(acadie.wtn(), 3.5);
-        assert_eq!(sansterre.uwtn(), 5.5);
-        assert_eq!(sansterre.wtn_port_modifier(), 0.0);
-        assert_eq!(sansterre.wtn(), 5.5);
-        assert_eq!(achille.uwtn(), 2.5);
-        assert_eq!(achille.wtn_port_modifier(), 0.0);
-        assert_eq!(achille.wtn(), 2.5);
-        assert_eq!(amondiage.uwtn(), 5.5);
-        assert_eq!(amondiage.wtn_port_modifier(), 0.0);
-        assert_eq!(amondiage.wtn(), 5.5);
-        assert_eq!(st_denis.uwtn(), 4.0);
-        assert_eq!(st_denis.wtn_port_modifier(), -0.5);
-        assert_eq!(st_denis.wtn(), 3.5);
-
-        assert!(zuflucht.neighbors[1].is_empty());
-        assert!(zuflucht.neighbors[2].is_empty());
-        assert_eq!(zuflucht.neighbors[3].len(), 1);
-        assert_eq!(
-            zuflucht.neighbors[3].iter().next(),
-            Some(&gloire.get_coords())
-        );
-        assert!(zuflucht.xboat_routes.is_empty());
-
-        assert_eq!(zuflucht.navigable_distance(wellington, &dist2), INFINITY);
-        assert_eq!(zuflucht.btn(wellington, &dist2, false, false), 0.0);
-        assert_eq!(zuflucht.btn(wellington, &dist2, true, false), 0.0);
-        assert_eq!(zuflucht.navigable_distance(wellington, &dist3), 6);
-        assert_eq!(zuflucht.wtcm(wellington), -0.5);
-        assert_eq!(zuflucht.btn(wellington, &dist3, false, false), 4.5);
-        assert_eq!(zuflucht.btn(wellington, &dist3, true, false), 4.5);
-
-        assert_eq!(zuflucht.navigable_distance(esperanza, &dist2), INFINITY);
-        assert_eq!(zuflucht.wtcm(esperanza), -0.5);
-        assert_eq!(zuflucht.btn(esperanza, &dist2, false, false), 0.0);
-        assert_eq!(zuflucht.btn(esperanza, &dist2, true, false), 0.0);
-        assert_eq!(zuflucht.navigable_distance(esperanza, &dist3), 7);
-        assert_eq!(zuflucht.btn(esperanza, &dist3, false, false), 8.0);
-        assert_eq!(zuflucht.btn(esperanza, &dist3, true, false), 8.0);
-
-        assert_eq!(zuflucht.navigable_distance(gloire, &dist2), INFINITY);
-        assert_eq!(zuflucht.wtcm(gloire), -0.5);
-        assert_eq!(zuflucht.btn(gloire, &dist2, false, false), 0.0);
-        assert_eq!(zuflucht.btn(gloire, &dist2, true, false), 0.0);
-        assert_eq!(zuflucht.navigable_distance(gloire, &dist3), 3);
-        assert_eq!(zuflucht.btn(gloire, &dist3, false, false), 6.0);
-        assert_eq!(zuflucht.btn(gloire, &dist3, true, false), 6.0);
-
-        assert_eq!(zuflucht.navigable_distance(serendip_belt, &dist3), 5);
-        assert_eq!(zuflucht.wtcm(serendip_belt), 0.0);
-        assert_eq!(zuflucht.btn(serendip_belt, &dist3, false, false), 8.5);
-        assert_eq!(zuflucht.btn(serendip_belt, &dist3, true, false), 8.5);
-
-        assert_eq!(zuflucht.navigable_distance(herzenslust, &dist3), 10);
-        assert_eq!(zuflucht.wtcm(herzenslust), -0.5);
-        assert_eq!(zuflucht.btn(herzenslust, &dist3, false, false), 4.5);
-        assert_eq!(zuflucht.btn(herzenslust, &dist3, true, false), 4.5);
-
-        assert_eq!(zuflucht.navigable_distance(orphee, &dist3), INFINITY);
-        assert_eq!(zuflucht.wtcm(orphee), -0.5);
-        assert_eq!(zuflucht.btn(orphee, &dist3, false, false), 0.0);
-        assert_eq!(zuflucht.btn(orphee, &dist3, true, false), 0.0);
-
-        assert_eq!(zuflucht.navigable_distance(topas, &dist3), 7);
-        assert_eq!(zuflucht.wtcm(topas), 0.0);
-        assert_eq!(zuflucht.btn(topas, &dist3, false, false), 6.5);
-        assert_eq!(zuflucht.btn(topas, &dist3, true, false), 6.5);
-
-        assert_eq!(zuflucht.navigable_distance(elysee, &dist3), 8);
-        assert_eq!(zuflucht.wtcm(elysee), -0.5);
-        assert_eq!(zuflucht.btn(elysee, &dist3, false, false), 6.0);
-        assert_eq!(zuflucht.btn(elysee, &dist3, true, false), 6.0);
-
-        assert_eq!(zuflucht.navigable_distance(besancon, &dist3), 9);
-        assert_eq!(zuflucht.wtcm(besancon), -0.5);
-        assert_eq!(zuflucht.btn(besancon, &dist3, false, false), 5.0);
-        assert_eq!(zuflucht.btn(besancon, &dist3, true, false), 5.0);
-
-        assert_eq!(zuflucht.navigable_distance(berlichingen, &dist3), 8);
-        assert_eq!(zuflucht.wtcm(berlichingen), -0.5);
-        assert_eq!(zuflucht.btn(berlichingen, &dist3, false, false), 4.5);
-        assert_eq!(zuflucht.btn(berlichingen, &dist3, true, false), 4.5);
-
-        assert_eq!(zuflucht.navigable_distance(joyeuse, &dist3), 12);
-        assert_eq!(zuflucht.wtcm(joyeuse), -0.5);
-        assert_eq!(zuflucht.btn(joyeuse, &dist3, false, false), 7.0);
-        assert_eq!(zuflucht.btn(joyeuse, &dist3, true, false), 7.0);
-
-        assert_eq!(zuflucht.navigable_distance(sturgeons_law, &dist3), 10);
-        assert_eq!(zuflucht.wtcm(sturgeons_law), -0.5);
-        assert_eq!(zuflucht.btn(sturgeons_law, &dist3, false, false), 4.5);
-        assert_eq!(zuflucht.btn(sturgeons_law, &dist3, true, false), 4.5);
-
-        assert_eq!(zuflucht.navigable_distance(quichotte, &dist3), 13);
-        assert_eq!(zuflucht.wtcm(quichotte), -0.5);
-        assert_eq!(zuflucht.btn(quichotte, &dist3, false, false), 4.5);
-        assert_eq!(zuflucht.btn(quichotte, &dist3, true, false), 4.5);
-
-        assert_eq!(zuflucht.navigable_distance(neubayern, &dist3), 10);
-        assert_eq!(zuflucht.wtcm(neubayern), -0.5);
-        assert_eq!(zuflucht.btn(neubayern, &dist3, false, false), 7.0);
-        assert_eq!(zuflucht.btn(neubayern, &dist3, true, false), 7.0);
-
-        assert_eq!(zuflucht.navigable_distance(schlesien_belt, &dist3), 11);
-        assert_eq!(zuflucht.wtcm(schlesien_belt), -0.5);
-        assert_eq!(zuflucht.btn(schlesien_belt, &dist3, false, false), 4.5);
-        assert_eq!(zuflucht.btn(schlesien_belt, &dist3, true, false), 4.5);
-
-        assert_eq!(zuflucht.navigable_distance(new_home, &dist3), 12);
-        assert_eq!(zuflucht.wtcm(new_home), -0.5);
-        assert_eq!(zuflucht.btn(new_home, &dist3, false, false), 6.5);
-        assert_eq!(zuflucht.btn(new_home, &dist3, true, false), 7.0);
-
-        assert_eq!(zuflucht.navigable_distance(colchis, &dist3), 14);
-        assert_eq!(zuflucht.wtcm(colchis), -0.5);
-        assert_eq!(zuflucht.btn(colchis, &dist3, false, false), 6.5);
-        assert_eq!(zuflucht.btn(colchis, &dist3, true, false), 6.5);
-
-        assert_eq!(zuflucht.navigable_distance(st_genevieve, &dist3), 13);
-        assert_eq!(zuflucht.wtcm(st_genevieve), -0.5);
-        assert_eq!(zuflucht.btn(st_genevieve, &dist3, false, false), 3.5);
-        assert_eq!(zuflucht.btn(st_genevieve, &dist3, true, false), 3.5);
-
-        assert_eq!(zuflucht.navigable_distance(acadie, &dist3), 15);
-        assert_eq!(zuflucht.wtcm(acadie), -0.5);
-        assert_eq!(zuflucht.btn(acadie, &dist3, false, false), 5.0);
-        assert_eq!(zuflucht.btn(acadie, &dist3, true, false), 5.0);
-
-        assert_eq!(zuflucht.navigable_distance(sansterre, &dist3), 15);
-        assert_eq!(zuflucht.wtcm(sansterre), -0.5);
-        assert_eq!(zuflucht.btn(sansterre, &dist3, false, false), 7.0);
-        assert_eq!(zuflucht.btn(sansterre, &dist3, true, false), 7.0);
-
-        assert_eq!(zuflucht.navigable_distance(achille, &dist3), 15);
-        assert_eq!(zuflucht.wtcm(achille), -0.5);
-        assert_eq!(zuflucht.btn(achille, &dist3, false, false), 4.0);
-        assert_eq!(zuflucht.btn(achille, &dist3, true, false), 4.0);
-
-        assert_eq!(zuflucht.navigable_distance(amondiage, &dist3), 16);
-        assert_eq!(zuflucht.wtcm(amondiage), -0.5);
-        assert_eq!(zuflucht.btn(amondiage, &dist3, false, false), 7.0);
-        assert_eq!(zuflucht.btn(amondiage, &dist3, true, false), 7.0);
-
-        assert_eq!(zuflucht.navigable_distance(st_denis, &dist3), 16);
-        assert_eq!(zuflucht.wtcm(st_denis), -0.5);
-        assert_eq!(zuflucht.btn(st_denis, &dist3, false, false), 5.0);
-        assert_eq!(zuflucht.btn(st_denis, &dist3, true, false), 5.0);
-
-        println!("{:?}", zuflucht.dbtn_to_coords);
-        for (ii, set) in zuflucht.dbtn_to_coords.iter().enumerate() {
-            print!("dbtn {} ", ii);
-            for coords in set {
-                println!("{}", coords_to_world.get(&coords).unwrap().name);
-            }
+        let waypoint = htw!(prov, "0420", coords_to_world);
+        let ember = htw!(prov, "0521", coords_to_world);
+        let kindle = htw!(prov, "0622", coords_to_world);
+        let tinderbox = htw!(prov, "0721", coords_to_world);
+        let refuge = htw!(prov, "1021", coords_to_world);
+        let harbor = htw!(prov, "1122", coords_to_world);
+        let outpost = htw!(prov, "1221", coords_to_world);
+        let keystone = htw!(prov, "2602", coords_to_world);
+
+        assert_eq!(waypoint.wtn(&*FT_RULES), 3.0);
+        assert_eq!(ember.wtn(&*FT_RULES), 4.5);
+        assert_eq!(kindle.wtn(&*FT_RULES), 2.0);
+        assert_eq!(tinderbox.wtn(&*FT_RULES), 2.5);
+        assert_eq!(refuge.wtn(&*FT_RULES), 5.0);
+        assert_eq!(harbor.wtn(&*FT_RULES), 4.5);
+        assert_eq!(outpost.wtn(&*FT_RULES), 2.5);
+
+        assert_eq!(ember.navigable_distance(tinderbox, dist2), 2);
+        assert_eq!(tinderbox.navigable_distance(refuge, dist2), INFINITY);
+        assert_eq!(tinderbox.navigable_distance(refuge, dist3), 3);
+        assert_eq!(ember.navigable_distance(refuge, dist3), 5);
+        assert_eq!(refuge.navigable_distance(keystone, dist3), INFINITY);
+
+        assert_eq!(ember.btn(refuge, dist2, false, &*FT_RULES), 0.0);
+        assert_eq!(ember.btn(refuge, dist3, false, &*FT_RULES), 8.5);
+        assert_eq!(refuge.btn(harbor, dist2, false, &*FT_RULES), 9.5);
+
+        // Only the jump-2 hop inside the eastern group is worth a route.
+        let empty = HashSet::new();
+        assert_eq!(refuge.feeder_routes, set!(harbor.get_coords()));
+        assert_eq!(harbor.feeder_routes, set!(refuge.get_coords()));
+        for world in [waypoint, ember, kindle, tinderbox, outpost] {
+            assert_eq!(world.major_routes, empty);
+            assert_eq!(world.main_routes, empty);
+            assert_eq!(world.intermediate_routes, empty);
+            assert_eq!(world.feeder_routes, empty);
+            assert_eq!(world.minor_routes, empty);
         }
-        assert_eq!(zuflucht.dbtn_to_coords[0].len(), 6);
-        for ii in 1..12 {
-            assert!(zuflucht.dbtn_to_coords[ii].is_empty());
+
+        assert_eq!(refuge.dbtn_to_coords[0].len(), 12);
+        for ii in 1..13 {
+            assert!(refuge.dbtn_to_coords[ii].is_empty());
         }
-        assert_eq!(zuflucht.dbtn_to_coords[13].len(), 3);
-        assert_eq!(zuflucht.dbtn_to_coords[14].len(), 5);
-        assert!(zuflucht.dbtn_to_coords[15].is_empty());
-        assert_eq!(zuflucht.dbtn_to_coords[16].len(), 1);
-        assert_eq!(
-            zuflucht.dbtn_to_coords[16].iter().next(),
-            Some(&esperanza.get_coords())
-        );
-        assert_eq!(zuflucht.dbtn_to_coords[17].len(), 1);
         assert_eq!(
-            zuflucht.dbtn_to_coords[17].iter().next(),
-            Some(&serendip_belt.get_coords())
-        );
-        for ii in 18..zuflucht.dbtn_to_coords.len() - 1 {
-            assert!(zuflucht.dbtn_to_coords[ii].is_empty());
+            refuge.dbtn_to_coords[13],
+            set!(
+                waypoint.get_coords(),
+                tinderbox.get_coords(),
+                outpost.get_coords()
+            )
+        );
+        assert!(refuge.dbtn_to_coords[14].is_empty());
+        assert!(refuge.dbtn_to_coords[15].is_empty());
+        assert!(refuge.dbtn_to_coords[16].is_empty());
+        assert_eq!(refuge.dbtn_to_coords[17], set!(ember.get_coords()));
+        assert!(refuge.dbtn_to_coords[18].is_empty());
+        assert_eq!(refuge.dbtn_to_coords[19], set!(harbor.get_coords()));
+        for ii in 20..refuge.dbtn_to_coords.len() {
+            assert!(refuge.dbtn_to_coords[ii].is_empty());
         }
-        assert_eq!(zuflucht.endpoint_trade_credits, 1_222_500_000);
-        assert_eq!(zuflucht.transient_trade_credits, 0);
+        assert_eq!(refuge.endpoint_trade_credits, 8_272_500_000);
+        assert_eq!(refuge.transient_trade_credits, 0);
+
+        assert_eq!(ember.endpoint_trade_credits, 907_500_000);
+        assert_eq!(ember.transient_trade_credits, 0);
 
         Ok(())
     }
 
     #[rstest]
-    fn test_generate_pdfs(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_generate_pdfs(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        let gvur = Sector::new(&data_dir, "Gvurrdon".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
-        location_to_sector.insert(gvur.location, gvur.clone());
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
         }
         let mut sorted_coords: Vec<Coords>;
         sorted_coords = coords_to_world.keys().cloned().collect();
@@ -2504,13 +3862,17 @@ mod tests {
         max_jumps.insert(Main, 3);
         max_jumps.insert(Major, 3);
         let all_jumps: HashSet<u64> = max_jumps.values().cloned().collect();
-        let mut dists: HashMap<u64, Array2<u16>> = HashMap::new();
-        let mut preds: HashMap<u64, Array2<u16>> = HashMap::new();
+        let mut dists: HashMap<u64, ShortestPaths> = HashMap::new();
         for jump in all_jumps.iter() {
-            let (dist, pred) =
-                populate_navigable_distances(&sorted_coords, &coords_to_world, *jump, false, ALG);
+            let dist = populate_navigable_distances(
+                &sorted_coords,
+                &coords_to_world,
+                *jump,
+                false,
+                &NO_SCENARIO,
+                ALG,
+            )?;
             dists.insert(*jump, dist);
-            preds.insert(*jump, pred);
         }
         populate_trade_routes(
             &mut coords_to_world,
@@ -2519,18 +3881,22 @@ mod tests {
             false,
             &max_jumps,
             &dists,
-            &preds,
-            false,
+            &*FT_RULES,
         );
 
         let temp_dir = tempdir()?;
         let output_dir: PathBuf = temp_dir.path().to_path_buf();
 
-        generate_pdfs(&output_dir, &location_to_sector, &coords_to_world);
+        generate_pdfs(
+            &output_dir,
+            &location_to_sector,
+            &coords_to_world,
+            &*FT_RULES,
+        );
         let found_filename_results: Vec<Result<OsString, io::Error>> = read_dir(&output_dir)?
             .map(|res| res.map(|e| e.file_name()))
             .collect();
-        assert_eq!(found_filename_results.len(), 3);
+        assert_eq!(found_filename_results.len(), 2);
         // TODO Validate the PDF files with pdf-rs
 
         temp_dir.close()?;
@@ -2539,29 +3905,34 @@ mod tests {
     }
 
     #[rstest]
-    fn test_generate_text_btns(data_dir: &PathBuf, download: &Result<Vec<String>>) -> Result<()> {
-        if let Ok(_sector_names) = download {};
+    fn test_generate_text_btns(source: &FsSource) -> Result<()> {
         let mut coords_to_world: HashMap<Coords, World> = HashMap::new();
         let mut location_to_sector: HashMap<(i64, i64), Sector> = HashMap::new();
-        let spin = Sector::new(
-            &data_dir,
-            "Spinward Marches".to_string(),
+        let prov = Sector::new(
+            source,
+            "Proving Ground".to_string(),
+            None,
+            false,
             &mut coords_to_world,
-        );
-        let dene = Sector::new(&data_dir, "Deneb".to_string(), &mut coords_to_world);
-        let gvur = Sector::new(&data_dir, "Gvurrdon".to_string(), &mut coords_to_world);
-        location_to_sector.insert(spin.location, spin.clone());
-        location_to_sector.insert(dene.location, dene.clone());
-        location_to_sector.insert(gvur.location, gvur.clone());
+        )?;
+        let mock = Sector::new(
+            source,
+            "Mockingbird".to_string(),
+            None,
+            false,
+            &mut coords_to_world,
+        )?;
+        location_to_sector.insert(prov.location, prov.clone());
+        location_to_sector.insert(mock.location, mock.clone());
         for sector in location_to_sector.values() {
             sector
-                .parse_xml_routes(&data_dir, &location_to_sector, &mut coords_to_world)
+                .parse_xml_routes(source, &location_to_sector, &mut coords_to_world)
                 .unwrap();
         }
         // Make a temporary clone to avoid having mutable and immutable refs.
         let coords_to_world2 = coords_to_world.clone();
         for world in coords_to_world.values_mut() {
-            world.populate_neighbors(&coords_to_world2, 3, false);
+            world.populate_neighbors(&coords_to_world2, 3, false, &NO_SCENARIO);
         }
         let mut sorted_coords: Vec<Coords>;
         sorted_coords = coords_to_world.keys().cloned().collect();
@@ -2578,13 +3949,17 @@ mod tests {
         max_jumps.insert(Main, 3);
         max_jumps.insert(Major, 3);
         let all_jumps: HashSet<u64> = max_jumps.values().cloned().collect();
-        let mut dists: HashMap<u64, Array2<u16>> = HashMap::new();
-        let mut preds: HashMap<u64, Array2<u16>> = HashMap::new();
+        let mut dists: HashMap<u64, ShortestPaths> = HashMap::new();
         for jump in all_jumps.iter() {
-            let (dist, pred) =
-                populate_navigable_distances(&sorted_coords, &coords_to_world, *jump, false, ALG);
+            let dist = populate_navigable_distances(
+                &sorted_coords,
+                &coords_to_world,
+                *jump,
+                false,
+                &NO_SCENARIO,
+                ALG,
+            )?;
             dists.insert(*jump, dist);
-            preds.insert(*jump, pred);
         }
         populate_trade_routes(
             &mut coords_to_world,
@@ -2593,8 +3968,7 @@ mod tests {
             false,
             &max_jumps,
             &dists,
-            &preds,
-            false,
+            &*FT_RULES,
         );
         let max_max_jump: u64 = *max_jumps.values().max().unwrap();
 
@@ -2609,22 +3983,21 @@ mod tests {
             max_max_jump,
             false,
             &dists,
-            false,
+            &*FT_RULES,
         )?;
         let found_filename_results: Vec<Result<OsString, io::Error>> = read_dir(&output_dir)?
             .map(|res| res.map(|e| e.file_name()))
             .collect();
-        assert_eq!(found_filename_results.len(), 3);
-        let mut spin_path = output_dir;
-        spin_path.push("Spinward Marches.txt");
-        let contents = read_to_string(spin_path)?;
-        let lines: Vec<&str> = contents.split("\n").collect();
-        assert!(lines
-            .contains(&"Aramis (Spinward Marches 3110) L'oeul d'Dieu (Spinward Marches 3010) 8"));
+        assert_eq!(found_filename_results.len(), 2);
+        let mut prov_path = output_dir;
+        prov_path.push("Proving Ground.txt");
+        let contents = read_to_string(prov_path)?;
+        let lines: Vec<&str> = contents.split('\n').collect();
+        assert!(lines.contains(&"Keystone (Proving Ground 2602) Cobble (Proving Ground 2703) 8.5"));
         assert!(
-            lines.contains(&"Aramis (Spinward Marches 3110) Natoko (Spinward Marches 3209) 6.5")
+            lines.contains(&"Keystone (Proving Ground 2602) Brimstone (Proving Ground 3003) 10.5")
         );
-        assert!(lines.contains(&"Aramis (Spinward Marches 3110) Reacher (Spinward Marches 3210) 7"));
+        assert!(lines.contains(&"Keystone (Proving Ground 2602) Tallow (Proving Ground 2904) 8"));
 
         temp_dir.close()?;
 
@@ -0,0 +1,390 @@
+use anyhow::Result;
+use log::{debug, warn};
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::apsp::{Algorithm, ShortestPaths, INFINITY};
+use crate::rules::{Ruleset, TradeRules};
+use crate::Route::*;
+use crate::{
+    generate_pdfs, generate_text_btns, populate_navigable_distances, populate_trade_routes,
+    write_atomically, Coords, Galaxy, Route, RouteCounter, World, MIN_BTN, MIN_ROUTE_BTN,
+};
+
+/// Rules and limits for computing trade routes.
+///
+/// The defaults match the command line: GURPS Traveller: Far Trader rules,
+/// freight rather than passenger BTN, jump-2 for minor routes, and jump-3
+/// for the rest.
+#[derive(Clone, Debug)]
+pub struct TradeModel {
+    algorithm: Algorithm,
+    min_btn: f64,
+    min_route_btn: f64,
+    max_jumps: RouteCounter,
+    ignore_xboat_routes: bool,
+    passenger: bool,
+    disallow_red_zones: bool,
+    rules: Arc<dyn TradeRules>,
+    distance_cache_dir: Option<PathBuf>,
+}
+
+impl Default for TradeModel {
+    fn default() -> TradeModel {
+        let mut max_jumps = HashMap::new();
+        max_jumps.insert(Minor, 2);
+        max_jumps.insert(Feeder, 3);
+        max_jumps.insert(Intermediate, 3);
+        max_jumps.insert(Main, 3);
+        max_jumps.insert(Major, 3);
+        TradeModel {
+            algorithm: Algorithm::Dial,
+            min_btn: *MIN_BTN,
+            min_route_btn: *MIN_ROUTE_BTN,
+            max_jumps,
+            ignore_xboat_routes: false,
+            passenger: false,
+            disallow_red_zones: false,
+            rules: Arc::new(Ruleset::far_trader()),
+            distance_cache_dir: None,
+        }
+    }
+}
+
+impl TradeModel {
+    pub fn new() -> TradeModel {
+        TradeModel::default()
+    }
+
+    /// Algorithm for All Pairs Shortest Paths
+    pub fn algorithm(mut self, algorithm: Algorithm) -> TradeModel {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Minimum BTN to use in route calculations
+    pub fn min_btn(mut self, min_btn: f64) -> TradeModel {
+        self.min_btn = min_btn;
+        self
+    }
+
+    /// Minimum BTN for a minor route
+    pub fn min_route_btn(mut self, min_route_btn: f64) -> TradeModel {
+        self.min_route_btn = min_route_btn;
+        self
+    }
+
+    /// Maximum jump for one kind of route
+    pub fn max_jump(mut self, route: Route, max_jump: u64) -> TradeModel {
+        self.max_jumps.insert(route, max_jump);
+        self
+    }
+
+    /// Maximum jump for every kind of route
+    pub fn max_jump_all(mut self, max_jump: u64) -> TradeModel {
+        for old_max_jump in self.max_jumps.values_mut() {
+            *old_max_jump = max_jump;
+        }
+        self
+    }
+
+    /// Ignore xboat routes; don't let them ignore max_jump
+    pub fn ignore_xboat_routes(mut self, ignore_xboat_routes: bool) -> TradeModel {
+        self.ignore_xboat_routes = ignore_xboat_routes;
+        self
+    }
+
+    /// Use passenger BTN instead of freight BTN
+    pub fn passenger(mut self, passenger: bool) -> TradeModel {
+        self.passenger = passenger;
+        self
+    }
+
+    /// Disallow all travel through red zones, including gas giant refueling
+    pub fn disallow_red_zones(mut self, disallow_red_zones: bool) -> TradeModel {
+        self.disallow_red_zones = disallow_red_zones;
+        self
+    }
+
+    /// Trade rules, like Ruleset::interstellar_wars(), a house-ruled
+    /// Ruleset::load(), or another TradeRules implementation
+    pub fn rules<R: TradeRules + 'static>(mut self, rules: R) -> TradeModel {
+        self.rules = Arc::new(rules);
+        self
+    }
+
+    /// Directory to save navigable distances in, and to load them from on
+    /// later runs over the same worlds with the same jump rules, so only
+    /// changing BTN thresholds doesn't mean finding them all again.
+    pub fn distance_cache_dir(mut self, distance_cache_dir: &Path) -> TradeModel {
+        self.distance_cache_dir = Some(distance_cache_dir.to_path_buf());
+        self
+    }
+
+    fn max_max_jump(&self) -> u64 {
+        *self.max_jumps.values().max().unwrap()
+    }
+
+    /// Find navigable distances and trade routes between all the worlds in
+    /// galaxy.
+    ///
+    /// Fails with a GalaxyError if galaxy has too many worlds.
+    pub fn compute(&self, mut galaxy: Galaxy) -> Result<TradeMap> {
+        let max_max_jump = self.max_max_jump();
+        let coords_to_world = &mut galaxy.coords_to_world;
+        debug!("Building neighbors");
+        {
+            // Make a temporary clone to avoid having mutable and immutable refs.
+            let coords_to_world2 = coords_to_world.clone();
+            for world in coords_to_world.values_mut() {
+                world.populate_neighbors(
+                    &coords_to_world2,
+                    max_max_jump,
+                    self.disallow_red_zones,
+                    self.rules.scenario(),
+                );
+            }
+        }
+        let mut sorted_coords: Vec<Coords> = coords_to_world.keys().cloned().collect();
+        sorted_coords.sort();
+        for (ii, coords) in sorted_coords.iter_mut().enumerate() {
+            let world = coords_to_world.get_mut(coords).unwrap();
+            world.index = Some(ii);
+        }
+
+        let all_jumps: HashSet<u64> = self.max_jumps.values().cloned().collect();
+        let mut dists: HashMap<u64, ShortestPaths> = HashMap::new();
+        for jump in all_jumps.iter() {
+            let dist = populate_navigable_distances(
+                &sorted_coords,
+                coords_to_world,
+                *jump,
+                self.ignore_xboat_routes,
+                self.rules.scenario(),
+                self.algorithm,
+            )?;
+            if let Some(cache_dir) = &self.distance_cache_dir {
+                load_distance_cache(cache_dir, &dist);
+            }
+            dists.insert(*jump, dist);
+        }
+        Ok(self.find_trade_routes(galaxy, dists))
+    }
+
+    /// Like compute, but if trade_map was computed with the same jump rules,
+    /// reuse its neighbors and navigable distances and only find the trade
+    /// routes again.  Handy for trying several BTN thresholds.
+    pub fn recompute(&self, trade_map: TradeMap) -> Result<TradeMap> {
+        if self.same_navigation(&trade_map.model) {
+            debug!("Reusing navigable distances");
+            Ok(self.find_trade_routes(trade_map.galaxy, trade_map.dists))
+        } else {
+            self.compute(trade_map.galaxy)
+        }
+    }
+
+    /// Return true if other finds the same navigable distances as self.
+    fn same_navigation(&self, other: &TradeModel) -> bool {
+        self.algorithm == other.algorithm
+            && self.max_jumps == other.max_jumps
+            && self.ignore_xboat_routes == other.ignore_xboat_routes
+            && self.disallow_red_zones == other.disallow_red_zones
+            && self.rules.scenario().closed_borders() == other.rules.scenario().closed_borders()
+    }
+
+    fn find_trade_routes(
+        &self,
+        mut galaxy: Galaxy,
+        dists: HashMap<u64, ShortestPaths>,
+    ) -> TradeMap {
+        let coords_to_world = &mut galaxy.coords_to_world;
+        for world in coords_to_world.values_mut() {
+            world.clear_trade_routes();
+        }
+        let num_entries: HashMap<u64, usize> = dists
+            .iter()
+            .map(|(jump, dist)| (*jump, dist.num_entries()))
+            .collect();
+
+        populate_trade_routes(
+            coords_to_world,
+            self.min_btn,
+            self.min_route_btn,
+            self.passenger,
+            &self.max_jumps,
+            &dists,
+            self.rules.as_ref(),
+        );
+
+        if let Some(cache_dir) = &self.distance_cache_dir {
+            for (jump, dist) in dists.iter() {
+                if dist.num_entries() != num_entries[jump] {
+                    if let Err(err) = save_distance_cache(cache_dir, dist) {
+                        warn!("Could not save navigable distances: {}", err);
+                    }
+                }
+            }
+        }
+
+        TradeMap {
+            galaxy,
+            model: self.clone(),
+            dists,
+        }
+    }
+}
+
+fn distance_cache_path(cache_dir: &Path, dist: &ShortestPaths) -> PathBuf {
+    cache_dir.join(format!("{:016x}.apsp", dist.cache_key()))
+}
+
+/// Load navigable distances saved by an earlier run over the same graph,
+/// if there are any.
+fn load_distance_cache(cache_dir: &Path, dist: &ShortestPaths) {
+    let path = distance_cache_path(cache_dir, dist);
+    if let Ok(file) = File::open(&path) {
+        match dist.read_rows(&mut BufReader::new(file)) {
+            Ok(()) => debug!("Loaded navigable distances from {}", path.display()),
+            Err(err) => warn!("Ignoring {}: {}", path.display(), err),
+        }
+    }
+}
+
+fn save_distance_cache(cache_dir: &Path, dist: &ShortestPaths) -> Result<()> {
+    create_dir_all(cache_dir)?;
+    let path = distance_cache_path(cache_dir, dist);
+    let mut body = Vec::new();
+    dist.write_rows(&mut body)?;
+    write_atomically(&path, &body)?;
+    debug!("Saved navigable distances to {}", path.display());
+    Ok(())
+}
+
+/// The result of running a TradeModel over a Galaxy: the galaxy's worlds
+/// with their trade routes and credits filled in, and the navigable
+/// distances between them.
+#[derive(Clone, Debug)]
+pub struct TradeMap {
+    galaxy: Galaxy,
+    model: TradeModel,
+    dists: HashMap<u64, ShortestPaths>,
+}
+
+impl TradeMap {
+    pub fn galaxy(&self) -> &Galaxy {
+        &self.galaxy
+    }
+
+    pub fn model(&self) -> &TradeModel {
+        &self.model
+    }
+
+    fn dist(&self) -> &ShortestPaths {
+        self.dists.get(&self.model.max_max_jump()).unwrap()
+    }
+
+    /// Return the shortest paths between worlds for the longest jump any
+    /// route may use.  Nodes are worlds in sorted coordinate order.
+    pub fn shortest_paths(&self) -> &ShortestPaths {
+        self.dist()
+    }
+
+    /// Return the shortest navigable distance in parsecs between two
+    /// worlds, or None if there is no way between them.
+    pub fn distance(&self, world1: &World, world2: &World) -> Option<u32> {
+        let distance = world1.navigable_distance(world2, self.dist());
+        if distance == INFINITY {
+            None
+        } else {
+            Some(distance)
+        }
+    }
+
+    /// Return the worlds along the shortest navigable path between two
+    /// worlds, including both ends, or None if there is no way between them.
+    pub fn path(&self, world1: &World, world2: &World) -> Option<Vec<&World>> {
+        let mut sorted_coords: Vec<Coords> = self.galaxy.coords_to_world.keys().cloned().collect();
+        sorted_coords.sort();
+        let path = world1.navigable_path(world2, &sorted_coords, self.dist())?;
+        Some(
+            path.iter()
+                .map(|coords| self.galaxy.coords_to_world.get(coords).unwrap())
+                .collect(),
+        )
+    }
+
+    /// Return the bilateral trade number between two worlds.
+    pub fn btn(&self, world1: &World, world2: &World) -> f64 {
+        world1.btn(
+            world2,
+            self.dist(),
+            self.model.passenger,
+            self.model.rules.as_ref(),
+        )
+    }
+
+    /// Return each trade route that touches world, with the world at its
+    /// other end, biggest routes first.
+    pub fn routes(&self, world: &World) -> Vec<(Route, &World)> {
+        let mut routes = Vec::new();
+        for (route, coords_set) in [
+            (Major, &world.major_routes),
+            (Main, &world.main_routes),
+            (Intermediate, &world.intermediate_routes),
+            (Feeder, &world.feeder_routes),
+            (Minor, &world.minor_routes),
+        ] {
+            let mut sorted_coords: Vec<&Coords> = coords_set.iter().collect();
+            sorted_coords.sort();
+            for coords in sorted_coords {
+                routes.push((route, self.galaxy.coords_to_world.get(coords).unwrap()));
+            }
+        }
+        routes
+    }
+
+    /// Return the number of trade routes of each kind, counting each jump
+    /// between two worlds as one route.
+    pub fn route_counts(&self) -> RouteCounter {
+        let mut route_counts = RouteCounter::new();
+        for route in [Major, Main, Intermediate, Feeder, Minor] {
+            route_counts.insert(route, 0);
+        }
+        for world in self.galaxy.worlds() {
+            for (route, other) in self.routes(world) {
+                if world.get_coords() < other.get_coords() {
+                    *route_counts.get_mut(&route).unwrap() += 1;
+                }
+            }
+        }
+        route_counts
+    }
+
+    /// Write a text file for each sector to output_dir, listing the BTN
+    /// between each world and each of its neighbors.
+    pub fn write_text_btns(&self, output_dir: &Path) -> Result<()> {
+        generate_text_btns(
+            output_dir,
+            &self.galaxy.location_to_sector,
+            &self.galaxy.coords_to_world,
+            self.model.passenger,
+            self.model.max_max_jump(),
+            self.model.ignore_xboat_routes,
+            &self.dists,
+            self.model.rules.as_ref(),
+        )
+    }
+
+    /// Draw a PDF map of each sector, with its trade routes, to output_dir.
+    pub fn write_pdfs(&self, output_dir: &Path) {
+        generate_pdfs(
+            output_dir,
+            &self.galaxy.location_to_sector,
+            &self.galaxy.coords_to_world,
+            self.model.rules.as_ref(),
+        );
+    }
+}
@@ -0,0 +1,432 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// One sector in the universe index.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UniverseSector {
+    pub location: (i64, i64),
+    pub names: Vec<String>,
+    pub abbreviation: String,
+}
+
+impl UniverseSector {
+    /// Return the canonical name, which is what sector data files are
+    /// named for.
+    pub fn name(&self) -> &str {
+        &self.names[0]
+    }
+}
+
+/// Index of all known sectors and their locations, as served by
+/// travellermap.com's universe API.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Universe {
+    sectors: Vec<UniverseSector>,
+}
+
+impl Universe {
+    /// Parse the JSON from the universe API.  Sectors without names are
+    /// ignored, since we can't download them.
+    pub fn parse(json: &str) -> Result<Universe> {
+        let root: Value = serde_json::from_str(json)?;
+        let sector_values = root["Sectors"]
+            .as_array()
+            .ok_or_else(|| anyhow!("No Sectors in universe"))?;
+        let mut sectors = Vec::new();
+        for sector_value in sector_values {
+            let x = sector_value["X"]
+                .as_i64()
+                .ok_or_else(|| anyhow!("Universe sector without X"))?;
+            let y = sector_value["Y"]
+                .as_i64()
+                .ok_or_else(|| anyhow!("Universe sector without Y"))?;
+            let mut names = Vec::new();
+            if let Some(name_values) = sector_value["Names"].as_array() {
+                for name_value in name_values {
+                    if let Some(name) = name_value["Text"].as_str() {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+            if names.is_empty() {
+                continue;
+            }
+            let abbreviation = sector_value["Abbreviation"]
+                .as_str()
+                .unwrap_or("")
+                .to_string();
+            sectors.push(UniverseSector {
+                location: (x, y),
+                names,
+                abbreviation,
+            });
+        }
+        sectors.sort_by_key(|sector| sector.location);
+        Ok(Universe { sectors })
+    }
+
+    pub fn sectors(&self) -> &[UniverseSector] {
+        &self.sectors
+    }
+
+    /// Find the sector that a user means by name, which may be its canonical
+    /// name, an alternate name, or its abbreviation, in any case.
+    ///
+    /// If that matches more than one sector, or none, the error lists the
+    /// likely candidates.
+    pub fn resolve(&self, name: &str) -> Result<&UniverseSector> {
+        if let Some(sector) = self.sectors.iter().find(|sector| sector.name() == name) {
+            return Ok(sector);
+        }
+        let lower_name = name.to_lowercase();
+        let matches: Vec<&UniverseSector> = self
+            .sectors
+            .iter()
+            .filter(|sector| {
+                sector.abbreviation.to_lowercase() == lower_name
+                    || sector
+                        .names
+                        .iter()
+                        .any(|name2| name2.to_lowercase() == lower_name)
+            })
+            .collect();
+        match matches.len() {
+            1 => Ok(matches[0]),
+            0 => {
+                let suggestions = self.suggest(&lower_name);
+                if suggestions.is_empty() {
+                    bail!("Unknown sector '{}'", name);
+                } else {
+                    bail!(
+                        "Unknown sector '{}'; did you mean {}?",
+                        name,
+                        quote_names(&suggestions)
+                    );
+                }
+            }
+            _ => {
+                let names: Vec<&str> = matches.iter().map(|sector| sector.name()).collect();
+                bail!(
+                    "Ambiguous sector '{}'; did you mean {}?",
+                    name,
+                    quote_names(&names)
+                );
+            }
+        }
+    }
+
+    /// Return the canonical names of up to MAX_SUGGESTIONS sectors with a
+    /// name or abbreviation close to lower_name, closest first.
+    fn suggest(&self, lower_name: &str) -> Vec<&str> {
+        let max_distance = (lower_name.chars().count() / 3).max(1);
+        let mut distance_names: Vec<(usize, &str)> = Vec::new();
+        for sector in self.sectors.iter() {
+            let distance = sector
+                .names
+                .iter()
+                .chain([&sector.abbreviation])
+                .filter(|name| !name.is_empty())
+                .map(|name| edit_distance(&name.to_lowercase(), lower_name))
+                .min()
+                .unwrap();
+            if distance <= max_distance {
+                distance_names.push((distance, sector.name()));
+            }
+        }
+        distance_names.sort();
+        distance_names
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    /// Return the names of all sectors in region, in location order.
+    pub fn sectors_in_region(&self, region: &SectorRegion) -> Vec<String> {
+        self.sectors
+            .iter()
+            .filter(|sector| region.contains(sector.location))
+            .map(|sector| sector.name().to_string())
+            .collect()
+    }
+
+    /// Return the names of all sectors within around.radius sectors of
+    /// around.sector_name, in location order.
+    pub fn sectors_around(&self, around: &SectorsAround) -> Result<Vec<String>> {
+        let (x, y) = self.resolve(&around.sector_name)?.location;
+        let region = SectorRegion {
+            min_x: x - around.radius,
+            max_x: x + around.radius,
+            min_y: y - around.radius,
+            max_y: y + around.radius,
+        };
+        Ok(self.sectors_in_region(&region))
+    }
+}
+
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Return names like "'A', 'B' or 'C'".
+fn quote_names(names: &[&str]) -> String {
+    let quoted: Vec<String> = names.iter().map(|name| format!("'{}'", name)).collect();
+    match quoted.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+        _ => quoted.join(""),
+    }
+}
+
+/// Return the Levenshtein distance between two strings, in chars.
+fn edit_distance(st1: &str, st2: &str) -> usize {
+    let chars2: Vec<char> = st2.chars().collect();
+    let mut prev_row: Vec<usize> = (0..=chars2.len()).collect();
+    for (ii, ch1) in st1.chars().enumerate() {
+        let mut row = vec![ii + 1];
+        for (jj, ch2) in chars2.iter().enumerate() {
+            let substitution = prev_row[jj] + usize::from(ch1 != *ch2);
+            let insertion = row[jj] + 1;
+            let deletion = prev_row[jj + 1] + 1;
+            row.push(substitution.min(insertion).min(deletion));
+        }
+        prev_row = row;
+    }
+    prev_row[chars2.len()]
+}
+
+/// A rectangle of sector locations, inclusive, written like "-4..0,-2..2"
+/// for X from -4 to 0 and Y from -2 to 2.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SectorRegion {
+    pub min_x: i64,
+    pub max_x: i64,
+    pub min_y: i64,
+    pub max_y: i64,
+}
+
+impl SectorRegion {
+    pub fn contains(&self, location: (i64, i64)) -> bool {
+        (self.min_x..=self.max_x).contains(&location.0)
+            && (self.min_y..=self.max_y).contains(&location.1)
+    }
+}
+
+fn parse_range(st: &str) -> Result<(i64, i64)> {
+    let (min, max) = match st.split_once("..") {
+        Some((min, max)) => (min.trim().parse()?, max.trim().parse()?),
+        None => {
+            let val = st.trim().parse()?;
+            (val, val)
+        }
+    };
+    if min > max {
+        bail!("Empty range {}", st);
+    }
+    Ok((min, max))
+}
+
+impl FromStr for SectorRegion {
+    type Err = anyhow::Error;
+
+    fn from_str(st: &str) -> Result<SectorRegion> {
+        let (x_range, y_range) = st
+            .split_once(',')
+            .ok_or_else(|| anyhow!("Expected a region like '-4..0,-2..2' but got '{}'", st))?;
+        let (min_x, max_x) = parse_range(x_range)?;
+        let (min_y, max_y) = parse_range(y_range)?;
+        Ok(SectorRegion {
+            min_x,
+            max_x,
+            min_y,
+            max_y,
+        })
+    }
+}
+
+/// A named sector and every sector within radius sectors of it in X and Y,
+/// written like "Core:2".  The radius defaults to 1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SectorsAround {
+    pub sector_name: String,
+    pub radius: i64,
+}
+
+impl FromStr for SectorsAround {
+    type Err = anyhow::Error;
+
+    fn from_str(st: &str) -> Result<SectorsAround> {
+        let (sector_name, radius) = match st.rsplit_once(':') {
+            Some((sector_name, radius)) => (sector_name, radius.trim().parse()?),
+            None => (st, 1),
+        };
+        if radius < 0 {
+            bail!("Negative radius in '{}'", st);
+        }
+        Ok(SectorsAround {
+            sector_name: sector_name.trim().to_string(),
+            radius,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read_to_string;
+
+    const UNIVERSE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test_data/universe.json");
+
+    fn universe() -> Universe {
+        Universe::parse(&read_to_string(UNIVERSE_PATH).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_universe() {
+        let universe = universe();
+        // The nameless sector is left out.
+        assert_eq!(universe.sectors().len(), 9);
+        let spin = universe.resolve("Spinward Marches").unwrap();
+        assert_eq!(spin.location, (-4, -1));
+        assert_eq!(spin.names, vec!["Spinward Marches", "Tloql"]);
+        assert_eq!(spin.abbreviation, "Spin");
+
+        assert!(Universe::parse("{}").is_err());
+        assert!(Universe::parse("not json").is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let universe = universe();
+        for name in [
+            "Spinward Marches",
+            "spinward marches",
+            "Spin",
+            "SPIN",
+            "Tloql",
+        ] {
+            assert_eq!(universe.resolve(name).unwrap().name(), "Spinward Marches");
+        }
+        assert_eq!(
+            universe.resolve("campo de pruebas").unwrap().name(),
+            "Proving Ground"
+        );
+        assert_eq!(
+            universe.resolve("Spinward Marsh").unwrap_err().to_string(),
+            "Unknown sector 'Spinward Marsh'; did you mean 'Spinward Marches'?"
+        );
+        assert_eq!(
+            universe.resolve("Proving Grounds").unwrap_err().to_string(),
+            "Unknown sector 'Proving Grounds'; did you mean 'Proving Ground'?"
+        );
+        assert_eq!(
+            universe.resolve("Nowhere").unwrap_err().to_string(),
+            "Unknown sector 'Nowhere'"
+        );
+    }
+
+    #[test]
+    fn test_resolve_ambiguous() {
+        let universe = Universe::parse(
+            r#"{"Sectors":[
+            {"X":0,"Y":0,"Abbreviation":"Fore","Names":[{"Text":"Foreven"}]},
+            {"X":1,"Y":0,"Abbreviation":"Fore","Names":[{"Text":"Forever"}]},
+            {"X":2,"Y":0,"Abbreviation":"Forx","Names":[{"Text":"Forex"}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            universe.resolve("fore").unwrap_err().to_string(),
+            "Ambiguous sector 'fore'; did you mean 'Foreven' or 'Forever'?"
+        );
+        assert_eq!(
+            universe.resolve("Forevr").unwrap_err().to_string(),
+            "Unknown sector 'Forevr'; did you mean 'Forever', 'Foreven' or 'Forex'?"
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("spin", ""), 4);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("deneb", "deneb"), 0);
+    }
+
+    #[test]
+    fn test_parse_sector_region() {
+        let region: SectorRegion = "-4..0,-2..2".parse().unwrap();
+        assert_eq!(
+            region,
+            SectorRegion {
+                min_x: -4,
+                max_x: 0,
+                min_y: -2,
+                max_y: 2
+            }
+        );
+        let region: SectorRegion = "1, -1..0".parse().unwrap();
+        assert_eq!(
+            region,
+            SectorRegion {
+                min_x: 1,
+                max_x: 1,
+                min_y: -1,
+                max_y: 0
+            }
+        );
+        assert!("-4..0".parse::<SectorRegion>().is_err());
+        assert!("0..-4,0..1".parse::<SectorRegion>().is_err());
+        assert!("a..b,0..1".parse::<SectorRegion>().is_err());
+    }
+
+    #[test]
+    fn test_parse_sectors_around() {
+        let around: SectorsAround = "Core:2".parse().unwrap();
+        assert_eq!(around.sector_name, "Core");
+        assert_eq!(around.radius, 2);
+        let around: SectorsAround = "Spinward Marches".parse().unwrap();
+        assert_eq!(around.sector_name, "Spinward Marches");
+        assert_eq!(around.radius, 1);
+        assert!("Core:-1".parse::<SectorsAround>().is_err());
+        assert!("Core:far".parse::<SectorsAround>().is_err());
+    }
+
+    #[test]
+    fn test_sectors_in_region() {
+        let universe = universe();
+        let region: SectorRegion = "-4..-3,-1..0".parse().unwrap();
+        assert_eq!(
+            universe.sectors_in_region(&region),
+            vec!["Spinward Marches", "Trojan Reach", "Deneb", "Reft"]
+        );
+        let region: SectorRegion = "10..12,10..12".parse().unwrap();
+        assert!(universe.sectors_in_region(&region).is_empty());
+    }
+
+    #[test]
+    fn test_sectors_around() {
+        let universe = universe();
+        let around: SectorsAround = "Proving Ground:1".parse().unwrap();
+        assert_eq!(
+            universe.sectors_around(&around).unwrap(),
+            vec![
+                "Stepping Stone",
+                "Proving Ground",
+                "Underfoot",
+                "Mockingbird"
+            ]
+        );
+        let around: SectorsAround = "Proving Ground:0".parse().unwrap();
+        assert_eq!(
+            universe.sectors_around(&around).unwrap(),
+            vec!["Proving Ground"]
+        );
+        let around: SectorsAround = "Proving Ground:2".parse().unwrap();
+        assert_eq!(universe.sectors_around(&around).unwrap().len(), 5);
+        let around: SectorsAround = "prov:0".parse().unwrap();
+        assert_eq!(
+            universe.sectors_around(&around).unwrap(),
+            vec!["Proving Ground"]
+        );
+        let around: SectorsAround = "Nowhere:2".parse().unwrap();
+        assert!(universe.sectors_around(&around).is_err());
+    }
+}